@@ -4,6 +4,10 @@
 
 /// user app's stack size
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// the virtual addr where the user heap (program break) starts
+pub const USER_HEAP_BASE: usize = 0x1_0000_0000;
+/// the virtual addr above which mmap places mappings without a usable hint
+pub const MMAP_BASE: usize = 0x20_0000_0000;
 /// kernel stack size
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 /// kernel heap size
@@ -17,14 +21,22 @@ pub const PAGE_SIZE_BITS: usize = 0xc;
 pub const MAX_SYSCALL_NUM: usize = 500;
 /// the virtual addr of trapoline
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
+/// the virtual addr of the user-visible sigreturn trampoline
+pub const SIGRETURN_TRAMPOLINE: usize = TRAMPOLINE - PAGE_SIZE;
 /// the virtual addr of trap context
-pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
+pub const TRAP_CONTEXT_BASE: usize = SIGRETURN_TRAMPOLINE - PAGE_SIZE;
+/// the largest number of harts the kernel runs on
+pub const MAX_HARTS: usize = 8;
 /// clock frequency
 pub const CLOCK_FREQ: usize = 12500000;
 /// the physical memory end
 pub const MEMORY_END: usize = 0x88000000;
-/// The base address of control registers in Virtio_Block device
+/// the base address of the PLIC in the virt machine
+pub const VIRT_PLIC: usize = 0x0c00_0000;
+/// The base address of control registers in the PLIC, UART and Virtio_Block devices
 pub const MMIO: &[(usize, usize)] = &[
+    (VIRT_PLIC, 0x40_0000),
+    (0x10000000, 0x1000),
     (0x10001000, 0x1000),
     (0x10002000, 0x1000),
 ];
\ No newline at end of file
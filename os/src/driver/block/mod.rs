@@ -17,6 +17,8 @@ type BlockDeviceImpl = virtio_blk::VirtIOBlock;
 /// The base address of control registers in Virtio_Block device
 #[allow(unused)]
 const VIRTIO0: usize = 0x10001000;
+/// The PLIC interrupt source of the Virtio_Block device at VIRTIO0
+pub const VIRTIO0_IRQ: usize = 1;
 #[allow(unused)]
 const VIRTIO1: usize = 0x10002000;
 #[allow(unused)]
@@ -3,36 +3,57 @@ use crate::mem::{
     frame_alloc, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
     StepByOne, VirtAddr,
 };
-use crate::sync::UPSafeCell;
+use crate::process::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use crate::sync::{may_sleep, SpinNoIrqGuard, SpinNoIrqLock};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use lazy_static::*;
-use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
+use virtio_drivers::{BlkResp, Hal, RespStatus, VirtIOBlk, VirtIOHeader};
 
 /// VirtIOBlock device driver structure for virtio_blk device
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtioHal>>);
+pub struct VirtIOBlock(SpinNoIrqLock<VirtIOBlockInner>);
+
+struct VirtIOBlockInner {
+    blk: VirtIOBlk<'static, VirtioHal>,
+    /// tasks sleeping on an in-flight request, keyed by its descriptor token
+    waiters: BTreeMap<u16, Arc<TaskControlBlock>>,
+}
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: SpinNoIrqLock<Vec<FrameTracker>> = SpinNoIrqLock::new(Vec::new());
 }
 
 impl BlockDevice for VirtIOBlock {
     
     fn read_block(&self, block_id: usize, buf: &mut [u8]) -> bool {
-        match self.0
-            .exclusive_access()
-            .read_block(block_id, buf) {
-                Ok(_) => true,
-                Err(_) => false,
-            }
+        let Some(task) = current_task().filter(|_| may_sleep()) else {
+            // nobody to put to sleep before the scheduler runs, or the caller
+            // holds a spinlock which others would spin on until we are back
+            return self.0.lock().blk.read_block(block_id, buf).is_ok();
+        };
+        let mut resp = BlkResp::default();
+        let mut inner = self.0.lock();
+        let token = match unsafe { inner.blk.read_block_nb(block_id, buf, &mut resp) } {
+            Ok(token) => token,
+            Err(_) => return false,
+        };
+        self.wait_for(inner, token, task);
+        resp.status() == RespStatus::Ok
     }
 
     fn write_block(&self, block_id: usize, buf: &[u8]) -> bool {
-        match self.0
-            .exclusive_access()
-            .write_block(block_id, buf) {
-                Ok(_) => true,
-                Err(_) => false,
-            }
+        let Some(task) = current_task().filter(|_| may_sleep()) else {
+            return self.0.lock().blk.write_block(block_id, buf).is_ok();
+        };
+        let mut resp = BlkResp::default();
+        let mut inner = self.0.lock();
+        let token = match unsafe { inner.blk.write_block_nb(block_id, buf, &mut resp) } {
+            Ok(token) => token,
+            Err(_) => return false,
+        };
+        self.wait_for(inner, token, task);
+        resp.status() == RespStatus::Ok
     }
     
     fn block_count(&self) -> usize {
@@ -46,6 +67,16 @@ impl BlockDevice for VirtIOBlock {
         let config = unsafe { & *(header.config_space() as *const BlkConfig) };
         config.blk_size.read() as usize
     }
+
+    fn handle_irq(&self) {
+        let mut inner = self.0.lock();
+        inner.blk.ack_interrupt();
+        while let Ok(token) = inner.blk.pop_used() {
+            if let Some(task) = inner.waiters.remove(&token) {
+                wakeup_task(task);
+            }
+        }
+    }
     
 }
 
@@ -53,9 +84,27 @@ impl VirtIOBlock {
     /// Create a new VirtIOBlock driver with VIRTIO0 base_addr for virtio_blk device
     pub fn new() -> Self {
         unsafe {
-            Self(UPSafeCell::new(
-                VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
-            ))
+            Self(SpinNoIrqLock::new(VirtIOBlockInner {
+                blk: VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
+                waiters: BTreeMap::new(),
+            }))
+        }
+    }
+
+    /// Sleep until the interrupt handler has seen the request `token` complete.
+    /// The waiter is registered before the device lock is released, so the
+    /// completion cannot slip past us.
+    fn wait_for(
+        &self,
+        mut inner: SpinNoIrqGuard<'_, VirtIOBlockInner>,
+        token: u16,
+        task: Arc<TaskControlBlock>,
+    ) {
+        inner.waiters.insert(token, task);
+        drop(inner);
+        // other wakeups may cut the sleep short, the request decides when we are done
+        while self.0.lock().waiters.contains_key(&token) {
+            block_current_and_run_next();
         }
     }
 }
@@ -71,7 +120,7 @@ impl Hal for VirtioHal {
                 ppn_base = frame.ppn;
             }
             assert_eq!(frame.ppn.0, ppn_base.0 + i);
-            QUEUE_FRAMES.exclusive_access().push(frame);
+            QUEUE_FRAMES.lock().push(frame);
         }
         let pa: PhysAddr = ppn_base.into();
         pa.0
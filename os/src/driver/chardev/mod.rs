@@ -0,0 +1,29 @@
+//! character devices, the serial console
+mod ns16550a;
+
+pub use ns16550a::NS16550a;
+
+/// The base address of control registers in the UART of the virt machine
+const UART0: usize = 0x1000_0000;
+/// The PLIC interrupt source of the UART at UART0
+pub const UART0_IRQ: usize = 10;
+
+/// A device transferring one byte at a time
+pub trait CharDevice {
+    /// Set the device up, interrupts included
+    fn init(&self);
+    /// Read a byte, sleeping until one arrives
+    #[allow(unused)]
+    fn read(&self) -> u8;
+    /// Read a byte if one has arrived
+    fn try_read(&self) -> Option<u8>;
+    /// Write a byte
+    fn write(&self, ch: u8);
+    /// Take the received bytes from the device, called from its interrupt
+    fn handle_irq(&self);
+}
+
+type CharDeviceImpl = NS16550a<UART0>;
+
+/// The console UART, usable before the heap and paging are set up
+pub static UART: CharDeviceImpl = CharDeviceImpl::new();
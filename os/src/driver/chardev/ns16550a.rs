@@ -0,0 +1,106 @@
+//! ns16550a UART, the serial port of the QEMU virt machine
+
+use super::CharDevice;
+use crate::sync::{SpinNoIrqLock, WaitQueue};
+use alloc::collections::VecDeque;
+
+/// Received bytes nobody has read yet are dropped beyond this
+const READ_BUFFER_SIZE: usize = 1024;
+
+// register offsets
+const RBR_THR: usize = 0; // receive buffer / transmit holding, DLL with DLAB
+const IER: usize = 1; // interrupt enable, DLM with DLAB
+const FCR: usize = 2; // fifo control
+const LCR: usize = 3; // line control
+const MCR: usize = 4; // modem control
+const LSR: usize = 5; // line status
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const FCR_ENABLE_AND_CLEAR: u8 = 0b111;
+const LCR_EIGHT_BITS: u8 = 0b11;
+const LCR_DLAB: u8 = 1 << 7;
+/// DTR | RTS | OUT2, OUT2 gates the interrupt line on the 16550
+const MCR_READY: u8 = 0b1011;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+/// An ns16550a whose registers start at `BASE_ADDR`
+pub struct NS16550a<const BASE_ADDR: usize> {
+    /// received bytes, filled by the interrupt handler
+    read_buffer: SpinNoIrqLock<VecDeque<u8>>,
+    /// readers waiting for `read_buffer` to fill
+    readers: WaitQueue,
+    /// keeps the transmitter to one hart at a time
+    tx: SpinNoIrqLock<()>,
+}
+
+impl<const BASE_ADDR: usize> NS16550a<BASE_ADDR> {
+    /// Create the driver, the device is set up by [`CharDevice::init`]
+    pub const fn new() -> Self {
+        Self {
+            read_buffer: SpinNoIrqLock::new(VecDeque::new()),
+            readers: WaitQueue::new(),
+            tx: SpinNoIrqLock::new(()),
+        }
+    }
+
+    fn reg_read(&self, offset: usize) -> u8 {
+        unsafe { ((BASE_ADDR + offset) as *const u8).read_volatile() }
+    }
+
+    fn reg_write(&self, offset: usize, value: u8) {
+        unsafe { ((BASE_ADDR + offset) as *mut u8).write_volatile(value) }
+    }
+}
+
+impl<const BASE_ADDR: usize> CharDevice for NS16550a<BASE_ADDR> {
+    fn init(&self) {
+        self.reg_write(IER, 0);
+        // the divisor is ignored by QEMU but real 16550s want one, 38400 baud
+        self.reg_write(LCR, LCR_DLAB);
+        self.reg_write(RBR_THR, 3);
+        self.reg_write(IER, 0);
+        self.reg_write(LCR, LCR_EIGHT_BITS);
+        self.reg_write(FCR, FCR_ENABLE_AND_CLEAR);
+        self.reg_write(MCR, MCR_READY);
+        self.reg_write(IER, IER_RX_AVAILABLE);
+    }
+
+    fn read(&self) -> u8 {
+        loop {
+            let mut read_buffer = self.read_buffer.lock();
+            if let Some(ch) = read_buffer.pop_front() {
+                return ch;
+            }
+            self.readers.wait_unlock(read_buffer);
+        }
+    }
+
+    fn try_read(&self) -> Option<u8> {
+        self.read_buffer.lock().pop_front()
+    }
+
+    fn write(&self, ch: u8) {
+        let _tx = self.tx.lock();
+        while self.reg_read(LSR) & LSR_THR_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        self.reg_write(RBR_THR, ch);
+    }
+
+    fn handle_irq(&self) {
+        let mut received = false;
+        let mut read_buffer = self.read_buffer.lock();
+        while self.reg_read(LSR) & LSR_DATA_READY != 0 {
+            let ch = self.reg_read(RBR_THR);
+            if read_buffer.len() < READ_BUFFER_SIZE {
+                read_buffer.push_back(ch);
+                received = true;
+            }
+        }
+        drop(read_buffer);
+        if received {
+            self.readers.notify_all();
+        }
+    }
+}
@@ -1,20 +1,33 @@
-use crate::sbi::console_putchar;
+use super::chardev::{CharDevice, UART};
+use crate::sync::SpinNoIrqLock;
 use core::fmt::{self, Write};
 
+/// Keeps the lines printed by different harts apart
+static STDOUT: SpinNoIrqLock<()> = SpinNoIrqLock::new(());
+
 struct Stdout;
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        for ch in s.bytes() {
+            UART.write(ch);
         }
         Ok(())
     }
 }
 
 pub fn print(args: fmt::Arguments) {
+    let _stdout = STDOUT.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Print raw bytes, which need not be UTF-8
+pub fn print_bytes(bytes: &[u8]) {
+    let _stdout = STDOUT.lock();
+    for &ch in bytes {
+        UART.write(ch);
+    }
+}
+
 #[macro_export]
 /// print macro implmentation in core
 macro_rules! print {
@@ -1,5 +1,55 @@
 #[macro_use]
 pub mod console;
 pub mod block;
+pub mod chardev;
+pub mod plic;
 #[allow(unused)]
 pub use block::{block_device_test, gpu_test};
+
+use crate::config::VIRT_PLIC;
+use crate::process::hart_id;
+use block::{BLOCK_DEVICE, VIRTIO0_IRQ};
+use chardev::{CharDevice, UART, UART0_IRQ};
+use crate::vfs::CONSOLE;
+use plic::{IntrTargetPriority, PLIC};
+use riscv::register::sie;
+
+/// Set up the devices which work without the heap, the console first
+pub fn init() {
+    UART.init();
+}
+
+/// Route device interrupts to the supervisor context of this hart
+pub fn init_irq(hart_id: usize) {
+    let plic = PLIC::new(VIRT_PLIC);
+    let supervisor = IntrTargetPriority::Supervisor;
+    plic.set_threshold(hart_id, supervisor, 0);
+    for intr_src_id in [VIRTIO0_IRQ, UART0_IRQ] {
+        plic.enable(hart_id, supervisor, intr_src_id);
+        plic.set_priority(intr_src_id, 1);
+    }
+    unsafe {
+        sie::set_sext();
+    }
+}
+
+/// Serve one supervisor external interrupt
+pub fn irq_handler() {
+    let plic = PLIC::new(VIRT_PLIC);
+    let hart_id = hart_id();
+    let supervisor = IntrTargetPriority::Supervisor;
+    let intr_src_id = plic.claim(hart_id, supervisor);
+    match intr_src_id as usize {
+        // every hart is interrupted, one of the others claimed it first
+        0 => return,
+        VIRTIO0_IRQ => BLOCK_DEVICE.handle_irq(),
+        UART0_IRQ => {
+            UART.handle_irq();
+            while let Some(ch) = UART.try_read() {
+                CONSOLE.receive(ch);
+            }
+        }
+        _ => warn!("[kernel] unexpected external interrupt {}", intr_src_id),
+    }
+    plic.complete(hart_id, supervisor, intr_src_id);
+}
@@ -0,0 +1,106 @@
+//! Platform-Level Interrupt Controller
+//!
+//! Every hart has a machine and a supervisor context. A context receives the
+//! sources enabled for it whose priority exceeds its threshold, claims one
+//! source at a time and reports completion when the device has been served.
+
+/// The privilege level a PLIC context delivers interrupts to
+#[derive(Copy, Clone)]
+#[allow(unused)]
+pub enum IntrTargetPriority {
+    /// M-mode, owned by the SBI firmware
+    Machine = 0,
+    /// S-mode, owned by the kernel
+    Supervisor = 1,
+}
+
+/// A PLIC mapped at `base_addr`
+#[allow(clippy::upper_case_acronyms)]
+pub struct PLIC {
+    base_addr: usize,
+}
+
+impl PLIC {
+    /// Access the PLIC whose registers start at `base_addr`
+    pub const fn new(base_addr: usize) -> Self {
+        Self { base_addr }
+    }
+
+    fn context_id(hart_id: usize, target_priority: IntrTargetPriority) -> usize {
+        hart_id * 2 + target_priority as usize
+    }
+
+    fn priority_ptr(&self, intr_source_id: usize) -> *mut u32 {
+        assert!(intr_source_id > 0 && intr_source_id <= 132);
+        (self.base_addr + intr_source_id * 4) as *mut u32
+    }
+
+    fn enable_ptr(
+        &self,
+        hart_id: usize,
+        target_priority: IntrTargetPriority,
+        intr_source_id: usize,
+    ) -> (*mut u32, usize) {
+        let id = Self::context_id(hart_id, target_priority);
+        let (reg_id, reg_shift) = (intr_source_id / 32, intr_source_id % 32);
+        (
+            (self.base_addr + 0x2000 + 0x80 * id + 0x4 * reg_id) as *mut u32,
+            reg_shift,
+        )
+    }
+
+    fn threshold_ptr(&self, hart_id: usize, target_priority: IntrTargetPriority) -> *mut u32 {
+        let id = Self::context_id(hart_id, target_priority);
+        (self.base_addr + 0x20_0000 + 0x1000 * id) as *mut u32
+    }
+
+    fn claim_complete_ptr(&self, hart_id: usize, target_priority: IntrTargetPriority) -> *mut u32 {
+        let id = Self::context_id(hart_id, target_priority);
+        (self.base_addr + 0x20_0004 + 0x1000 * id) as *mut u32
+    }
+
+    /// Set the priority of an interrupt source, 0 masks it for every context
+    pub fn set_priority(&self, intr_source_id: usize, priority: u32) {
+        assert!(priority < 8);
+        unsafe {
+            self.priority_ptr(intr_source_id).write_volatile(priority);
+        }
+    }
+
+    /// Let a context receive an interrupt source
+    pub fn enable(&self, hart_id: usize, target_priority: IntrTargetPriority, intr_source_id: usize) {
+        let (reg_ptr, shift) = self.enable_ptr(hart_id, target_priority, intr_source_id);
+        unsafe {
+            reg_ptr.write_volatile(reg_ptr.read_volatile() | 1 << shift);
+        }
+    }
+
+    /// Stop a context from receiving an interrupt source
+    #[allow(unused)]
+    pub fn disable(&self, hart_id: usize, target_priority: IntrTargetPriority, intr_source_id: usize) {
+        let (reg_ptr, shift) = self.enable_ptr(hart_id, target_priority, intr_source_id);
+        unsafe {
+            reg_ptr.write_volatile(reg_ptr.read_volatile() & !(1u32 << shift));
+        }
+    }
+
+    /// A context only receives sources with a priority above its threshold
+    pub fn set_threshold(&self, hart_id: usize, target_priority: IntrTargetPriority, threshold: u32) {
+        assert!(threshold < 8);
+        unsafe {
+            self.threshold_ptr(hart_id, target_priority).write_volatile(threshold);
+        }
+    }
+
+    /// Claim the highest priority pending source, 0 if another context was faster
+    pub fn claim(&self, hart_id: usize, target_priority: IntrTargetPriority) -> u32 {
+        unsafe { self.claim_complete_ptr(hart_id, target_priority).read_volatile() }
+    }
+
+    /// Signal that a claimed source has been served
+    pub fn complete(&self, hart_id: usize, target_priority: IntrTargetPriority, completion: u32) {
+        unsafe {
+            self.claim_complete_ptr(hart_id, target_priority).write_volatile(completion);
+        }
+    }
+}
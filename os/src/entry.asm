@@ -1,11 +1,28 @@
     .section .text.entry
     .globl _start
 _start:
-    la sp, boot_stack_top
+    # a0 = hart id, kept in tp by the kernel
+    mv tp, a0
+    call set_boot_stack
     call rust_main
+
+    .globl _start_secondary
+_start_secondary:
+    # harts started through SBI HSM come here with a0 = hart id
+    mv tp, a0
+    call set_boot_stack
+    call rust_main_secondary
+
+set_boot_stack:
+    # each hart gets 64 KiB of the boot stack, counting down from the top
+    la sp, boot_stack_top
+    slli t0, tp, 16
+    sub sp, sp, t0
+    ret
+
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    .space 4096 * 16 * 8
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
@@ -7,7 +7,6 @@
 // #![feature(panic_info_message)]
 #![feature(alloc_error_handler)]
 #![feature(assert_matches)]
-#![feature(get_mut_unchecked)]
 
 #[macro_use]
 mod driver;
@@ -48,8 +47,9 @@ global_asm!(include_str!("link_app.S"));
 
 #[no_mangle]
 /// kernel enter point
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
     clear_bss();
+    driver::init();
     embassy::futures_test();
     mem::init();
     mem::remap_test();
@@ -64,13 +64,44 @@ pub fn rust_main() -> ! {
     println!("after initproc!");
     trap::init();
     trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    driver::init_irq(hart_id);
     time::set_next_trigger();
     // loader::list_apps();
     loader::list_bins();
+    start_secondary_harts(hart_id);
     process::run_tasks();
     panic!("Unreachable in rust_main!");
 }
 
+#[no_mangle]
+/// kernel enter point of the harts started by the boot hart
+pub fn rust_main_secondary(hart_id: usize) -> ! {
+    mem::init_hart();
+    trap::init();
+    trap::enable_timer_interrupt();
+    trap::enable_software_interrupt();
+    driver::init_irq(hart_id);
+    time::set_next_trigger();
+    println!("[kernel] hart {} started", hart_id);
+    process::run_tasks();
+    panic!("Unreachable in rust_main_secondary!");
+}
+
+/// Bring up the other harts through SBI HSM
+fn start_secondary_harts(boot_hart_id: usize) {
+    extern "C" {
+        fn _start_secondary();
+    }
+    assert!(boot_hart_id < config::MAX_HARTS, "hart id {} out of range", boot_hart_id);
+    for hart_id in (0..config::MAX_HARTS).filter(|&id| id != boot_hart_id) {
+        // the kernel runs identity mapped, so this is also the physical address
+        if sbi::hart_stopped(hart_id) && !sbi::hart_start(hart_id, _start_secondary as usize, 0) {
+            warn!("[kernel] failed to start hart {}", hart_id);
+        }
+    }
+}
+
 
 fn clear_bss() {
     extern "C" {
@@ -1,14 +1,19 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
 use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::{PTEFlags, PageTable, PageTableEntry, UserBuffer};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, MMIO};
-use crate::sync::UPSafeCell;
-use alloc::collections::BTreeMap;
+use crate::vfs::{queue_write_back, VfsNodeRef};
+use crate::config::{
+    MEMORY_END, MMAP_BASE, MMIO, PAGE_SIZE, SIGRETURN_TRAMPOLINE, TRAMPOLINE, USER_HEAP_BASE,
+};
+use crate::sync::SpinNoIrqLock;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch::asm;
+use core::mem::{size_of, MaybeUninit};
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -23,23 +28,68 @@ extern "C" {
     fn ebss();
     fn ekernel();
     fn strampoline();
+    fn ssigreturn();
+}
+
+/// End of the lower half of the SV39 address space, which holds all user mappings
+const USER_SPACE_END: usize = 1 << 38;
+
+/// A user pointer that user space itself is not allowed to access
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BadAddress;
+
+/// Why an image could not be loaded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ElfError {
+    /// not an ELF file the loader can map
+    BadElf,
+    /// no frame is left for its segments
+    OutOfMemory,
+}
+
+/// Why a user page fault could not be resolved
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PageFault {
+    /// no area allows the access
+    BadAccess,
+    /// no frame is left to back the page
+    OutOfMemory,
+    /// the page of a file mapping has to be read first, which cannot be done
+    /// with the address space locked, see [`MemorySet::take_unloaded`]
+    NotLoaded,
 }
 
 lazy_static! {
     /// The kernel's initial memory mapping(kernel address space)
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinNoIrqLock<MemorySet>> =
+        Arc::new(SpinNoIrqLock::new(MemorySet::new_kernel()));
 }
 
 /// the kernel token
 pub fn kernel_token() -> usize {
-    KERNEL_SPACE.exclusive_access().token()
+    KERNEL_SPACE.lock().token()
+}
+
+/// What the loader learns about an ELF image, handed to the program
+/// through the auxiliary vector
+#[derive(Copy, Clone, Debug)]
+pub struct ElfInfo {
+    /// entry point
+    pub entry: usize,
+    /// user address of the program headers
+    pub phdr: usize,
+    /// size of one program header
+    pub phent: usize,
+    /// number of program headers
+    pub phnum: usize,
 }
 
 /// address space
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    /// the page and access of the last fault that ended in [`PageFault::NotLoaded`]
+    unloaded: Option<(VirtAddr, MapPermission)>,
 }
 
 impl MemorySet {
@@ -48,23 +98,36 @@ impl MemorySet {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            unloaded: None,
         }
     }
     /// Get the page table token
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
-    /// Assume that no conflicts.
+    /// Assume that no conflicts. Fails if no frame is left to back it.
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
+    ) -> Result<(), PageFault> {
         self.push(
             MapArea::new(start_va, end_va, MapType::Framed, permission),
             None,
-        );
+        )
+    }
+    /// Insert a framed area whose frames are allocated on first access.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Framed, permission);
+        map_area.lazy = true;
+        // lazy areas take no frames up front
+        let _ = self.push(map_area, None);
     }
     /// remove a area
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
@@ -80,13 +143,24 @@ impl MemorySet {
     }
     /// Add a new MapArea into this MemorySet.
     /// Assuming that there are no conflicts in the virtual address
-    /// space.
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+    /// space. Fails, with nothing added, if no frame is left to back it.
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) -> Result<(), PageFault> {
+        self.push_with_offset(map_area, 0, data)
+    }
+    /// Like [`MemorySet::push`], but `data` starts `offset` bytes into the
+    /// first page of the area, as ELF segments with unaligned vaddr do.
+    fn push_with_offset(
+        &mut self,
+        mut map_area: MapArea,
+        offset: usize,
+        data: Option<&[u8]>,
+    ) -> Result<(), PageFault> {
+        map_area.map(&mut self.page_table)?;
         if let Some(data) = data {
-            map_area.copy_data(&mut self.page_table, data);
+            map_area.copy_data(&mut self.page_table, offset, data);
         }
         self.areas.push(map_area);
+        Ok(())
     }
     /// Mention that trampoline is not collected by areas.
     fn map_trampoline(&mut self) {
@@ -96,6 +170,15 @@ impl MemorySet {
             PTEFlags::R | PTEFlags::X,
         );
     }
+    /// Map the sigreturn trampoline, which signal handlers return into.
+    /// Like the trampoline, it is not collected by areas.
+    fn map_sigreturn_trampoline(&mut self) {
+        self.page_table.map(
+            VirtAddr::from(SIGRETURN_TRAMPOLINE).into(),
+            PhysAddr::from(ssigreturn as usize).into(),
+            PTEFlags::R | PTEFlags::X | PTEFlags::U,
+        );
+    }
     /// Without kernel stacks.
     pub fn new_kernel() -> Self {
         let mut memory_set = Self::new_bare();
@@ -118,7 +201,8 @@ impl MemorySet {
                 MapPermission::R | MapPermission::X,
             ),
             None,
-        );
+        )
+        .unwrap();
         info!("mapping .rodata section");
         memory_set.push(
             MapArea::new(
@@ -128,7 +212,8 @@ impl MemorySet {
                 MapPermission::R,
             ),
             None,
-        );
+        )
+        .unwrap();
         info!("mapping .data section");
         memory_set.push(
             MapArea::new(
@@ -138,7 +223,8 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        )
+        .unwrap();
         info!("mapping .bss section");
         memory_set.push(
             MapArea::new(
@@ -148,7 +234,8 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        )
+        .unwrap();
         info!("mapping physical memory");
         memory_set.push(
             MapArea::new(
@@ -158,7 +245,8 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        )
+        .unwrap();
         info!("mapping memory-mapped registers");
         for pair in MMIO {
             memory_set.push(
@@ -169,28 +257,60 @@ impl MemorySet {
                     MapPermission::R | MapPermission::W,
                 ),
                 None,
-            );
+            )
+            .unwrap();
         }
         memory_set
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
-    /// also returns user_sp_base and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// also returns user_sp_base and what the auxiliary vector needs to know
+    /// about the image. Fails if `elf_data` is not an ELF file, or one with
+    /// segments that can not be mapped, or if the frames for its segments
+    /// run out.
+    pub fn from_elf(elf_data: &[u8]) -> Result<(Self, usize, ElfInfo), ElfError> {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
+        memory_set.map_sigreturn_trampoline();
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| ElfError::BadElf)?;
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(ElfError::BadElf);
+        }
         let ph_count = elf_header.pt2.ph_count();
+        let ph_offset = elf_header.pt2.ph_offset() as usize;
+        let mut phdr = 0;
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            let ph = elf.program_header(i).map_err(|_| ElfError::BadElf)?;
+            let ph_type = ph.get_type().map_err(|_| ElfError::BadElf)?;
+            if ph_type == xmas_elf::program::Type::Phdr {
+                phdr = ph.virtual_addr() as usize;
+            }
+            if ph_type == xmas_elf::program::Type::Load {
+                // without PT_PHDR, the headers are found in the segment mapping them
+                let offset = ph.offset() as usize;
+                if phdr == 0 && offset <= ph_offset && ph_offset < offset + ph.file_size() as usize {
+                    phdr = ph.virtual_addr() as usize + ph_offset - offset;
+                }
+                // the file data has to be in the file and in the segment, and
+                // the segment in user space clear of the others
+                let data = offset
+                    .checked_add(ph.file_size() as usize)
+                    .and_then(|end| elf.input.get(offset..end))
+                    .ok_or(ElfError::BadElf)?;
+                let end = ph.virtual_addr().checked_add(ph.mem_size()).ok_or(ElfError::BadElf)?;
+                if ph.file_size() > ph.mem_size() || end as usize > USER_SPACE_END {
+                    return Err(ElfError::BadElf);
+                }
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                let end_va: VirtAddr = (end as usize).into();
+                let pages = end_va.ceil().0 - start_va.floor().0;
+                if !memory_set.range_free(start_va.floor(), pages) {
+                    return Err(ElfError::BadElf);
+                }
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -204,41 +324,77 @@ impl MemorySet {
                 }
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
                 max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                memory_set
+                    .push_with_offset(map_area, start_va.page_offset(), Some(data))
+                    .map_err(|_| ElfError::OutOfMemory)?;
             }
         }
+        // empty heap, grown lazily by sbrk
+        memory_set.insert_lazy_area(
+            USER_HEAP_BASE.into(),
+            USER_HEAP_BASE.into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
         // map user stack with U flags
         let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_base: usize = max_end_va.into();
         user_stack_base += PAGE_SIZE;
-        (
-            memory_set,
-            user_stack_base,
-            elf.header.pt2.entry_point() as usize,
-        )
+        let info = ElfInfo {
+            entry: elf.header.pt2.entry_point() as usize,
+            phdr,
+            phent: elf_header.pt2.ph_entry_size() as usize,
+            phnum: ph_count as usize,
+        };
+        Ok((memory_set, user_stack_base, info))
     }
-    /// Create a new address space by copy code&data from a exited process's address space.
-    pub fn from_existed_user(user_space: &Self) -> Self {
+    /// Create a new address space from an existed process's address space.
+    ///
+    /// User pages are shared copy-on-write: both spaces map the same frames
+    /// without W, and the first store to such a page copies it. Pages only
+    /// visible to the kernel (trap contexts) are still copied eagerly, since
+    /// the kernel writes them through their physical address, and fail with
+    /// [`PageFault::OutOfMemory`] if the frames for those run out.
+    pub fn from_existed_user(user_space: &mut Self) -> Result<Self, PageFault> {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
-        for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
+        memory_set.map_sigreturn_trampoline();
+        // share data sections/user stacks/shared mappings, copy trap_contexts;
+        // the pages of a shared mapping not touched yet are filled on a fault
+        // of either process through the frames the two areas share
+        for area in user_space.areas.iter_mut() {
+            let mut new_area = MapArea::from_another(area);
+            if area.map_type == MapType::Framed && area.map_perm.contains(MapPermission::U) {
+                let mut pte_flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+                if !area.shared || area.file.is_some() {
+                    // copy-on-write, or dirty tracking of a shared file page
+                    pte_flags.remove(PTEFlags::W);
+                }
+                for (vpn, frame) in area.data_frames.iter() {
+                    memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                    if !area.shared && area.map_perm.contains(MapPermission::W) {
+                        user_space.page_table.set_flags(*vpn, pte_flags);
+                    }
+                    new_area.data_frames.insert(*vpn, Arc::clone(frame));
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            memory_set.push(new_area, None)?;
             // copy data from another space
             for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
+                let src_ppn = user_space.page_table.translate(vpn).unwrap().ppn();
                 let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
                 dst_ppn
                     .get_bytes_array()
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
-        memory_set
+        // the parent may be the active space, drop its stale writable entries
+        unsafe {
+            asm!("sfence.vma");
+        }
+        Ok(memory_set)
     }
     /// Change page table by writing satp CSR Register.
     pub fn activate(&self) {
@@ -248,6 +404,209 @@ impl MemorySet {
             asm!("sfence.vma");
         }
     }
+    /// Handle a user page fault at `va` caused by an `access` of R, W or X.
+    /// Return Ok if it is resolved and the faulting access can be retried.
+    ///
+    /// A store to a writable area mapped without W is a copy-on-write page,
+    /// and a missing page of a lazy area gets its frame now, unless it has
+    /// to be read from a file first.
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MapPermission) -> Result<(), PageFault> {
+        let vpn = va.floor();
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() <= vpn && vpn < area.vpn_range.get_end())
+            .ok_or(PageFault::BadAccess)?;
+        if !area.map_perm.contains(access | MapPermission::U) {
+            return Err(PageFault::BadAccess);
+        }
+        match self.page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => {
+                if access == MapPermission::W && !pte.writable() {
+                    if area.shared {
+                        area.mark_dirty(&mut self.page_table, vpn);
+                    } else {
+                        area.copy_on_write(&mut self.page_table, vpn)?;
+                    }
+                    unsafe {
+                        asm!("sfence.vma {}", in(reg) VirtAddr::from(vpn).0);
+                    }
+                }
+                Ok(())
+            }
+            _ if area.lazy => area
+                .populate(&mut self.page_table, vpn, access)
+                .inspect_err(|err| {
+                    if *err == PageFault::NotLoaded {
+                        self.unloaded = Some((va, access));
+                    }
+                }),
+            _ => Err(PageFault::BadAccess),
+        }
+    }
+    /// Take the page and access of the last fault which found a page of a
+    /// file mapping not read yet. The caller reads it from
+    /// [`MemorySet::file_page`] with the address space unlocked, then hands
+    /// it to [`MemorySet::fill_page`].
+    pub fn take_unloaded(&mut self) -> Option<(VirtAddr, MapPermission)> {
+        self.unloaded.take()
+    }
+    /// The node and offset the page at `va` of a file mapping is read from,
+    /// if it is missing
+    pub fn file_page(&self, va: VirtAddr) -> Option<(VfsNodeRef, usize)> {
+        let vpn = va.floor();
+        let area = self.areas.iter().find(|area| {
+            area.vpn_range.get_start() <= vpn && vpn < area.vpn_range.get_end()
+        })?;
+        let file = area.file.as_ref()?;
+        if area.data_frames.contains_key(&vpn) || area.shared_frame(vpn).is_some() {
+            return None;
+        }
+        Some((Arc::clone(&file.node), area.file_offset(vpn)))
+    }
+    /// Resolve a fault at `va` with `frame`, which the caller filled from
+    /// `source` as told by [`MemorySet::file_page`]. If the page has been
+    /// mapped or remapped from elsewhere meanwhile, `frame` is dropped and
+    /// the fault handled over again.
+    pub fn fill_page(
+        &mut self,
+        va: VirtAddr,
+        access: MapPermission,
+        source: &(VfsNodeRef, usize),
+        frame: FrameTracker,
+    ) -> Result<(), PageFault> {
+        match self.file_page(va) {
+            Some((node, offset)) if Arc::ptr_eq(&node, &source.0) && offset == source.1 => {}
+            _ => return self.handle_page_fault(va, access),
+        }
+        let vpn = va.floor();
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() <= vpn && vpn < area.vpn_range.get_end())
+            .unwrap();
+        if !area.map_perm.contains(access | MapPermission::U) {
+            return Err(PageFault::BadAccess);
+        }
+        let mut frame = Arc::new(frame);
+        if let Some(shared_frames) = area.shared_frames.as_ref() {
+            // another process may have read the page meanwhile
+            frame = Arc::clone(shared_frames.lock().entry(vpn).or_insert(frame));
+        }
+        area.map_page(&mut self.page_table, vpn, access, frame);
+        Ok(())
+    }
+    /// The frame behind user page `vpn` if user space may read it (or write
+    /// it if `write`), resolving lazy and copy-on-write pages on the way,
+    /// just like the fault user space would take itself.
+    fn user_page(&mut self, vpn: VirtPageNum, write: bool) -> Result<PhysPageNum, BadAddress> {
+        let accessible = |pte: PageTableEntry| {
+            pte.is_valid()
+                && pte.flags().contains(PTEFlags::U)
+                && if write { pte.writable() } else { pte.readable() }
+        };
+        if let Some(pte) = self.page_table.translate(vpn) {
+            if accessible(pte) {
+                return Ok(pte.ppn());
+            }
+        }
+        let access = if write { MapPermission::W } else { MapPermission::R };
+        if self.handle_page_fault(vpn.into(), access).is_err() {
+            return Err(BadAddress);
+        }
+        match self.page_table.translate(vpn) {
+            Some(pte) if accessible(pte) => Ok(pte.ppn()),
+            _ => Err(BadAddress),
+        }
+    }
+    /// The physical address behind user address `va`, which user space may
+    /// read (or write if `write`)
+    pub fn user_phys_addr(&mut self, va: usize, write: bool) -> Result<PhysAddr, BadAddress> {
+        if va >= USER_SPACE_END {
+            return Err(BadAddress);
+        }
+        let va = VirtAddr::from(va);
+        let ppn = self.user_page(va.floor(), write)?;
+        Ok(PhysAddr(PhysAddr::from(ppn).0 + va.page_offset()))
+    }
+    /// Check that user space may access `[start, start + len)` and return
+    /// the pieces of physical memory behind it
+    pub fn user_buffer(
+        &mut self,
+        start: usize,
+        len: usize,
+        write: bool,
+    ) -> Result<UserBuffer, BadAddress> {
+        let end = start.checked_add(len).ok_or(BadAddress)?;
+        if end > USER_SPACE_END {
+            return Err(BadAddress);
+        }
+        let mut buffers = Vec::new();
+        let mut current = start;
+        while current < end {
+            let va = VirtAddr::from(current);
+            let ppn = self.user_page(va.floor(), write)?;
+            let offset = va.page_offset();
+            let chunk = (PAGE_SIZE - offset).min(end - current);
+            buffers.push(&mut ppn.get_bytes_array()[offset..offset + chunk]);
+            current += chunk;
+        }
+        Ok(UserBuffer::new(buffers))
+    }
+    /// Copy `dst.len()` bytes from user space at `src`
+    pub fn read_user_bytes(&mut self, src: usize, dst: &mut [u8]) -> Result<(), BadAddress> {
+        let buffer = self.user_buffer(src, dst.len(), false)?;
+        let mut copied = 0;
+        for piece in buffer.buffers {
+            dst[copied..copied + piece.len()].copy_from_slice(piece);
+            copied += piece.len();
+        }
+        Ok(())
+    }
+    /// Copy `src` to user space at `dst`
+    pub fn write_user_bytes(&mut self, dst: usize, src: &[u8]) -> Result<(), BadAddress> {
+        let buffer = self.user_buffer(dst, src.len(), true)?;
+        let mut copied = 0;
+        for piece in buffer.buffers {
+            piece.copy_from_slice(&src[copied..copied + piece.len()]);
+            copied += piece.len();
+        }
+        Ok(())
+    }
+    /// Read a `T` from user space, which may span several pages
+    pub fn read_user<T: Copy>(&mut self, src: *const T) -> Result<T, BadAddress> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        self.read_user_bytes(src as usize, bytes)?;
+        Ok(unsafe { value.assume_init() })
+    }
+    /// Write `value` to user space, which may span several pages
+    pub fn write_user<T>(&mut self, dst: *mut T, value: &T) -> Result<(), BadAddress> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        self.write_user_bytes(dst as usize, bytes)
+    }
+    /// Copy a `\0` terminated string from user space
+    pub fn read_user_str(&mut self, src: *const u8) -> Result<String, BadAddress> {
+        let mut string = String::new();
+        let mut current = src as usize;
+        loop {
+            if current >= USER_SPACE_END {
+                return Err(BadAddress);
+            }
+            let va = VirtAddr::from(current);
+            let ppn = self.user_page(va.floor(), false)?;
+            for &ch in &ppn.get_bytes_array()[va.page_offset()..] {
+                if ch == 0 {
+                    return Ok(string);
+                }
+                string.push(ch as char);
+            }
+            current += PAGE_SIZE - va.page_offset();
+        }
+    }
     /// Translate a virtual page number to a page table entry
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
@@ -279,105 +638,266 @@ impl MemorySet {
             .iter_mut()
             .find(|area| area.vpn_range.get_start() == start.floor())
         {
-            area.append_to(&mut self.page_table, new_end.ceil());
-            true
+            area.append_to(&mut self.page_table, new_end.ceil()).is_ok()
         } else {
             false
         }
     }
 
-    /// Ferform mmap operation.
-    pub fn mmap(&mut self, start: usize, len: usize, port: usize) -> isize {
-        // Fixed: Test 04_4 test
-        if start % PAGE_SIZE != 0 {
-            return -1;
+    /// Perform mmap operation: map `len` bytes at `hint`, or at an address
+    /// picked by the kernel unless MAP_FIXED is given. Pages are populated
+    /// lazily, from `file` if the mapping is file-backed. `len` is not 0
+    /// and a MAP_FIXED `hint` page-aligned.
+    /// Return the start address of the mapping, or None if there is no room.
+    pub fn mmap(
+        &mut self,
+        hint: usize,
+        len: usize,
+        permission: MapPermission,
+        flags: MmapFlags,
+        file: Option<FileBacking>,
+    ) -> Option<usize> {
+        let pages = len.div_ceil(PAGE_SIZE);
+        let start_vpn = if flags.contains(MmapFlags::MAP_FIXED) {
+            let start_vpn = VirtPageNum(hint / PAGE_SIZE);
+            let end_vpn = VirtPageNum(start_vpn.0 + pages);
+            // a fixed mapping replaces whatever user pages were there, check
+            // that it can before anything is unmapped
+            if !Self::in_user_space(start_vpn, pages)
+                || self.areas.iter().any(|area| {
+                    !area.map_perm.contains(MapPermission::U)
+                        && area.vpn_range.get_start() < end_vpn
+                        && start_vpn < area.vpn_range.get_end()
+                })
+            {
+                return None;
+            }
+            self.munmap(hint, len);
+            start_vpn
+        } else {
+            self.find_free_range(hint, pages)?
+        };
+        let end_vpn = VirtPageNum(start_vpn.0 + pages);
+        let mut map_area = MapArea::new(
+            start_vpn.into(),
+            end_vpn.into(),
+            MapType::Framed,
+            permission | MapPermission::U,
+        );
+        map_area.lazy = true;
+        if flags.contains(MmapFlags::MAP_SHARED) {
+            map_area.shared = true;
+            map_area.shared_frames = Some(Arc::new(SpinNoIrqLock::new(BTreeMap::new())));
         }
+        map_area.file = file;
+        self.push(map_area, None).ok()?;
+        Some(VirtAddr::from(start_vpn).into())
+    }
 
-        // port must be a positive integer less than 8 and non-zero
-        // port: [X, W, R]
-        if port > 8 || port == 0 {
-            return -1;
+    /// Perform msync operation: write dirty pages of shared file-backed
+    /// areas within the range back to their files.
+    pub fn msync(&mut self, start: usize, len: usize) -> bool {
+        let start_va = VirtAddr::from(start);
+        if !start_va.aligned() {
+            return false;
+        }
+        let start_vpn = start_va.floor();
+        let end_vpn = VirtAddr::from(start + len).ceil();
+        for area in self.areas.iter_mut() {
+            let vpns: Vec<VirtPageNum> = area
+                .dirty
+                .iter()
+                .filter(|vpn| start_vpn <= **vpn && **vpn < end_vpn)
+                .copied()
+                .collect();
+            for vpn in vpns {
+                area.clean_page(&mut self.page_table, vpn);
+            }
         }
+        unsafe {
+            asm!("sfence.vma");
+        }
+        true
+    }
+
+    /// Is `[start_vpn, start_vpn + pages)` not covered by any area?
+    fn range_free(&self, start_vpn: VirtPageNum, pages: usize) -> bool {
+        let end_vpn = VirtPageNum(start_vpn.0 + pages);
+        !self
+            .areas
+            .iter()
+            .any(|area| area.vpn_range.get_start() < end_vpn && start_vpn < area.vpn_range.get_end())
+    }
 
-        let start_address = VirtAddr::from(start);
-        let end_address = VirtAddr::from(start + len);
-        
-        // find whether there are duplicated allocations.
-        let mut found = false;
+    /// Does `[start_vpn, start_vpn + pages)` lie in the user half of the
+    /// address space?
+    fn in_user_space(start_vpn: VirtPageNum, pages: usize) -> bool {
+        start_vpn
+            .0
+            .checked_add(pages)
+            .is_some_and(|end| end <= USER_SPACE_END / PAGE_SIZE)
+    }
 
-        for area in self.areas.iter() {
-            let range_start = area.vpn_range.get_start();
-            let range_end = area.vpn_range.get_end();
-            // range (range_start, range_end) contains NONE of (start_address, end_address)
-            if range_end > start_address.floor() && range_start < end_address.ceil() {
-                found = true;
+    /// Find `pages` free pages for a mapping, trying `hint` first and then
+    /// the first gap above MMAP_BASE. Return None if user space is full.
+    fn find_free_range(&self, hint: usize, pages: usize) -> Option<VirtPageNum> {
+        let hint_vpn = VirtPageNum(hint / PAGE_SIZE);
+        if hint != 0 && Self::in_user_space(hint_vpn, pages) && self.range_free(hint_vpn, pages) {
+            return Some(hint_vpn);
+        }
+        let mut start_vpn = VirtAddr::from(MMAP_BASE).floor();
+        loop {
+            if !Self::in_user_space(start_vpn, pages) {
+                return None;
+            }
+            let end_vpn = VirtPageNum(start_vpn.0 + pages);
+            match self
+                .areas
+                .iter()
+                .filter(|area| area.vpn_range.get_start() < end_vpn && start_vpn < area.vpn_range.get_end())
+                .map(|area| area.vpn_range.get_end())
+                .max()
+            {
+                Some(overlap_end) => start_vpn = overlap_end,
+                None => return Some(start_vpn),
             }
         }
+    }
 
-        if found {
-            return -1;
+    /// Perform munmap operation: unmap the pages of `[start, start + len)`,
+    /// splitting the areas that straddle the range.
+    pub fn munmap(&mut self, start: usize, len: usize) -> bool {
+        let (start_vpn, end_vpn) = match Self::page_range(start, len) {
+            Some(range) => range,
+            None => return false,
+        };
+        self.split_at_range(start_vpn, end_vpn);
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &mut self.areas[idx];
+            if area.map_perm.contains(MapPermission::U)
+                && start_vpn <= area.vpn_range.get_start()
+                && area.vpn_range.get_end() <= end_vpn
+            {
+                area.unmap(&mut self.page_table);
+                self.areas.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+        unsafe {
+            asm!("sfence.vma");
         }
+        true
+    }
 
-        let mut permission_flags = MapPermission::empty();
-        // Read permission
-        if port & (1 << 0) != 0 {
-            permission_flags |= MapPermission::R;
+    /// Perform mprotect operation: change the permission of the pages of
+    /// `[start, start + len)`, which have to be mapped entirely.
+    pub fn mprotect(&mut self, start: usize, len: usize, permission: MapPermission) -> bool {
+        let (start_vpn, end_vpn) = match Self::page_range(start, len) {
+            Some(range) => range,
+            None => return false,
+        };
+        // every page must belong to a user area
+        let mut vpn = start_vpn;
+        while vpn < end_vpn {
+            match self.areas.iter().find(|area| {
+                area.map_perm.contains(MapPermission::U)
+                    && area.vpn_range.get_start() <= vpn
+                    && vpn < area.vpn_range.get_end()
+            }) {
+                Some(area) => vpn = area.vpn_range.get_end(),
+                None => return false,
+            }
         }
-        // Write permission
-        if port & (1 << 1) != 0 {
-            permission_flags |= MapPermission::W;
+        self.split_at_range(start_vpn, end_vpn);
+        for area in self.areas.iter_mut() {
+            if start_vpn <= area.vpn_range.get_start() && area.vpn_range.get_end() <= end_vpn {
+                area.set_permission(&mut self.page_table, permission | MapPermission::U);
+            }
         }
-        // Execute permission
-        if port & (1 << 2) != 0 {
-            permission_flags |= MapPermission::X;
+        unsafe {
+            asm!("sfence.vma");
         }
-        permission_flags |= MapPermission::U;
-
-        self.insert_framed_area(start_address, end_address, permission_flags);
-
-        0
+        true
     }
 
-    /// Ferform munmap operation.
-    pub fn munmap(&mut self, start: usize, len: usize) -> isize {
-        // Convert into VirtAddr
-        let start_address: VirtAddr = start.into();
-        let end_address: VirtAddr = (start + len).into();
-        // Fixed:  Test 04_6 failed
-        if !start_address.aligned() || !end_address.aligned() {
-            return -1;
-        }
-        let virtual_page_start = start_address.floor();
-        let virtual_page_end = end_address.ceil();
-
-        let mut index: isize = 0;
-        let mut found_index: isize = -1;
+    /// Does `[start, start + len)` hold a shared mapping of a file which was
+    /// not opened for writing?
+    pub fn maps_read_only_file(&self, start: usize, len: usize) -> bool {
+        let (start_vpn, end_vpn) = match Self::page_range(start, len) {
+            Some(range) => range,
+            None => return false,
+        };
+        self.areas.iter().any(|area| {
+            area.shared
+                && area.file.as_ref().is_some_and(|file| !file.writable)
+                && area.vpn_range.get_start() < end_vpn
+                && start_vpn < area.vpn_range.get_end()
+        })
+    }
 
-        // Traverse the areas and found the area containing the virtual memory address range
-        for area in self.areas.iter_mut() {
-            let range_start = area.vpn_range.get_start();
-            let range_end = area.vpn_range.get_end();
-            // range (range_start, range_end) contains (virtual_page_start, virtual_page_end)
-            if range_start <= virtual_page_start && range_end <= virtual_page_end {
-                found_index = index;
-            }
-            index += 1;
+    /// Page range of `[start, start + len)`, with `start` page-aligned and
+    /// the range in user space
+    fn page_range(start: usize, len: usize) -> Option<(VirtPageNum, VirtPageNum)> {
+        if !start.is_multiple_of(PAGE_SIZE) || len == 0 {
+            return None;
+        }
+        let start_vpn = VirtPageNum(start / PAGE_SIZE);
+        let pages = len.div_ceil(PAGE_SIZE);
+        if !Self::in_user_space(start_vpn, pages) {
+            return None;
         }
+        Some((start_vpn, VirtPageNum(start_vpn.0 + pages)))
+    }
 
-        if found_index >= 0 {
-            let index = found_index as usize;
-            self.areas[index].unmap(&mut self.page_table);
-            self.areas.remove(index);
+    /// Split the user areas crossing `start_vpn` or `end_vpn`, so that every
+    /// area lies either entirely inside or entirely outside the range.
+    fn split_at_range(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        for at in [start_vpn, end_vpn] {
+            if let Some(area) = self.areas.iter_mut().find(|area| {
+                area.map_perm.contains(MapPermission::U)
+                    && area.vpn_range.get_start() < at
+                    && at < area.vpn_range.get_end()
+            }) {
+                let tail = area.split_off(at);
+                self.areas.push(tail);
+            }
         }
-        0
     }
 }
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// frames are allocated on the first page fault instead of up front
+    lazy: bool,
+    /// MAP_SHARED: frames are shared with forked children instead of copied on write
+    shared: bool,
+    /// every frame of a shared area, also those only a forked child or parent
+    /// has touched so far
+    shared_frames: Option<Arc<SharedFrames>>,
+    /// the file this area is mapped from
+    file: Option<FileBacking>,
+    /// pages of a shared file mapping written since they were last synced
+    dirty: BTreeSet<VirtPageNum>,
+}
+
+/// The frames of a shared area, by page, common to the processes sharing it
+type SharedFrames = SpinNoIrqLock<BTreeMap<VirtPageNum, Arc<FrameTracker>>>;
+
+/// The file behind a file-backed map area
+#[derive(Clone)]
+pub struct FileBacking {
+    /// the mapped node
+    pub node: VfsNodeRef,
+    /// file offset of the first page of the area
+    pub offset: usize,
+    /// the file was opened for writing, a shared mapping of it may be
+    /// made writable
+    pub writable: bool,
 }
 
 impl MapArea {
@@ -394,6 +914,11 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            lazy: false,
+            shared: false,
+            shared_frames: None,
+            file: None,
+            dirty: BTreeSet::new(),
         }
     }
     pub fn from_another(another: &Self) -> Self {
@@ -402,33 +927,52 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            lazy: another.lazy,
+            shared: another.shared,
+            shared_frames: another.shared_frames.clone(),
+            file: another.file.clone(),
+            dirty: BTreeSet::new(),
         }
     }
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Result<(), PageFault> {
         let ppn: PhysPageNum;
         match self.map_type {
             MapType::Identical => {
                 ppn = PhysPageNum(vpn.0);
             }
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc().ok_or(PageFault::OutOfMemory)?;
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
         }
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         page_table.map(vpn, ppn, pte_flags);
+        Ok(())
     }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        if self.dirty.remove(&vpn) {
+            self.write_back(vpn);
+        }
+        // pages of a lazy area may never have been touched
+        if self.map_type == MapType::Framed && self.data_frames.remove(&vpn).is_none() {
+            return;
         }
         page_table.unmap(vpn);
     }
-    pub fn map(&mut self, page_table: &mut PageTable) {
+    /// Map all pages of a non-lazy area, or none of them if the frames
+    /// run out.
+    pub fn map(&mut self, page_table: &mut PageTable) -> Result<(), PageFault> {
+        if self.lazy {
+            return Ok(());
+        }
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            if let Err(err) = self.map_one(page_table, vpn) {
+                self.unmap(page_table);
+                return Err(err);
+            }
         }
+        Ok(())
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
@@ -441,36 +985,192 @@ impl MapArea {
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
-    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
-        for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
-            self.map_one(page_table, vpn)
+    /// Grow the area to `new_end`, or leave it as it is if the frames for
+    /// the new pages of a non-lazy area run out.
+    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) -> Result<(), PageFault> {
+        let old_end = self.vpn_range.get_end();
+        if !self.lazy {
+            for vpn in VPNRange::new(old_end, new_end) {
+                if let Err(err) = self.map_one(page_table, vpn) {
+                    for vpn in VPNRange::new(old_end, vpn) {
+                        self.unmap_one(page_table, vpn);
+                    }
+                    return Err(err);
+                }
+            }
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+        Ok(())
+    }
+    /// Split the area at `at`: this area keeps `[start, at)` and the
+    /// returned one takes `[at, end)` along with its frames.
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let mut tail = Self::from_another(self);
+        tail.vpn_range = VPNRange::new(at, self.vpn_range.get_end());
+        tail.data_frames = self.data_frames.split_off(&at);
+        tail.dirty = self.dirty.split_off(&at);
+        if let Some(file) = tail.file.as_mut() {
+            file.offset += (at.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        tail
+    }
+    /// Change the permission of the area and of its mapped pages.
+    /// Pages still shared copy-on-write and clean pages of a shared file
+    /// mapping stay write-protected.
+    pub fn set_permission(&mut self, page_table: &mut PageTable, permission: MapPermission) {
+        self.map_perm = permission;
+        for (vpn, frame) in self.data_frames.iter() {
+            let mut pte_flags = PTEFlags::from_bits(permission.bits).unwrap();
+            let write_protected = if self.shared {
+                self.file.is_some() && !self.dirty.contains(vpn)
+            } else {
+                Arc::strong_count(frame) > 1
+            };
+            if write_protected {
+                pte_flags.remove(PTEFlags::W);
+            }
+            page_table.set_flags(*vpn, pte_flags);
+        }
+    }
+    /// Map a missing page of a lazy area: the frame another process sharing
+    /// the area has filled already, else a new one for an anonymous area.
+    /// The page of a file mapping is read by the caller and mapped with
+    /// [`MapArea::map_page`].
+    pub fn populate(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        access: MapPermission,
+    ) -> Result<(), PageFault> {
+        let Some(shared_frames) = self.shared_frames.clone() else {
+            if self.file.is_some() {
+                return Err(PageFault::NotLoaded);
+            }
+            return self.map_one(page_table, vpn);
+        };
+        // locked until the new frame is in, so the processes agree on it
+        let mut shared_frames = shared_frames.lock();
+        let frame = match shared_frames.get(&vpn) {
+            Some(frame) => Arc::clone(frame),
+            None if self.file.is_some() => return Err(PageFault::NotLoaded),
+            None => {
+                let frame = Arc::new(frame_alloc().ok_or(PageFault::OutOfMemory)?);
+                shared_frames.insert(vpn, Arc::clone(&frame));
+                frame
+            }
+        };
+        drop(shared_frames);
+        self.map_page(page_table, vpn, access, frame);
+        Ok(())
+    }
+    /// The frame of page `vpn` of a shared area, if a process has filled it
+    fn shared_frame(&self, vpn: VirtPageNum) -> Option<Arc<FrameTracker>> {
+        self.shared_frames.as_ref()?.lock().get(&vpn).cloned()
+    }
+    /// Map `frame`, holding the page `vpn` read from the file or shared with
+    /// another process, for an `access`
+    pub fn map_page(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        access: MapPermission,
+        frame: Arc<FrameTracker>,
+    ) {
+        page_table.map(vpn, frame.ppn, PTEFlags::from_bits(self.map_perm.bits).unwrap());
+        self.data_frames.insert(vpn, frame);
+        if self.shared && self.file.is_some() && self.map_perm.contains(MapPermission::W) {
+            if access == MapPermission::W {
+                self.dirty.insert(vpn);
+            } else {
+                // catch the first store to know the page is dirty
+                let mut pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+                pte_flags.remove(PTEFlags::W);
+                page_table.set_flags(vpn, pte_flags);
+            }
+        }
+    }
+    /// Record the first store to a clean page of a shared mapping
+    pub fn mark_dirty(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        if self.file.is_some() {
+            self.dirty.insert(vpn);
+        }
+        page_table.set_flags(vpn, PTEFlags::from_bits(self.map_perm.bits).unwrap());
+    }
+    /// Write a dirty page back and write-protect it again
+    pub fn clean_page(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        if self.dirty.remove(&vpn) {
+            self.write_back(vpn);
+            let mut pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+            pte_flags.remove(PTEFlags::W);
+            page_table.set_flags(vpn, pte_flags);
+        }
+    }
+    /// File offset the page `vpn` is mapped from
+    fn file_offset(&self, vpn: VirtPageNum) -> usize {
+        self.file.as_ref().unwrap().offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE
+    }
+    /// Queue the page `vpn` to be written back to the file. The address
+    /// space is locked here, so the write is left to the file system
+    fn write_back(&self, vpn: VirtPageNum) {
+        let file = self.file.as_ref().unwrap();
+        let frame = Arc::clone(&self.data_frames[&vpn]);
+        queue_write_back(Arc::clone(&file.node), self.file_offset(vpn), frame);
+    }
+    /// Give `vpn` a private writable frame. The shared frame is copied unless
+    /// this area holds its last reference.
+    pub fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Result<(), PageFault> {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        let frame = self.data_frames.get(&vpn).unwrap();
+        if Arc::strong_count(frame) == 1 {
+            page_table.set_flags(vpn, pte_flags);
+            return Ok(());
+        }
+        let new_frame = frame_alloc().ok_or(PageFault::OutOfMemory)?;
+        new_frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(frame.ppn.get_bytes_array());
+        page_table.remap(vpn, new_frame.ppn, pte_flags);
+        self.data_frames.insert(vpn, Arc::new(new_frame));
+        Ok(())
     }
-    /// data: start-aligned but maybe with shorter length
+    /// data: copied to `offset` bytes into the first page, maybe with shorter
+    /// length than the area
     /// assume that all frames were cleared before
-    pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]) {
+    pub fn copy_data(&mut self, page_table: &mut PageTable, offset: usize, data: &[u8]) {
         assert_eq!(self.map_type, MapType::Framed);
         let mut start: usize = 0;
+        let mut page_offset = offset;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
-        loop {
-            let src = &data[start..len.min(start + PAGE_SIZE)];
+        while start < len {
+            let n = (PAGE_SIZE - page_offset).min(len - start);
+            let src = &data[start..start + n];
             let dst = &mut page_table
                 .translate(current_vpn)
                 .unwrap()
                 .ppn()
-                .get_bytes_array()[..src.len()];
+                .get_bytes_array()[page_offset..page_offset + n];
             dst.copy_from_slice(src);
-            start += PAGE_SIZE;
-            if start >= len {
-                break;
-            }
+            start += n;
+            page_offset = 0;
             current_vpn.step();
         }
     }
 }
 
+impl Drop for MapArea {
+    /// dirty pages of a shared file mapping reach the file even if the area
+    /// goes away with its whole address space, e.g. on exit or exec
+    fn drop(&mut self) {
+        let dirty = core::mem::take(&mut self.dirty);
+        for vpn in dirty {
+            self.write_back(vpn);
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 /// map type for memory set: identical or framed
 pub enum MapType {
@@ -492,9 +1192,23 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// flags of mmap
+    pub struct MmapFlags: usize {
+        /// Share the mapping with forked children and the file
+        const MAP_SHARED    = 0x01;
+        /// Private copy-on-write mapping
+        const MAP_PRIVATE   = 0x02;
+        /// Place the mapping exactly at the given address
+        const MAP_FIXED     = 0x10;
+        /// Not backed by any file
+        const MAP_ANONYMOUS = 0x20;
+    }
+}
+
 /// remap test in kernel space
 pub fn remap_test() {
-    let kernel_space = KERNEL_SPACE.exclusive_access();
+    let kernel_space = KERNEL_SPACE.lock();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
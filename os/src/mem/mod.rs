@@ -17,12 +17,17 @@ pub use frame_allocator::frame_allocator_test;
 pub use address::{StepByOne, VPNRange};
 pub use frame_allocator::{frame_alloc, FrameTracker, frame_dealloc};
 pub use memory_set::remap_test;
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE, kernel_token};
-pub use page_table::{translated_byte_buffer, translated_refmut, translated_str, PageTableEntry, translated_ref};
+pub use memory_set::{BadAddress, ElfError, ElfInfo, FileBacking, MapPermission, MemorySet, MmapFlags, PageFault, KERNEL_SPACE, kernel_token};
+pub use page_table::PageTableEntry;
 pub use page_table::{PTEFlags, PageTable, UserBuffer};
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.exclusive_access().activate();
+    init_hart();
+}
+
+/// turn on paging on a secondary hart, once [`init`] has run on the boot hart
+pub fn init_hart() {
+    KERNEL_SPACE.lock().activate();
 }
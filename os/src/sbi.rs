@@ -1,21 +1,29 @@
-/// Invoke SBI call to put timer
-pub fn console_putchar(c: usize) {
+/// Invoke SBI call to set timer
+pub fn set_timer(timer: usize) {
     #[allow(deprecated)]
-    sbi_rt::legacy::console_putchar(c);
+    sbi_rt::legacy::set_timer(timer as u64);
 }
 
-/// Invoke SBI call to get char
-pub fn console_getchar() -> usize {
-    #[allow(deprecated)]
-    sbi_rt::legacy::console_getchar()
+/// Start `hartid` at physical address `start_addr` with `opaque` in a1,
+/// return false if the hart does not exist or is already running
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> bool {
+    sbi_rt::hart_start(hartid, start_addr, opaque).error == 0
 }
 
-/// Invoke SBI call to set timer
-pub fn set_timer(timer: usize) {
-    #[allow(deprecated)]
-    sbi_rt::legacy::set_timer(timer as u64);
+/// Whether `hartid` exists and is stopped, i.e. can be started
+pub fn hart_stopped(hartid: usize) -> bool {
+    /// SBI HSM state of a hart which is not running
+    const HART_STOPPED: usize = 1;
+    let ret = sbi_rt::hart_get_status(hartid);
+    ret.error == 0 && ret.value == HART_STOPPED
+}
+
+/// Send a software interrupt to `hartid`
+pub fn send_ipi(hartid: usize) {
+    sbi_rt::send_ipi(1, hartid);
 }
 
+/// Invoke SBI call to shut down the machine
 pub fn shutdown(failure: bool) -> ! {
     use sbi_rt::{system_reset, NoReason, Shutdown, SystemFailure};
     if !failure {
@@ -1,13 +1,13 @@
 //! Conditian variable
 
-use crate::sync::{Mutex, UPSafeCell};
+use crate::sync::{Mutex, SpinNoIrqLock};
 use crate::process::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
 use alloc::{collections::VecDeque, sync::Arc};
 
 /// Condition variable structure
 pub struct Condvar {
     /// Condition variable inner
-    pub inner: UPSafeCell<CondvarInner>,
+    pub inner: SpinNoIrqLock<CondvarInner>,
 }
 
 pub struct CondvarInner {
@@ -19,17 +19,15 @@ impl Condvar {
     pub fn new() -> Self {
         trace!("kernel: Condvar::new");
         Self {
-            inner: unsafe {
-                UPSafeCell::new(CondvarInner {
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
     /// Signal a task waiting on the condition variable
     pub fn signal(&self) {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         if let Some(task) = inner.wait_queue.pop_front() {
             wakeup_task(task);
         }
@@ -39,7 +37,7 @@ impl Condvar {
     pub fn wait(&self, mutex: Arc<dyn Mutex>) {
         trace!("kernel: Condvar::wait_with_mutex");
         mutex.unlock();
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         inner.wait_queue.push_back(current_task().unwrap());
         drop(inner);
         block_current_and_run_next();
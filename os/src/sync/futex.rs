@@ -0,0 +1,106 @@
+//! Futex wait queues
+//!
+//! A futex is a 32-bit word in user memory. Its wait queue is keyed by the
+//! physical address of the word, so threads of one process and processes
+//! sharing memory meet on the same queue whatever their virtual addresses.
+
+use crate::mem::PhysAddr;
+use crate::process::{block_current_interruptible_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use crate::sync::SpinNoIrqLock;
+use crate::time::{add_timer, remove_timer};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use lazy_static::*;
+
+lazy_static! {
+    /// FUTEX_QUEUES: the tasks waiting on each futex, by physical address
+    static ref FUTEX_QUEUES: SpinNoIrqLock<BTreeMap<usize, VecDeque<Arc<TaskControlBlock>>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// Why [`futex_wait`] returned
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FutexWakeup {
+    /// woken by [`futex_wake`] or [`futex_requeue`]
+    Woken,
+    /// the timeout expired first
+    TimedOut,
+    /// woken for another reason, e.g. a signal
+    Interrupted,
+}
+
+/// Remove `task` from whichever futex queue it is waiting on, return false
+/// if it is not waiting
+pub fn futex_cancel(task: &Arc<TaskControlBlock>) -> bool {
+    let mut queues = FUTEX_QUEUES.lock();
+    let found = queues.iter_mut().find_map(|(key, queue)| {
+        let index = queue.iter().position(|t| Arc::ptr_eq(t, task))?;
+        queue.remove(index);
+        Some((*key, queue.is_empty()))
+    });
+    match found {
+        Some((key, true)) => {
+            queues.remove(&key);
+            true
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Block the current task on the futex at `key` until it is woken, or until
+/// `expire_ms` if given. The caller has checked the futex word already.
+pub fn futex_wait(key: PhysAddr, expire_ms: Option<usize>) -> FutexWakeup {
+    let task = current_task().unwrap();
+    FUTEX_QUEUES
+        .lock()
+        .entry(key.0)
+        .or_default()
+        .push_back(Arc::clone(&task));
+    if let Some(expire_ms) = expire_ms {
+        add_timer(expire_ms, Arc::clone(&task));
+    }
+    block_current_interruptible_and_run_next();
+    if expire_ms.is_some() {
+        remove_timer(Arc::clone(&task));
+    }
+    // wakers take the task off the queue, so it is still there if something
+    // else woke it up
+    if !futex_cancel(&task) {
+        FutexWakeup::Woken
+    } else if expire_ms.is_some_and(|expire_ms| crate::time::get_time_ms() >= expire_ms) {
+        FutexWakeup::TimedOut
+    } else {
+        FutexWakeup::Interrupted
+    }
+}
+
+/// Wake up to `n` tasks waiting on the futex at `key`, return how many
+/// were woken
+pub fn futex_wake(key: PhysAddr, n: usize) -> usize {
+    futex_requeue(key, n, key, 0)
+}
+
+/// Wake up to `n_wake` tasks waiting on the futex at `key` and move up to
+/// `n_requeue` of the remaining ones to the futex at `key2`. Return how
+/// many tasks were woken and moved.
+pub fn futex_requeue(key: PhysAddr, n_wake: usize, key2: PhysAddr, n_requeue: usize) -> usize {
+    let mut queues = FUTEX_QUEUES.lock();
+    let Some(mut queue) = queues.remove(&key.0) else {
+        return 0;
+    };
+    let woken: VecDeque<_> = queue.drain(..n_wake.min(queue.len())).collect();
+    let moved: VecDeque<_> = queue.drain(..n_requeue.min(queue.len())).collect();
+    let count = woken.len() + moved.len();
+    if !queue.is_empty() {
+        queues.insert(key.0, queue);
+    }
+    if !moved.is_empty() {
+        queues.entry(key2.0).or_default().extend(moved);
+    }
+    drop(queues);
+    for task in woken {
+        wakeup_task(task);
+    }
+    count
+}
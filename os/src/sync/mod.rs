@@ -1,13 +1,17 @@
 //! Synchronization and interior mutability primitives
 
 mod condvar;
+mod futex;
 mod mutex;
 mod semaphore;
-mod up;
+mod spin;
 mod lazy_init;
+mod wait_queue;
 
 pub use condvar::Condvar;
+pub use futex::{futex_cancel, futex_requeue, futex_wait, futex_wake, FutexWakeup};
 pub use mutex::{Mutex, MutexBlocking, MutexSpin};
 pub use semaphore::Semaphore;
-pub use up::UPSafeCell;
+pub use spin::{may_sleep, SpinNoIrqGuard, SpinNoIrqLock};
 pub use lazy_init::LazyInit;
+pub use wait_queue::WaitQueue;
@@ -1,13 +1,13 @@
 //! Semaphore
 
-use crate::sync::UPSafeCell;
+use crate::sync::SpinNoIrqLock;
 use crate::process::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
 use alloc::{collections::VecDeque, sync::Arc};
 
 /// semaphore structure
 pub struct Semaphore {
     /// semaphore inner
-    pub inner: UPSafeCell<SemaphoreInner>,
+    pub inner: SpinNoIrqLock<SemaphoreInner>,
 }
 
 pub struct SemaphoreInner {
@@ -20,19 +20,17 @@ impl Semaphore {
     pub fn new(res_count: usize) -> Self {
         trace!("kernel: Semaphore::new");
         Self {
-            inner: unsafe {
-                UPSafeCell::new(SemaphoreInner {
-                    count: res_count as isize,
-                    wait_queue: VecDeque::new(),
-                })
-            },
+            inner: SpinNoIrqLock::new(SemaphoreInner {
+                count: res_count as isize,
+                wait_queue: VecDeque::new(),
+            }),
         }
     }
 
     /// up operation of semaphore
     pub fn up(&self) {
         trace!("kernel: Semaphore::up");
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         inner.count += 1;
         if inner.count <= 0 {
             if let Some(task) = inner.wait_queue.pop_front() {
@@ -44,12 +42,18 @@ impl Semaphore {
     /// down operation of semaphore
     pub fn down(&self) {
         trace!("kernel: Semaphore::down");
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         inner.count -= 1;
         if inner.count < 0 {
-            inner.wait_queue.push_back(current_task().unwrap());
-            drop(inner);
-            block_current_and_run_next();
+            let task = current_task().unwrap();
+            inner.wait_queue.push_back(Arc::clone(&task));
+            // a stale wakeup can end the sleep early, the resource is ours
+            // only once up has taken us off the queue
+            while inner.wait_queue.iter().any(|waiting| Arc::ptr_eq(waiting, &task)) {
+                drop(inner);
+                block_current_and_run_next();
+                inner = self.inner.lock();
+            }
         }
     }
 }
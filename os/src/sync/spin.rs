@@ -0,0 +1,142 @@
+//! Spinlock which keeps interrupts off on its hart while held
+//!
+//! A hart taking an interrupt while it holds a lock must not try to take the
+//! same lock in the handler, so [`SpinNoIrqLock::lock`] disables supervisor
+//! interrupts before spinning and the guard restores them when dropped.
+//!
+//! A task must not sleep while it holds such a lock either: the harts waiting
+//! for it spin with interrupts off. Each hart counts the locks it holds, and
+//! code which may either block or busy-wait asks [`may_sleep`] first.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::config::MAX_HARTS;
+use crate::process::hart_id;
+use riscv::register::sstatus;
+
+/// The number of [`SpinNoIrqLock`]s each hart holds
+static HELD_LOCKS: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+
+/// Whether the current hart holds no spinlock, so its task may go to sleep
+pub fn may_sleep() -> bool {
+    HELD_LOCKS[hart_id()].load(Ordering::Relaxed) == 0
+}
+
+/// Mutual exclusion between harts, with interrupts disabled while held
+pub struct SpinNoIrqLock<T: ?Sized> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Sync for SpinNoIrqLock<T> {}
+unsafe impl<T: ?Sized + Send> Send for SpinNoIrqLock<T> {}
+
+/// Access to the data behind a [`SpinNoIrqLock`], released on drop
+pub struct SpinNoIrqGuard<'a, T: ?Sized> {
+    locked: &'a AtomicBool,
+    data: &'a mut T,
+    /// whether interrupts were enabled before the lock was taken
+    irq_enabled: bool,
+}
+
+impl<T> SpinNoIrqLock<T> {
+    /// Create an unlocked lock
+    pub const fn new(data: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> SpinNoIrqLock<T> {
+    /// Disable interrupts and spin until the lock is ours
+    pub fn lock(&self) -> SpinNoIrqGuard<'_, T> {
+        let irq_enabled = sstatus::read().sie();
+        unsafe { sstatus::clear_sie() };
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+        HELD_LOCKS[hart_id()].fetch_add(1, Ordering::Relaxed);
+        SpinNoIrqGuard {
+            locked: &self.locked,
+            data: unsafe { &mut *self.data.get() },
+            irq_enabled,
+        }
+    }
+
+    /// Access the data through an exclusive borrow, no locking needed
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Whether some hart holds the lock
+    #[allow(unused)]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: Default> Default for SpinNoIrqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Clone> Clone for SpinNoIrqLock<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.lock().clone())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinNoIrqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SpinNoIrqLock { .. }")
+    }
+}
+
+impl<'a, T: ?Sized> SpinNoIrqGuard<'a, T> {
+    /// Narrow the guard to a part of the locked data, like `RefMut::map`
+    pub fn map<U: ?Sized>(guard: Self, f: impl FnOnce(&mut T) -> &mut U) -> SpinNoIrqGuard<'a, U> {
+        // the mapped guard takes over the lock, `guard` is never dropped
+        let guard = core::mem::ManuallyDrop::new(guard);
+        let data = unsafe { core::ptr::read(&guard.data) };
+        SpinNoIrqGuard {
+            locked: guard.locked,
+            data: f(data),
+            irq_enabled: guard.irq_enabled,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for SpinNoIrqGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinNoIrqGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.data
+    }
+}
+
+impl<T: ?Sized> Drop for SpinNoIrqGuard<'_, T> {
+    fn drop(&mut self) {
+        self.locked.store(false, Ordering::Release);
+        HELD_LOCKS[hart_id()].fetch_sub(1, Ordering::Relaxed);
+        if self.irq_enabled {
+            unsafe { sstatus::set_sie() };
+        }
+    }
+}
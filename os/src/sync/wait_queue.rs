@@ -0,0 +1,83 @@
+//! Wait queue
+
+use crate::sync::{SpinNoIrqGuard, SpinNoIrqLock};
+use crate::process::{
+    block_current_and_run_next, block_current_interruptible_and_run_next, current_task, wakeup_task,
+    TaskControlBlock,
+};
+use alloc::{collections::VecDeque, sync::Arc};
+
+/// A queue of tasks sleeping until some event happens
+pub struct WaitQueue {
+    queue: SpinNoIrqLock<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    /// Create an empty wait queue
+    pub const fn new() -> Self {
+        Self {
+            queue: SpinNoIrqLock::new(VecDeque::new()),
+        }
+    }
+
+    /// Block the current task until it is woken up by [`WaitQueue::notify_one`]
+    /// or [`WaitQueue::notify_all`]. The caller should check its condition
+    /// again after waking up.
+    #[allow(unused)]
+    pub fn wait(&self) {
+        self.queue.lock().push_back(current_task().unwrap());
+        block_current_and_run_next();
+    }
+
+    /// Like [`WaitQueue::wait`], but queue up before `guard` is released, so
+    /// a notification sent by the next holder of the lock cannot be missed
+    pub fn wait_unlock<T: ?Sized>(&self, guard: SpinNoIrqGuard<'_, T>) {
+        self.queue.lock().push_back(current_task().unwrap());
+        drop(guard);
+        block_current_and_run_next();
+    }
+
+    /// Like [`WaitQueue::wait_unlock`], but a signal for the process wakes
+    /// the task up too, so the caller checks for pending signals as well
+    pub fn wait_unlock_interruptible<T: ?Sized>(&self, guard: SpinNoIrqGuard<'_, T>) {
+        let task = current_task().unwrap();
+        self.queue.lock().push_back(Arc::clone(&task));
+        drop(guard);
+        block_current_interruptible_and_run_next();
+        // still queued if the signal came first
+        self.cancel(&task);
+    }
+
+    /// Queue up `task` without blocking, for waiting on several queues at
+    /// once. The caller blocks itself and takes the task off again with
+    /// [`WaitQueue::cancel`]
+    pub fn register(&self, task: Arc<TaskControlBlock>) {
+        self.queue.lock().push_back(task);
+    }
+
+    /// Take `task` off the queue if it is still waiting
+    pub fn cancel(&self, task: &Arc<TaskControlBlock>) {
+        self.queue.lock().retain(|waiting| !Arc::ptr_eq(waiting, task));
+    }
+
+    /// Wake up the task waiting longest, return false if there is none
+    #[allow(unused)]
+    pub fn notify_one(&self) -> bool {
+        let task = self.queue.lock().pop_front();
+        match task {
+            Some(task) => {
+                wakeup_task(task);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wake up all waiting tasks
+    pub fn notify_all(&self) {
+        let tasks: VecDeque<_> = core::mem::take(&mut *self.queue.lock());
+        for task in tasks {
+            wakeup_task(task);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Linux error numbers
+//!
+//! Every `sys_*` handler returns a [`SysResult`]; [`super::syscall()`] turns
+//! an error into the negated error number user space expects.
+
+use crate::mem::{BadAddress, ElfError};
+use crate::vfs::DevError;
+
+/// Result of a syscall handler, the value is returned to user space as is
+pub type SysResult<T = isize> = Result<T, Errno>;
+
+/// Linux error numbers (asm-generic/errno-base.h and errno.h)
+#[allow(unused, clippy::upper_case_acronyms)]
+#[repr(isize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Errno {
+    /// Operation not permitted
+    EPERM = 1,
+    /// No such file or directory
+    ENOENT = 2,
+    /// No such process
+    ESRCH = 3,
+    /// Interrupted system call
+    EINTR = 4,
+    /// I/O error
+    EIO = 5,
+    /// No such device or address
+    ENXIO = 6,
+    /// Argument list too long
+    E2BIG = 7,
+    /// Exec format error
+    ENOEXEC = 8,
+    /// Bad file number
+    EBADF = 9,
+    /// No child processes
+    ECHILD = 10,
+    /// Try again
+    EAGAIN = 11,
+    /// Out of memory
+    ENOMEM = 12,
+    /// Permission denied
+    EACCES = 13,
+    /// Bad address
+    EFAULT = 14,
+    /// Device or resource busy
+    EBUSY = 16,
+    /// File exists
+    EEXIST = 17,
+    /// Cross-device link
+    EXDEV = 18,
+    /// No such device
+    ENODEV = 19,
+    /// Not a directory
+    ENOTDIR = 20,
+    /// Is a directory
+    EISDIR = 21,
+    /// Invalid argument
+    EINVAL = 22,
+    /// File table overflow
+    ENFILE = 23,
+    /// Too many open files
+    EMFILE = 24,
+    /// Not a typewriter
+    ENOTTY = 25,
+    /// File too large
+    EFBIG = 27,
+    /// No space left on device
+    ENOSPC = 28,
+    /// Illegal seek
+    ESPIPE = 29,
+    /// Read-only file system
+    EROFS = 30,
+    /// Too many links
+    EMLINK = 31,
+    /// Broken pipe
+    EPIPE = 32,
+    /// Math result not representable
+    ERANGE = 34,
+    /// Resource deadlock would occur
+    EDEADLK = 35,
+    /// File name too long
+    ENAMETOOLONG = 36,
+    /// Invalid system call number
+    ENOSYS = 38,
+    /// Directory not empty
+    ENOTEMPTY = 39,
+    /// Operation not supported
+    EOPNOTSUPP = 95,
+    /// Connection timed out
+    ETIMEDOUT = 110,
+}
+
+impl Errno {
+    /// The value returned to user space, i.e. `-errno`
+    pub fn as_ret(self) -> isize {
+        -(self as isize)
+    }
+}
+
+impl From<DevError> for Errno {
+    fn from(err: DevError) -> Self {
+        match err {
+            DevError::ReadError
+            | DevError::WriteError
+            | DevError::IoError
+            | DevError::UnexpectedEof
+            | DevError::WriteZero => Errno::EIO,
+            DevError::InvalidInput(_) | DevError::InvalidData => Errno::EINVAL,
+            DevError::Unsupported => Errno::EOPNOTSUPP,
+            DevError::NotADirectory => Errno::ENOTDIR,
+            DevError::IsADirectory => Errno::EISDIR,
+            DevError::NotAFile => Errno::EINVAL,
+            DevError::PermissionDenied => Errno::EACCES,
+            DevError::NotFound => Errno::ENOENT,
+            DevError::DirectoryNotEmpty => Errno::ENOTEMPTY,
+            DevError::AlreadyExists => Errno::EEXIST,
+            DevError::StorageFull => Errno::ENOSPC,
+        }
+    }
+}
+
+impl From<BadAddress> for Errno {
+    fn from(_: BadAddress) -> Self {
+        Errno::EFAULT
+    }
+}
+
+impl From<ElfError> for Errno {
+    fn from(err: ElfError) -> Self {
+        match err {
+            ElfError::BadElf => Errno::ENOEXEC,
+            ElfError::OutOfMemory => Errno::ENOMEM,
+        }
+    }
+}
@@ -1,182 +1,695 @@
-use crate::vfs::{link_file, open_file, unlink_file, OpenFlags, Stat, make_pipe};
-use crate::mem::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer};
-use crate::process::{current_process, current_task, current_user_token};
+use crate::vfs::{link_file, open_file, open_pty, unlink_file, File, OpenFlags, Stat, make_pipe};
+use crate::vfs::{canonicalize, is_dir, make_dir, remove_dir, DevError, PollEvents, SeekFrom};
+use crate::mem::UserBuffer;
+use super::process::TimeSpec;
+use super::{Errno, SysResult};
+use crate::process::{block_current_interruptible_and_run_next, current_signal_pending, SignalFlags};
+use crate::process::{copy_from_user, copy_to_user, current_process, current_task, user_buffer, user_str};
+use crate::process::FdEntry;
+use crate::time::{add_timer, get_time_ms, remove_timer};
+use alloc::format;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The file behind `fd` of the current process
+fn fd_file(fd: usize) -> SysResult<Arc<dyn File + Send + Sync>> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let entry = inner.fd_table().get(fd).cloned().flatten();
+    entry.map(|entry| entry.file).ok_or(Errno::EBADF)
+}
+
+/// Descriptors are below this, like Linux' default RLIMIT_NOFILE
+const FD_LIMIT: usize = 1024;
+
+/// Put `file` at the lowest free descriptor not below `min`
+fn install_fd(min: usize, file: Arc<dyn File + Send + Sync>, cloexec: bool) -> SysResult<usize> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd_from(min);
+    if fd >= FD_LIMIT {
+        return Err(Errno::EMFILE);
+    }
+    inner.fd_table()[fd] = Some(FdEntry::new(file, cloexec));
+    Ok(fd)
+}
+
+/// `dirfd` of the *at syscalls standing for the working directory
+pub const AT_FDCWD: isize = -100;
+/// unlinkat removes a directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// The absolute path named by `path`, which starts at the directory open
+/// as `dirfd` (or at the working directory for AT_FDCWD) unless absolute
+pub fn at_path(dirfd: isize, path: &str) -> SysResult<String> {
+    if path.is_empty() {
+        return Err(Errno::ENOENT);
+    }
+    if path.starts_with('/') {
+        return Ok(canonicalize(path));
+    }
+    let base = if dirfd == AT_FDCWD {
+        current_process().inner_exclusive_access().cwd.clone()
+    } else {
+        let dir = fd_file(usize::try_from(dirfd).map_err(|_| Errno::EBADF)?)?;
+        let base = String::from(dir.path().ok_or(Errno::ENOTDIR)?);
+        if !is_dir(&base)? {
+            return Err(Errno::ENOTDIR);
+        }
+        base
+    };
+    Ok(canonicalize(&format!("{}/{}", base, path)))
+}
+
 /// write syscall
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_write",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
-        if !file.writable() {
-            return -1;
-        }
-        let file = file.clone();
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
+    let file = fd_file(fd)?;
+    if !file.writable() {
+        return Err(Errno::EBADF);
     }
+    let buffer = user_buffer(buf as usize, len, false)?;
+    Ok(file.write(buffer)? as isize)
 }
 /// read syscall
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_read",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let token = current_user_token();
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        if !file.readable() {
-            return -1;
-        }
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        trace!("kernel: sys_read .. file.read");
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
-    } else {
-        -1
+    let file = fd_file(fd)?;
+    if !file.readable() {
+        return Err(Errno::EBADF);
     }
+    let buffer = user_buffer(buf as usize, len, true)?;
+    trace!("kernel: sys_read .. file.read");
+    Ok(file.read(buffer)? as isize)
 }
-/// open sys
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+
+/// lseek whence: from the start of the file
+const SEEK_SET: usize = 0;
+/// lseek whence: from the current offset
+const SEEK_CUR: usize = 1;
+/// lseek whence: from the end of the file
+const SEEK_END: usize = 2;
+/// lseek whence: to the next data at or after the offset
+const SEEK_DATA: usize = 3;
+/// lseek whence: to the next hole at or after the offset
+const SEEK_HOLE: usize = 4;
+
+/// lseek syscall, the offset is shared by every fd the file is dup'ed or forked into
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_open",
+        "kernel:pid[{}] sys_lseek",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let process = current_process();
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = process.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
+    let file = fd_file(fd)?;
+    let absolute = || usize::try_from(offset).map_err(|_| Errno::EINVAL);
+    let pos = match whence {
+        SEEK_SET => SeekFrom::Start(absolute()?),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        SEEK_DATA => SeekFrom::Data(absolute().map_err(|_| Errno::ENXIO)?),
+        SEEK_HOLE => SeekFrom::Hole(absolute().map_err(|_| Errno::ENXIO)?),
+        _ => return Err(Errno::EINVAL),
+    };
+    Ok(file.seek(pos)? as isize)
+}
+/// pread64 syscall, reads at `offset` and leaves the file offset alone
+pub fn sys_pread64(fd: usize, buf: *const u8, len: usize, offset: isize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_pread64",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let file = fd_file(fd)?;
+    if !file.readable() {
+        return Err(Errno::EBADF);
+    }
+    let offset = usize::try_from(offset).map_err(|_| Errno::EINVAL)?;
+    let buffer = user_buffer(buf as usize, len, true)?;
+    Ok(file.pread(offset, buffer)? as isize)
+}
+/// pwrite64 syscall, writes at `offset` and leaves the file offset alone
+pub fn sys_pwrite64(fd: usize, buf: *const u8, len: usize, offset: isize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_pwrite64",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let file = fd_file(fd)?;
+    if !file.writable() {
+        return Err(Errno::EBADF);
+    }
+    let offset = usize::try_from(offset).map_err(|_| Errno::EINVAL)?;
+    let buffer = user_buffer(buf as usize, len, false)?;
+    Ok(file.pwrite(offset, buffer)? as isize)
+}
+
+/// struct iovec of readv and writev
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct IoVec {
+    base: usize,
+    len: usize,
+}
+
+/// readv and writev take at most this many iovecs, like Linux' UIO_MAXIOV
+const IOV_MAX: usize = 1024;
+
+/// The buffers of `iovcnt` iovecs at `iov` strung together into one
+fn iovec_buffer(iov: *const IoVec, iovcnt: usize, write: bool) -> SysResult<UserBuffer> {
+    if iovcnt > IOV_MAX {
+        return Err(Errno::EINVAL);
+    }
+    let mut buffers = Vec::new();
+    let mut total = 0usize;
+    for i in 0..iovcnt {
+        let vec = copy_from_user(iov.wrapping_add(i))?;
+        // the sum has to fit the return value
+        total = total
+            .checked_add(vec.len)
+            .filter(|&total| total <= isize::MAX as usize)
+            .ok_or(Errno::EINVAL)?;
+        buffers.extend(user_buffer(vec.base, vec.len, write)?.buffers);
+    }
+    Ok(UserBuffer::new(buffers))
+}
+/// readv syscall, one read filling the iovecs in order
+pub fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_readv",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let file = fd_file(fd)?;
+    if !file.readable() {
+        return Err(Errno::EBADF);
+    }
+    let buffer = iovec_buffer(iov, iovcnt, true)?;
+    Ok(file.read(buffer)? as isize)
+}
+/// writev syscall, one write gathering the iovecs in order
+pub fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_writev",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let file = fd_file(fd)?;
+    if !file.writable() {
+        return Err(Errno::EBADF);
+    }
+    let buffer = iovec_buffer(iov, iovcnt, false)?;
+    Ok(file.write(buffer)? as isize)
+}
+/// openat syscall
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_openat",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let path = at_path(dirfd, &user_str(path)?)?;
+    // unknown flags are ignored, as on Linux
+    let flags = OpenFlags::from_bits_truncate(flags);
+    let inode: Arc<dyn File + Send + Sync> = match open_pty(path.as_str()) {
+        Some(pty) => pty?,
+        None => open_file(path.as_str(), flags)?,
+    };
+    Ok(install_fd(0, inode, flags.contains(OpenFlags::CLOEXEC))? as isize)
+}
+/// ioctl syscall, `arg` is interpreted by the file according to `cmd`
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_ioctl",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    fd_file(fd)?.ioctl(cmd, arg)
+}
+/// getdents64 syscall
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_getdents64",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let file = fd_file(fd)?;
+    let buffer = user_buffer(buf as usize, len, true)?;
+    Ok(file.getdents(buffer)? as isize)
+}
+/// fcntl: duplicate to the lowest free descriptor not below arg
+const F_DUPFD: usize = 0;
+/// fcntl: get the descriptor flags
+const F_GETFD: usize = 1;
+/// fcntl: set the descriptor flags
+const F_SETFD: usize = 2;
+/// fcntl: get the access mode and status flags of the open file
+const F_GETFL: usize = 3;
+/// fcntl: set the status flags of the open file
+const F_SETFL: usize = 4;
+/// fcntl: F_DUPFD with close-on-exec set on the new descriptor
+const F_DUPFD_CLOEXEC: usize = 1030;
+/// the only descriptor flag: close on execve
+const FD_CLOEXEC: usize = 1;
+
+/// fcntl syscall, the descriptor and status flag commands are handled here
+/// and the rest are left to the file
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_fcntl",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let file = fd_file(fd)?;
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            if arg >= FD_LIMIT {
+                return Err(Errno::EINVAL);
+            }
+            Ok(install_fd(arg, file, cmd == F_DUPFD_CLOEXEC)? as isize)
+        }
+        F_GETFD | F_SETFD => {
+            let process = current_process();
+            let inner = process.inner_exclusive_access();
+            let mut fd_table = inner.fd_table();
+            // the descriptor may have been closed by another thread meanwhile
+            let entry = fd_table.get_mut(fd).and_then(Option::as_mut).ok_or(Errno::EBADF)?;
+            if cmd == F_GETFD {
+                return Ok(if entry.cloexec { FD_CLOEXEC as isize } else { 0 });
+            }
+            entry.cloexec = arg & FD_CLOEXEC != 0;
+            Ok(0)
+        }
+        F_GETFL => {
+            let mode = OpenFlags::access_mode(file.readable(), file.writable());
+            Ok((mode | file.status_flags()).bits() as isize)
+        }
+        F_SETFL => {
+            // the access mode and the creation flags are left as they are
+            let flags = OpenFlags::from_bits_truncate(arg as u32) & OpenFlags::STATUS;
+            file.set_status_flags(flags);
+            Ok(0)
+        }
+        _ => file.fcntl(cmd, arg),
     }
 }
 /// close syscall
-pub fn sys_close(fd: usize) -> isize {
+pub fn sys_close(fd: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_close",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
     let process = current_process();
-    let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
-    }
-    inner.fd_table[fd].take();
-    0
+    let inner = process.inner_exclusive_access();
+    let file = inner.fd_table().get_mut(fd).and_then(Option::take);
+    file.map(|_| 0).ok_or(Errno::EBADF)
 }
-/// pipe syscall
-pub fn sys_pipe(pipe: *mut usize) -> isize {
+/// pipe syscall, the two descriptors are stored as the `int[2]` of pipe2.
+/// O_NONBLOCK and O_CLOEXEC are the only flags understood.
+pub fn sys_pipe(pipe: *mut i32, flags: u32) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_pipe",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let process = current_process();
-    let token = current_user_token();
-    let mut inner = process.inner_exclusive_access();
-    let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
-    inner.fd_table[read_fd] = Some(pipe_read);
-    let write_fd = inner.alloc_fd();
-    inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
-    0
+    let flags = OpenFlags::from_bits(flags)
+        .filter(|flags| (OpenFlags::NONBLOCK | OpenFlags::CLOEXEC).contains(*flags))
+        .ok_or(Errno::EINVAL)?;
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
+    let (pipe_read, pipe_write) = make_pipe(flags);
+    let read_fd = install_fd(0, pipe_read, cloexec)?;
+    let write_fd = match install_fd(0, pipe_write, cloexec) {
+        Ok(fd) => fd,
+        Err(err) => {
+            sys_close(read_fd)?;
+            return Err(err);
+        }
+    };
+    let fds = [read_fd as i32, write_fd as i32];
+    if let Err(err) = copy_to_user(pipe as *mut [i32; 2], &fds) {
+        sys_close(read_fd)?;
+        sys_close(write_fd)?;
+        return Err(err.into());
+    }
+    Ok(0)
 }
 /// dup syscall
-pub fn sys_dup(fd: usize) -> isize {
+pub fn sys_dup(fd: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_dup",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let process = current_process();
-    let mut inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+    let file = fd_file(fd)?;
+    Ok(install_fd(0, file, false)? as isize)
+}
+/// dup3 syscall, `new_fd` is closed first if open. O_CLOEXEC is the only flag.
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_dup3",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let flags = OpenFlags::from_bits(flags)
+        .filter(|flags| OpenFlags::CLOEXEC.contains(*flags))
+        .ok_or(Errno::EINVAL)?;
+    let file = fd_file(old_fd)?;
+    if old_fd == new_fd {
+        return Err(Errno::EINVAL);
     }
-    if inner.fd_table[fd].is_none() {
-        return -1;
+    if new_fd >= FD_LIMIT {
+        return Err(Errno::EBADF);
     }
-    let new_fd = inner.alloc_fd();
-    inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
-    new_fd as isize
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let mut fd_table = inner.fd_table();
+    if fd_table.len() <= new_fd {
+        fd_table.resize(new_fd + 1, None);
+    }
+    fd_table[new_fd] = Some(FdEntry::new(file, flags.contains(OpenFlags::CLOEXEC)));
+    Ok(new_fd as isize)
 }
 
-/// YOUR JOB: Implement fstat.
-pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+/// fstat syscall
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_fstat NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_fstat",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let process = current_process();
-    let inner = process.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+    let file = fd_file(fd)?;
+    let stat = file.stat().ok_or(Errno::EINVAL)?;
+    copy_to_user(st, &stat)?;
+    Ok(0)
+}
+
+/// linkat syscall
+pub fn sys_linkat(
+    old_dirfd: isize,
+    old_name: *const u8,
+    new_dirfd: isize,
+    new_name: *const u8,
+) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_linkat",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let old_name = at_path(old_dirfd, &user_str(old_name)?)?;
+    let new_name = at_path(new_dirfd, &user_str(new_name)?)?;
+    link_file(old_name.as_str(), new_name.as_str()).map_err(|err| match err {
+        // as on Linux for directories and for file systems without hard links
+        DevError::IsADirectory | DevError::Unsupported => Errno::EPERM,
+        err => err.into(),
+    })?;
+    Ok(0)
+}
+
+/// unlinkat syscall, removes a directory if `flags` has AT_REMOVEDIR
+pub fn sys_unlinkat(dirfd: isize, name: *const u8, flags: u32) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_unlinkat",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let name = at_path(dirfd, &user_str(name)?)?;
+    match flags {
+        0 => unlink_file(name.as_str())?,
+        AT_REMOVEDIR => remove_dir(name.as_str())?,
+        _ => return Err(Errno::EINVAL),
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        // release Process lock manually to avoid deadlock
-        drop(inner);
-        if let Some(stat) = file.stat() {
-            let token = current_user_token();
+    Ok(0)
+}
 
-            *translated_refmut(token, st) = stat;
+/// mkdirat syscall, the mode is not kept
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, _mode: u32) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_mkdirat",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let path = at_path(dirfd, &user_str(path)?)?;
+    make_dir(path.as_str())?;
+    Ok(0)
+}
 
-            return 0;
-        }
-        -1
-    } else {
-        -1
+/// Make the directory at the absolute `path` the working directory
+fn change_dir(path: String) -> SysResult {
+    if !is_dir(&path)? {
+        return Err(Errno::ENOTDIR);
     }
+    current_process().inner_exclusive_access().cwd = path;
+    Ok(0)
 }
 
-/// YOUR JOB: Implement linkat.
-pub fn sys_linkat(old_name: *const u8, new_name: *const u8) -> isize {
+/// chdir syscall
+pub fn sys_chdir(path: *const u8) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_linkat NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_chdir",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let token = current_user_token();
+    change_dir(at_path(AT_FDCWD, &user_str(path)?)?)
+}
 
-    let old_name = translated_str(token, old_name);
-    let new_name = translated_str(token, new_name);
+/// fchdir syscall
+pub fn sys_fchdir(fd: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_fchdir",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let path = String::from(fd_file(fd)?.path().ok_or(Errno::ENOTDIR)?);
+    change_dir(path)
+}
 
-    link_file(
-        old_name.as_str(),
-        new_name.as_str(),
-    )
+/// getcwd syscall, return the length of the path including its NUL
+pub fn sys_getcwd(buf: *mut u8, size: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_getcwd",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let mut cwd = current_process().inner_exclusive_access().cwd.clone().into_bytes();
+    cwd.push(0);
+    if size < cwd.len() {
+        return Err(Errno::ERANGE);
+    }
+    let buffer = user_buffer(buf as usize, cwd.len(), true)?;
+    for (dst, src) in buffer.into_iter().zip(cwd.iter()) {
+        unsafe { *dst = *src };
+    }
+    Ok(cwd.len() as isize)
 }
 
-/// YOUR JOB: Implement unlinkat.
-pub fn sys_unlinkat(name: *const u8) -> isize {
+/// An entry of the ppoll array, laid out as the Linux `struct pollfd`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PollFd {
+    /// the descriptor to watch, ignored if negative
+    pub fd: i32,
+    /// the [`PollEvents`] asked for
+    pub events: i16,
+    /// the [`PollEvents`] that happened
+    pub revents: i16,
+}
+
+/// Most descriptors a single ppoll or pselect6 call may watch
+const MAX_POLL_FDS: usize = 1024;
+
+/// Wait until one of `files` is ready for the events it is watched for, the
+/// timeout runs out or a signal arrives. Files which are None never get
+/// ready. Return the events each file is ready for, errors and hangups
+/// always included.
+fn wait_ready(
+    files: &[(Option<Arc<dyn File + Send + Sync>>, PollEvents)],
+    timeout_ms: Option<usize>,
+) -> SysResult<Vec<PollEvents>> {
+    let task = current_task().unwrap();
+    let expire_ms = timeout_ms.map(|ms| get_time_ms().saturating_add(ms));
+    let queues = || files.iter().filter_map(|(file, _)| file.as_ref()?.poll_queue());
+    loop {
+        // queue up before looking, so a change in between wakes us up
+        for queue in queues() {
+            queue.register(Arc::clone(&task));
+        }
+        let ready: Vec<PollEvents> = files
+            .iter()
+            .map(|(file, events)| match file {
+                Some(file) => file.poll() & (*events | PollEvents::POLLERR | PollEvents::POLLHUP),
+                None => PollEvents::empty(),
+            })
+            .collect();
+        let timed_out = expire_ms.is_some_and(|expire_ms| get_time_ms() >= expire_ms);
+        let done = timed_out || ready.iter().any(|events| !events.is_empty());
+        let interrupted = !done && current_signal_pending();
+        if !done && !interrupted {
+            if let Some(expire_ms) = expire_ms {
+                add_timer(expire_ms, Arc::clone(&task));
+            }
+            block_current_interruptible_and_run_next();
+            if expire_ms.is_some() {
+                remove_timer(Arc::clone(&task));
+            }
+        }
+        for queue in queues() {
+            queue.cancel(&task);
+        }
+        if done {
+            return Ok(ready);
+        }
+        if interrupted {
+            return Err(Errno::EINTR);
+        }
+    }
+}
+
+/// Read a relative timeout, None for a null pointer which waits forever
+fn read_timeout(timeout: *const TimeSpec) -> SysResult<Option<usize>> {
+    if timeout.is_null() {
+        return Ok(None);
+    }
+    let timeout = copy_from_user(timeout)?;
+    timeout.as_ms().map(Some).ok_or(Errno::EINVAL)
+}
+
+/// Run `f` with the signal mask at `sigmask` in place, unless it is null.
+/// The old mask is back when `f` returns, or if `f` is interrupted, once the
+/// signal has been delivered on the way back to user mode.
+fn with_sigmask<T>(sigmask: *const u64, f: impl FnOnce() -> SysResult<T>) -> SysResult<T> {
+    if sigmask.is_null() {
+        return f();
+    }
+    let mask = SignalFlags::from_bits_truncate(copy_from_user(sigmask)?);
+    let task = current_task().unwrap();
+    let old_mask = core::mem::replace(
+        &mut task.inner_exclusive_access().signal_mask,
+        mask - SignalFlags::unmaskable(),
+    );
+    let ret = f();
+    let mut task_inner = task.inner_exclusive_access();
+    if let Err(Errno::EINTR) = ret {
+        // handle_signals restores it
+        task_inner.saved_signal_mask = Some(old_mask);
+    } else {
+        task_inner.signal_mask = old_mask;
+    }
+    ret
+}
+
+/// ppoll syscall
+///
+/// Wait for the descriptors of the `nfds` entries at `fds`, at most as long
+/// as `timeout` with `sigmask` as the signal mask. Return how many entries
+/// have events, 0 on timeout.
+pub fn sys_ppoll(fds: *mut PollFd, nfds: usize, timeout: *const TimeSpec, sigmask: *const u64) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_unlinkat NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_ppoll",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let token = current_user_token();
+    if nfds > MAX_POLL_FDS {
+        return Err(Errno::EINVAL);
+    }
+    let mut entries = Vec::with_capacity(nfds);
+    for i in 0..nfds {
+        entries.push(copy_from_user(fds.wrapping_add(i))?);
+    }
+    let mut timeout_ms = read_timeout(timeout)?;
+    // descriptors which are not open are left out and reported as POLLNVAL
+    let mut files = Vec::with_capacity(nfds);
+    let mut invalid = Vec::with_capacity(nfds);
+    for entry in entries.iter() {
+        let events = PollEvents::from_bits_truncate(entry.events as u16);
+        let file = usize::try_from(entry.fd).ok().map(fd_file);
+        invalid.push(matches!(file, Some(Err(_))));
+        files.push((file.and_then(Result::ok), events));
+    }
+    if invalid.contains(&true) {
+        timeout_ms = Some(0);
+    }
+    let ready = with_sigmask(sigmask, || wait_ready(&files, timeout_ms))?;
+    let mut count = 0;
+    for (i, (entry, events)) in entries.iter_mut().zip(ready).enumerate() {
+        let events = if invalid[i] { PollEvents::POLLNVAL } else { events };
+        entry.revents = events.bits() as i16;
+        if !events.is_empty() {
+            count += 1;
+        }
+        copy_to_user(fds.wrapping_add(i), entry)?;
+    }
+    Ok(count)
+}
 
-    let name = translated_str(token, name);
+/// Read the first `nfds` bits of the fd_set at `set`, None if it is null
+fn read_fd_set(set: *mut u64, nfds: usize) -> SysResult<Option<Vec<u64>>> {
+    if set.is_null() {
+        return Ok(None);
+    }
+    let mut words = Vec::new();
+    for i in 0..nfds.div_ceil(64) {
+        words.push(copy_from_user(set.wrapping_add(i))?);
+    }
+    Ok(Some(words))
+}
 
-    unlink_file(name.as_str())
+/// pselect6 syscall
+///
+/// Wait until a descriptor below `nfds` in `readfds` is readable, one in
+/// `writefds` is writable or one in `exceptfds` has urgent data. The sets
+/// are left with the ready descriptors, whose number is returned. `sigmask`
+/// points to a pointer to the signal mask and its size.
+pub fn sys_pselect6(
+    nfds: usize,
+    readfds: *mut u64,
+    writefds: *mut u64,
+    exceptfds: *mut u64,
+    timeout: *const TimeSpec,
+    sigmask: *const [usize; 2],
+) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_pselect6",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    if nfds > MAX_POLL_FDS {
+        return Err(Errno::EINVAL);
+    }
+    let sets = [
+        (readfds, PollEvents::POLLIN | PollEvents::POLLHUP | PollEvents::POLLERR),
+        (writefds, PollEvents::POLLOUT | PollEvents::POLLERR),
+        (exceptfds, PollEvents::POLLPRI),
+    ];
+    let mut bits = Vec::new();
+    for (set, _) in sets.iter() {
+        bits.push(read_fd_set(*set, nfds)?);
+    }
+    let timeout_ms = read_timeout(timeout)?;
+    let sigmask = if sigmask.is_null() {
+        core::ptr::null()
+    } else {
+        copy_from_user(sigmask)?[0] as *const u64
+    };
+    // every descriptor in any set is watched for the events of all its sets
+    let mut files = Vec::new();
+    for fd in 0..nfds {
+        let mut events = PollEvents::empty();
+        for ((_, set_events), words) in sets.iter().zip(bits.iter()) {
+            if let Some(words) = words {
+                if words[fd / 64] & (1 << (fd % 64)) != 0 {
+                    events |= *set_events;
+                }
+            }
+        }
+        if !events.is_empty() {
+            files.push((fd, (Some(fd_file(fd)?), events)));
+        }
+    }
+    let watched: Vec<_> = files.iter().map(|(_, file)| file.clone()).collect();
+    let ready = with_sigmask(sigmask, || wait_ready(&watched, timeout_ms))?;
+    let mut count = 0;
+    for ((set, set_events), words) in sets.iter().zip(bits.iter_mut()) {
+        let Some(words) = words else {
+            continue;
+        };
+        let asked = core::mem::replace(words, vec![0; words.len()]);
+        for ((fd, _), events) in files.iter().zip(ready.iter()) {
+            let bit = 1 << (fd % 64);
+            if asked[fd / 64] & bit != 0 && events.intersects(*set_events) {
+                words[fd / 64] |= bit;
+                count += 1;
+            }
+        }
+        for (i, word) in words.iter().enumerate() {
+            copy_to_user(set.wrapping_add(i), word)?;
+        }
+    }
+    Ok(count)
 }
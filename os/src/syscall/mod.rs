@@ -10,14 +10,34 @@
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
 
+/// getcwd syscall
+pub const SYSCALL_GETCWD: usize = 17;
+/// mkdirat syscall
+pub const SYSCALL_MKDIRAT: usize = 34;
+/// chdir syscall
+pub const SYSCALL_CHDIR: usize = 49;
+/// fchdir syscall
+pub const SYSCALL_FCHDIR: usize = 50;
 /// openat syscall
 pub const SYSCALL_OPENAT: usize = 56;
 /// close syscall
 pub const SYSCALL_CLOSE: usize = 57;
+/// getdents64 syscall
+pub const SYSCALL_GETDENTS64: usize = 61;
+/// lseek syscall
+pub const SYSCALL_LSEEK: usize = 62;
 /// read syscall
 pub const SYSCALL_READ: usize = 63;
 /// write syscall
 pub const SYSCALL_WRITE: usize = 64;
+/// readv syscall
+pub const SYSCALL_READV: usize = 65;
+/// writev syscall
+pub const SYSCALL_WRITEV: usize = 66;
+/// pread64 syscall
+pub const SYSCALL_PREAD64: usize = 67;
+/// pwrite64 syscall
+pub const SYSCALL_PWRITE64: usize = 68;
 /// unlinkat syscall
 pub const SYSCALL_UNLINKAT: usize = 35;
 /// linkat syscall
@@ -26,32 +46,38 @@ pub const SYSCALL_LINKAT: usize = 37;
 pub const SYSCALL_FSTAT: usize = 80;
 /// exit syscall
 pub const SYSCALL_EXIT: usize = 93;
+/// set_tid_address syscall
+pub const SYSCALL_SET_TID_ADDRESS: usize = 96;
+/// futex syscall
+pub const SYSCALL_FUTEX: usize = 98;
 /// sleep syscall
 pub const SYSCALL_SLEEP: usize = 101;
 /// yield syscall
 pub const SYSCALL_YIELD: usize = 124;
 /// kill syscall
 pub const SYSCALL_KILL: usize = 129;
-/*
+/// setpgid syscall
+pub const SYSCALL_SETPGID: usize = 154;
+/// getpgid syscall
+pub const SYSCALL_GETPGID: usize = 155;
 /// sigaction syscall
 pub const SYSCALL_SIGACTION: usize = 134;
 /// sigprocmask syscall
 pub const SYSCALL_SIGPROCMASK: usize = 135;
 /// sigreturn syscall
 pub const SYSCALL_SIGRETURN: usize = 139;
-*/
 /// gettimeofday syscall
 pub const SYSCALL_GETTIMEOFDAY: usize = 169;
 /// getpid syscall
 pub const SYSCALL_GETPID: usize = 172;
 /// gettid syscall
 pub const SYSCALL_GETTID: usize = 178;
-/// fork syscall
-pub const SYSCALL_FORK: usize = 220;
-/// exec syscall
-pub const SYSCALL_EXEC: usize = 221;
-/// waitpid syscall
-pub const SYSCALL_WAITPID: usize = 260;
+/// clone syscall
+pub const SYSCALL_CLONE: usize = 220;
+/// execve syscall
+pub const SYSCALL_EXECVE: usize = 221;
+/// wait4 syscall
+pub const SYSCALL_WAIT4: usize = 260;
 /// set priority syscall
 pub const SYSCALL_SET_PRIORITY: usize = 140;
 
@@ -62,6 +88,10 @@ pub const SYSCALL_SBRK: usize = 214;
 pub const SYSCALL_MUNMAP: usize = 215;
 /// mmap syscall
 pub const SYSCALL_MMAP: usize = 222;
+/// mprotect syscall
+pub const SYSCALL_MPROTECT: usize = 226;
+/// msync syscall
+pub const SYSCALL_MSYNC: usize = 227;
 /// spawn syscall
 pub const SYSCALL_SPAWN: usize = 400;
 /*
@@ -71,7 +101,17 @@ pub const SYSCALL_MAIL_READ: usize = 401;
 pub const SYSCALL_MAIL_WRITE: usize = 402;
 */
 /// dup syscall
-pub const SYSCALL_DUP: usize = 24;
+pub const SYSCALL_DUP: usize = 23;
+/// dup3 syscall
+pub const SYSCALL_DUP3: usize = 24;
+/// fcntl syscall
+pub const SYSCALL_FCNTL: usize = 25;
+/// ioctl syscall
+pub const SYSCALL_IOCTL: usize = 29;
+/// pselect6 syscall
+pub const SYSCALL_PSELECT6: usize = 72;
+/// ppoll syscall
+pub const SYSCALL_PPOLL: usize = 73;
 /// pipe syscall
 pub const SYSCALL_PIPE: usize = 59;
 /// task info syscall
@@ -101,8 +141,10 @@ pub const SYSCALL_CONDVAR_SIGNAL: usize = 472;
 /// condvar_wait syscallca
 pub const SYSCALL_CONDVAR_WAIT: usize = 473;
 
+pub use errno::{Errno, SysResult};
 pub use process::TaskInfo;
 
+mod errno;
 mod fs;
 mod process;
 mod sync;
@@ -113,39 +155,95 @@ use process::*;
 use sync::*;
 use thread::*;
 
-use crate::{process::task_watch_syscall, vfs::Stat};
+use crate::{
+    process::{task_watch_syscall, SignalAction},
+    vfs::Stat,
+};
 
 /// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     // println!("Syscall {} called with args: {:?}", syscall_id, args);
     task_watch_syscall(syscall_id);
-    match syscall_id {
+    let result = match syscall_id {
         SYSCALL_DUP => sys_dup(args[0]),
-        SYSCALL_LINKAT => sys_linkat(args[1] as *const u8, args[3] as *const u8),
-        SYSCALL_UNLINKAT => sys_unlinkat(args[1] as *const u8),
-        SYSCALL_OPENAT => sys_open(args[1] as *const u8, args[2] as u32),
+        SYSCALL_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYSCALL_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYSCALL_MKDIRAT => sys_mkdirat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_LINKAT => sys_linkat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as isize,
+            args[3] as *const u8,
+        ),
+        SYSCALL_UNLINKAT => sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYSCALL_CHDIR => sys_chdir(args[0] as *const u8),
+        SYSCALL_FCHDIR => sys_fchdir(args[0]),
+        SYSCALL_OPENAT => sys_openat(args[0] as isize, args[1] as *const u8, args[2] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
-        SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_PIPE => sys_pipe(args[0] as *mut i32, args[1] as u32),
+        SYSCALL_PSELECT6 => sys_pselect6(
+            args[0],
+            args[1] as *mut u64,
+            args[2] as *mut u64,
+            args[3] as *mut u64,
+            args[4] as *const TimeSpec,
+            args[5] as *const [usize; 2],
+        ),
+        SYSCALL_PPOLL => sys_ppoll(
+            args[0] as *mut PollFd,
+            args[1],
+            args[2] as *const TimeSpec,
+            args[3] as *const u64,
+        ),
+        SYSCALL_GETDENTS64 => sys_getdents64(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYSCALL_READV => sys_readv(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
+        SYSCALL_PREAD64 => sys_pread64(args[0], args[1] as *const u8, args[2], args[3] as isize),
+        SYSCALL_PWRITE64 => sys_pwrite64(args[0], args[1] as *const u8, args[2], args[3] as isize),
         SYSCALL_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2], args[3], args[4], args[5] as u32),
         SYSCALL_SLEEP => sys_sleep(args[0]),
         SYSCALL_YIELD => sys_yield(),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
         SYSCALL_GETTID => sys_gettid(),
-        SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_SET_TID_ADDRESS => sys_set_tid_address(args[0] as *mut i32),
+        SYSCALL_CLONE => sys_clone(
+            args[0],
+            args[1],
+            args[2] as *mut i32,
+            args[3],
+            args[4] as *mut i32,
+        ),
+        SYSCALL_EXECVE => sys_execve(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
+        SYSCALL_WAIT4 => sys_wait4(
+            args[0] as isize,
+            args[1] as *mut i32,
+            args[2],
+            args[3] as *mut RUsage,
+        ),
         SYSCALL_GETTIMEOFDAY => sys_get_time(args[0] as *mut TimeVal, args[1]),
-        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYSCALL_MSYNC => sys_msync(args[0], args[1], args[2]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
         SYSCALL_SBRK => sys_sbrk(args[0] as i32),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
-        SYSCALL_WAITTID => sys_waittid(args[0]) as isize,
+        SYSCALL_WAITTID => sys_waittid(args[0], args[1] as *mut i32),
         SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
@@ -156,7 +254,21 @@ pub fn syscall(syscall_id: usize, args: [usize; 4]) -> isize {
         SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
         SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
-        SYSCALL_KILL => sys_kill(args[0], args[1] as u32),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(
+            args[0],
+            args[1] as *const SignalAction,
+            args[2] as *mut SignalAction,
+        ),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0], args[1] as *const u64, args[2] as *mut u64),
+        SYSCALL_SIGRETURN => sys_sigreturn(),
+        _ => {
+            warn!("Unsupported syscall_id: {}", syscall_id);
+            Err(Errno::ENOSYS)
+        }
+    };
+    match result {
+        Ok(ret) => ret,
+        Err(errno) => errno.as_ret(),
     }
 }
@@ -1,21 +1,76 @@
 use crate::{
-    config::MAX_SYSCALL_NUM,
-    mem::{translated_ref, translated_refmut, translated_str},
+    config::{MAX_SYSCALL_NUM, PAGE_SIZE},
+    mem::{FileBacking, MapPermission, MmapFlags},
     process::{
-        current_process, current_task, current_task_memset_mmap, current_task_memset_munmap, current_task_spawn, current_user_token, exit_current_and_run_next, fetch_task_info, pid2process, suspend_current_and_run_next, SignalFlags, TaskStatus
+        add_task, current_process, current_task, current_task_memset_mmap, current_task_memset_mprotect, current_task_memset_msync, current_task_memset_munmap, current_task_spawn, exit_current_and_run_next, fetch_task_info, pid2process, process_group, suspend_current_and_run_next, SignalFlags, TaskStatus,
+        copy_from_user, copy_to_user, current_force_signal, current_signal_pending_locked, user_str, current_sigreturn, current_trap_cx, send_signal, CloneFlags, SignalAction, SIG_BLOCK, SIG_SETMASK, SIG_UNBLOCK,
     },
     time::get_time_us,
     vfs::{open_file, OpenFlags},
 };
+use super::fs::{at_path, AT_FDCWD};
+use super::{Errno, SysResult};
 use alloc::{string::String, sync::Arc, vec::Vec};
+use bitflags::*;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TimeVal {
     pub sec: usize,
     pub usec: usize,
 }
 
+impl TimeVal {
+    fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / 1_000_000,
+            usec: us % 1_000_000,
+        }
+    }
+}
+
+/// Time in seconds and nanoseconds, laid out as the Linux `struct timespec`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    /// seconds
+    pub sec: usize,
+    /// nanoseconds
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    /// The time in milliseconds, rounded up; None if `nsec` is out of range
+    pub fn as_ms(&self) -> Option<usize> {
+        if self.nsec >= 1_000_000_000 {
+            return None;
+        }
+        Some(self.sec.saturating_mul(1000).saturating_add(self.nsec.div_ceil(1_000_000)))
+    }
+}
+
+/// Resource usage reported by wait4, laid out as the Linux `struct rusage`
+#[repr(C)]
+#[derive(Default)]
+pub struct RUsage {
+    /// user time used
+    pub utime: TimeVal,
+    /// system time used
+    pub stime: TimeVal,
+    /// maxrss up to nivcsw, which are not tracked
+    rest: [isize; 14],
+}
+
+bitflags! {
+    /// Options of wait4
+    pub struct WaitOptions: usize {
+        /// Return at once if no child has changed state
+        const WNOHANG = 1;
+        /// Also report children which have stopped
+        const WUNTRACED = 2;
+    }
+}
+
 /// Task information
 #[allow(dead_code)]
 pub struct TaskInfo {
@@ -38,217 +93,517 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 /// yield syscall
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SysResult {
     //trace!("kernel: sys_yield");
     suspend_current_and_run_next();
-    0
+    Ok(0)
 }
 /// getpid syscall
-pub fn sys_getpid() -> isize {
+pub fn sys_getpid() -> SysResult {
     trace!(
         "kernel: sys_getpid pid:{}",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    current_task().unwrap().process.upgrade().unwrap().getpid() as isize
+    Ok(current_task().unwrap().process.upgrade().unwrap().getpid() as isize)
+}
+/// setpgid syscall
+///
+/// Move process `pid` (0 for the caller) into process group `pgid` (0 for a
+/// group of its own). Only the caller and its children can be moved.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_setpgid",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let current = current_process();
+    let target = if pid == 0 || pid == current.getpid() {
+        Arc::clone(&current)
+    } else {
+        let inner = current.inner_exclusive_access();
+        let child = inner.children.iter().find(|child| child.getpid() == pid);
+        child.map(Arc::clone).ok_or(Errno::ESRCH)?
+    };
+    if (pgid as isize) < 0 {
+        return Err(Errno::EINVAL);
+    }
+    let pgid = if pgid == 0 { target.getpid() } else { pgid };
+    // only a group of its own or one that already exists can be joined
+    if pgid != target.getpid() && process_group(pgid).is_empty() {
+        return Err(Errno::EPERM);
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    Ok(0)
+}
+/// getpgid syscall
+pub fn sys_getpgid(pid: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_getpgid",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let process = if pid == 0 {
+        current_process()
+    } else {
+        pid2process(pid).ok_or(Errno::ESRCH)?
+    };
+    let pgid = process.inner_exclusive_access().pgid;
+    Ok(pgid as isize)
 }
-/// fork child process syscall
-pub fn sys_fork() -> isize {
+/// clone syscall
+///
+/// With CLONE_THREAD the child is a new thread of the current process and
+/// its tid is returned, otherwise it is a new process and its pid is
+/// returned; fork is clone with SIGCHLD and nothing else. The child returns
+/// 0, on `stack` if it is not 0.
+pub fn sys_clone(flags: usize, stack: usize, ptid: *mut i32, tls: usize, ctid: *mut i32) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_fork",
+        "kernel:pid[{}] sys_clone",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
+    // the low byte is the exit signal, the parent always gets SIGCHLD
+    let flags = CloneFlags::from_bits_truncate(flags & !0xff);
+    if flags.contains(CloneFlags::CLONE_SIGHAND) && !flags.contains(CloneFlags::CLONE_VM)
+        || flags.contains(CloneFlags::CLONE_THREAD) && !flags.contains(CloneFlags::CLONE_SIGHAND)
+    {
+        return Err(Errno::EINVAL);
+    }
     let current_process = current_process();
-    let new_process = current_process.fork();
-    let new_pid = new_process.getpid();
+    let (new_task, new_id) = if flags.contains(CloneFlags::CLONE_THREAD) {
+        let new_task = current_process.clone_thread();
+        let new_tid = new_task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        (new_task, new_tid)
+    } else {
+        let new_process = current_process.clone_process(flags)?;
+        let new_task = new_process.inner_exclusive_access().get_task(0);
+        (new_task, new_process.getpid())
+    };
     // modify trap context of new_task, because it returns immediately after switching
-    let new_process_inner = new_process.inner_exclusive_access();
-    let task = new_process_inner.tasks[0].as_ref().unwrap();
-    let trap_cx = task.inner_exclusive_access().get_trap_cx();
+    let mut new_task_inner = new_task.inner_exclusive_access();
+    let trap_cx = new_task_inner.get_trap_cx();
     // we do not have to move to next instruction since we have done it before
-    // for child process, fork returns 0
+    // for the child, clone returns 0
     trap_cx.x[10] = 0;
-    new_pid as isize
+    if stack != 0 {
+        trap_cx.set_sp(stack);
+    }
+    if flags.contains(CloneFlags::CLONE_SETTLS) {
+        trap_cx.x[4] = tls;
+    }
+    if flags.contains(CloneFlags::CLONE_CHILD_CLEARTID) {
+        new_task_inner.clear_child_tid = ctid as usize;
+    }
+    // the child inherits the blocked mask of the cloning thread
+    new_task_inner.signal_mask = current_task().unwrap().inner_exclusive_access().signal_mask;
+    drop(new_task_inner);
+    if flags.contains(CloneFlags::CLONE_PARENT_SETTID) {
+        // like Linux, a bad ptid does not fail the clone
+        let _ = copy_to_user(ptid, &(new_id as i32));
+    }
+    // only now may another hart pick the child up
+    add_task(new_task);
+    Ok(new_id as isize)
 }
-/// exec syscall
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    trace!(
-        "kernel:pid[{}] sys_exec",
-        current_task().unwrap().process.upgrade().unwrap().getpid()
-    );
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    let mut args_vec: Vec<String> = Vec::new();
+/// Read a NULL-terminated array of user strings such as argv or envp.
+/// A null array is taken as empty.
+fn user_str_array(mut array: *const usize) -> SysResult<Vec<String>> {
+    let mut strs: Vec<String> = Vec::new();
+    if array.is_null() {
+        return Ok(strs);
+    }
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
-        if arg_str_ptr == 0 {
+        let str_ptr = copy_from_user(array)?;
+        if str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
+        strs.push(user_str(str_ptr as *const u8)?);
         unsafe {
-            args = args.add(1);
-        }
-    }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        if all_data.len() == 0 {
-            return -1;
+            array = array.add(1);
         }
-        let process = current_process();
-        let argc = args_vec.len();
-        process.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
-    } else {
-        -1
     }
+    Ok(strs)
 }
 
-/// waitpid syscall
-///
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    //trace!("kernel: sys_waitpid");
+/// execve syscall
+pub fn sys_execve(path: *const u8, argv: *const usize, envp: *const usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_execve",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let path = user_str(path)?;
+    let args_vec = user_str_array(argv)?;
+    let envs_vec = user_str_array(envp)?;
+    let path = at_path(AT_FDCWD, &path)?;
+    let app_inode = open_file(path.as_str(), OpenFlags::RDONLY)?;
+    let all_data = app_inode.read_all();
     let process = current_process();
-    // find a child process
+    process.exec(all_data.as_slice(), args_vec, envs_vec)?;
+    // a0 is not part of the entry ABI, the C runtime takes 0 as "no
+    // rtld_fini"; argc and argv are found on the stack
+    Ok(0)
+}
 
-    let mut inner = process.inner_exclusive_access();
-    if !inner
-        .children
-        .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        return -1;
-        // ---- release current PCB
-    }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    } else {
-        -2
+/// wait4 syscall
+///
+/// Wait for a child matching `pid` (-1 for any child, 0 for one in the
+/// caller's process group, below -1 for one in group -`pid`) to exit, or to
+/// stop if WUNTRACED is given, and return its pid. The caller sleeps until a
+/// child changes state unless WNOHANG is given, in which case 0 is returned.
+/// If there is no such child, return -ECHILD.
+pub fn sys_wait4(pid: isize, wstatus: *mut i32, options: usize, rusage: *mut RUsage) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_wait4",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let options = WaitOptions::from_bits_truncate(options);
+    let process = current_process();
+    let own_pgid = process.inner_exclusive_access().pgid;
+    let matches = |child_pid: usize, child_pgid: usize| match pid {
+        -1 => true,
+        0 => child_pgid == own_pgid,
+        pid if pid < -1 => child_pgid == pid.unsigned_abs(),
+        pid => child_pid == pid as usize,
+    };
+    loop {
+        let inner = process.inner_exclusive_access();
+        if !inner.children.iter().any(|p| matches(p.getpid(), p.inner_exclusive_access().pgid)) {
+            return Err(Errno::ECHILD);
+            // ---- release current PCB
+        }
+        let zombie = inner.children.iter().position(|p| {
+            // ++++ temporarily access child PCB exclusively
+            let child_inner = p.inner_exclusive_access();
+            matches(p.getpid(), child_inner.pgid) && child_inner.is_zombie
+            // ++++ release child PCB
+        });
+        if let Some(idx) = zombie {
+            let child = Arc::clone(&inner.children[idx]);
+            let child_inner = child.inner_exclusive_access();
+            let status = child_inner.wait_status();
+            let usage = RUsage {
+                utime: TimeVal::from_us(child_inner.utime),
+                stime: TimeVal::from_us(child_inner.stime),
+                ..Default::default()
+            };
+            drop(child_inner);
+            // user memory may have to be read in from a file mapping
+            drop(inner);
+            // leave the zombie in place if its status can not be stored
+            if !wstatus.is_null() {
+                copy_to_user(wstatus, &status)?;
+            }
+            if !rusage.is_null() {
+                copy_to_user(rusage, &usage)?;
+            }
+            let mut inner = process.inner_exclusive_access();
+            // another thread may have reaped it meanwhile
+            let Some(idx) = inner.children.iter().position(|p| Arc::ptr_eq(p, &child)) else {
+                continue;
+            };
+            // the exiting thread may still hold a reference for a moment, the
+            // process is deallocated when the last one is dropped
+            inner.children.remove(idx);
+            return Ok(child.getpid() as isize);
+        }
+        if options.contains(WaitOptions::WUNTRACED) {
+            let stopped = inner.children.iter().position(|p| {
+                let child_inner = p.inner_exclusive_access();
+                matches(p.getpid(), child_inner.pgid) && child_inner.stop_signal.is_some()
+            });
+            if let Some(idx) = stopped {
+                let child = Arc::clone(&inner.children[idx]);
+                let child_inner = child.inner_exclusive_access();
+                let status = ((child_inner.stop_signal.unwrap() as i32) << 8) | 0x7f;
+                let usage = RUsage {
+                    utime: TimeVal::from_us(child_inner.utime),
+                    stime: TimeVal::from_us(child_inner.stime),
+                    ..Default::default()
+                };
+                drop(child_inner);
+                drop(inner);
+                if !wstatus.is_null() {
+                    copy_to_user(wstatus, &status)?;
+                }
+                if !rusage.is_null() {
+                    copy_to_user(rusage, &usage)?;
+                }
+                // a stop is reported only once
+                child.inner_exclusive_access().stop_signal = None;
+                return Ok(child.getpid() as isize);
+            }
+        }
+        if options.contains(WaitOptions::WNOHANG) {
+            return Ok(0);
+        }
+        if current_signal_pending_locked(&inner) {
+            return Err(Errno::EINTR);
+        }
+        // woken up by exiting or stopping children, or by a signal; queue up
+        // before the lock is released so a child exiting meanwhile is not missed
+        process.wait_queue.wait_unlock_interruptible(inner);
     }
-    // ---- release current PCB automatically
 }
 
 /// kill syscall
-pub fn sys_kill(pid: usize, signal: u32) -> isize {
+pub fn sys_kill(pid: usize, signum: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_kill",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    if let Some(process) = pid2process(pid) {
-        if let Some(flag) = SignalFlags::from_bits(signal) {
-            process.inner_exclusive_access().signals |= flag;
-            0
-        } else {
-            -1
-        }
+    let process = pid2process(pid).ok_or(Errno::ESRCH)?;
+    // signal 0 only checks whether the process exists
+    if signum == 0 {
+        return Ok(0);
+    }
+    let flag = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    send_signal(&process, flag);
+    Ok(0)
+}
+
+/// sigaction syscall
+///
+/// Install `action` for `signum` if it is not null, and store the previous
+/// action to `old_action` if it is not null.
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_sigaction",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let flag = SignalFlags::from_signum(signum).ok_or(Errno::EINVAL)?;
+    let new_action = if action.is_null() {
+        None
     } else {
-        -1
+        Some(copy_from_user(action)?)
+    };
+    // SIGKILL and SIGSTOP can not be caught or ignored
+    if new_action.is_some() && SignalFlags::unmaskable().contains(flag) {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let current = inner.signal_actions().table[signum];
+    if let Some(mut new_action) = new_action {
+        new_action.mask -= SignalFlags::unmaskable();
+        inner.signal_actions().table[signum] = new_action;
     }
+    drop(inner);
+    if !old_action.is_null() {
+        copy_to_user(old_action, &current)?;
+    }
+    Ok(0)
+}
+
+/// sigprocmask syscall
+///
+/// Change the blocked mask of the current thread according to `how`.
+pub fn sys_sigprocmask(how: usize, set: *const u64, old_set: *mut u64) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_sigprocmask",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    if !set.is_null() {
+        let set = SignalFlags::from_bits_truncate(copy_from_user(set)?);
+        let new_mask = match how {
+            SIG_BLOCK => old_mask | set,
+            SIG_UNBLOCK => old_mask - set,
+            SIG_SETMASK => set,
+            _ => return Err(Errno::EINVAL),
+        };
+        inner.signal_mask = new_mask - SignalFlags::unmaskable();
+    }
+    if !old_set.is_null() {
+        if let Err(err) = copy_to_user(old_set, &old_mask.bits()) {
+            inner.signal_mask = old_mask;
+            return Err(err.into());
+        }
+    }
+    Ok(0)
+}
+
+/// sigreturn syscall
+///
+/// Return from a signal handler through the frame on the user stack.
+pub fn sys_sigreturn() -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_sigreturn",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    if !current_sigreturn() {
+        // a corrupted frame is fatal
+        current_force_signal(SignalFlags::SIGSEGV);
+        return Err(Errno::EFAULT);
+    }
+    // the restored a0 is written back as the return value
+    Ok(current_trap_cx().x[10] as isize)
 }
 
 /// get_time syscall
 ///
-/// YOUR JOB: get time with second and microsecond
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TimeVal`] is splitted by two pages ?
-pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
-    let token = current_user_token();
-    let time = get_time_us();
-    // TODO: split TimeVal into two pages
-    *translated_refmut(token, ts) = TimeVal {
-        sec: time / 1_000_000,
-        usec: time % 1_000_000,
-    };
-    0
+/// Get time with second and microsecond. The [`TimeVal`] may span two pages.
+pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> SysResult {
+    copy_to_user(ts, &TimeVal::from_us(get_time_us()))?;
+    Ok(0)
 }
 
 /// task_info syscall
 ///
-/// YOUR JOB: Finish sys_task_info to pass testcases
-/// HINT: You might reimplement it with virtual memory management.
-/// HINT: What if [`TaskInfo`] is splitted by two pages ?
-pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
+/// Copy the [`TaskInfo`] of the current task, which may span two pages.
+pub fn sys_task_info(ti: *mut TaskInfo) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_task_info NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_task_info",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    *translated_refmut(current_user_token(), ti) = fetch_task_info();
-    0
+    copy_to_user(ti, &fetch_task_info())?;
+    Ok(0)
 }
 
 /// mmap syscall
 ///
-/// YOUR JOB: Implement mmap.
-pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
+/// Map `len` bytes of anonymous memory, or of the file `fd` from `offset`,
+/// at `start` or at an address picked by the kernel when `start` is 0.
+/// Return the start address of the mapping.
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_mmap",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let flags = MmapFlags::from_bits_truncate(flags);
+    // exactly one of MAP_SHARED and MAP_PRIVATE
+    if flags.contains(MmapFlags::MAP_SHARED) == flags.contains(MmapFlags::MAP_PRIVATE) {
+        return Err(Errno::EINVAL);
+    }
+    if !offset.is_multiple_of(PAGE_SIZE) || len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    if flags.contains(MmapFlags::MAP_FIXED) && !start.is_multiple_of(PAGE_SIZE) {
+        return Err(Errno::EINVAL);
+    }
+    let permission = prot_to_permission(prot).ok_or(Errno::EINVAL)?;
+    let file = if flags.contains(MmapFlags::MAP_ANONYMOUS) {
+        None
+    } else {
+        let process = current_process();
+        let inner = process.inner_exclusive_access();
+        let file = match inner.fd_table().get(fd) {
+            Some(Some(entry)) => entry.file.clone(),
+            _ => return Err(Errno::EBADF),
+        };
+        drop(inner);
+        if !file.readable() {
+            return Err(Errno::EACCES);
+        }
+        // a shared writable mapping writes through to the file
+        if flags.contains(MmapFlags::MAP_SHARED)
+            && permission.contains(MapPermission::W)
+            && !file.writable()
+        {
+            return Err(Errno::EACCES);
+        }
+        // only regular files can be mapped
+        let node = file.node().ok_or(Errno::ENODEV)?;
+        Some(FileBacking { node, offset, writable: file.writable() })
+    };
+    current_task_memset_mmap(start, len, permission, flags, file)
+}
+
+/// msync syscall
+///
+/// Write dirty pages of shared file mappings in the range back to the files.
+pub fn sys_msync(start: usize, len: usize, _flags: usize) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_mmap NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_msync",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    current_task_memset_mmap(start, len, port)
+    current_task_memset_msync(start, len)
 }
 
 /// munmap syscall
 ///
-/// YOUR JOB: Implement munmap.
-pub fn sys_munmap(start: usize, len: usize) -> isize {
+/// Unmap the pages of `[start, start + len)`, which may cover only part of a mapping.
+pub fn sys_munmap(start: usize, len: usize) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_munmap NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_munmap",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
     current_task_memset_munmap(start, len)
 }
 
+/// mprotect syscall
+///
+/// Change the protection of the pages of `[start, start + len)`.
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_mprotect",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    if !start.is_multiple_of(PAGE_SIZE) {
+        return Err(Errno::EINVAL);
+    }
+    let permission = prot_to_permission(prot).ok_or(Errno::EINVAL)?;
+    current_task_memset_mprotect(start, len, permission)
+}
+
+/// Convert PROT_READ/PROT_WRITE/PROT_EXEC bits to a map permission
+fn prot_to_permission(prot: usize) -> Option<MapPermission> {
+    // prot: [X, W, R]
+    if prot > 7 {
+        return None;
+    }
+    let mut permission = MapPermission::empty();
+    if prot & (1 << 0) != 0 {
+        permission |= MapPermission::R;
+    }
+    if prot & (1 << 1) != 0 {
+        permission |= MapPermission::W;
+    }
+    if prot & (1 << 2) != 0 {
+        permission |= MapPermission::X;
+    }
+    Some(permission)
+}
+
 /// change data segment size
-pub fn sys_sbrk(size: i32) -> isize {
+pub fn sys_sbrk(size: i32) -> SysResult {
     trace!(
         "kernel:pid[{}] sys_sbrk",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    if let Some(old_brk) = current_process().change_program_brk(size) {
-        old_brk as isize
-    } else {
-        -1
+    match current_process().change_program_brk(size) {
+        Some(old_brk) => Ok(old_brk as isize),
+        None => Err(Errno::ENOMEM),
     }
 }
 
 /// spawn syscall
-/// YOUR JOB: Implement spawn.
-/// HINT: fork + exec =/= spawn
-pub fn sys_spawn(path: *const u8) -> isize {
+pub fn sys_spawn(path: *const u8) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_spawn NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_spawn",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let path = translated_str(current_user_token(), path);
-    current_task_spawn(path.as_str())
+    current_task_spawn(user_str(path)?.as_str())
 }
 
 /// set priority syscall
-///
-/// YOUR JOB: Set task priority
-pub fn sys_set_priority(prio: isize) -> isize {
+pub fn sys_set_priority(prio: isize) -> SysResult {
     trace!(
-        "kernel:pid[{}] sys_set_priority NOT IMPLEMENTED",
+        "kernel:pid[{}] sys_set_priority",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
     if prio <= 1 {
-        -1
+        Err(Errno::EINVAL)
     } else {
-        let mut current_task = current_task().unwrap();
-        current_task.set_priority(prio as usize) as isize
+        Ok(current_task().unwrap().set_priority(prio as usize) as isize)
     }
 }
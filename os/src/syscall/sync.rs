@@ -1,9 +1,17 @@
-use crate::sync::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
-use crate::process::{block_current_and_run_next, current_process, current_task};
+use crate::mem::PhysAddr;
+use crate::sync::{
+    futex_requeue, futex_wait, futex_wake, Condvar, FutexWakeup, Mutex, MutexBlocking, MutexSpin,
+    Semaphore,
+};
+use crate::process::{
+    block_current_and_run_next, copy_from_user, current_process, current_task, user_phys_addr,
+};
 use crate::time::{add_timer, get_time_ms};
+use super::process::TimeSpec;
+use super::{Errno, SysResult};
 use alloc::sync::Arc;
 /// sleep syscall
-pub fn sys_sleep(ms: usize) -> isize {
+pub fn sys_sleep(ms: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_sleep",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -19,10 +27,10 @@ pub fn sys_sleep(ms: usize) -> isize {
     let task = current_task().unwrap();
     add_timer(expire_ms, task);
     block_current_and_run_next();
-    0
+    Ok(0)
 }
 /// mutex create syscall
-pub fn sys_mutex_create(blocking: bool) -> isize {
+pub fn sys_mutex_create(blocking: bool) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_mutex_create",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -49,15 +57,15 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
         .map(|(id, _)| id)
     {
         process_inner.mutex_list[id] = mutex;
-        id as isize
+        Ok(id as isize)
     } else {
         process_inner.locker.add(0);
         process_inner.mutex_list.push(mutex);
-        process_inner.mutex_list.len() as isize - 1
+        Ok(process_inner.mutex_list.len() as isize - 1)
     }
 }
 /// mutex lock syscall
-pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+pub fn sys_mutex_lock(mutex_id: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_mutex_lock",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -81,17 +89,21 @@ pub fn sys_mutex_lock(mutex_id: usize) -> isize {
             .tid, 
         0
     ) != 0 {
-        return -0xDEAD;
+        return Err(Errno::EDEADLK);
     }
 
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = process_inner
+        .mutex_list
+        .get(mutex_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
     drop(process_inner);
     drop(process);
     mutex.lock();
-    0
+    Ok(0)
 }
 /// mutex unlock syscall
-pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+pub fn sys_mutex_unlock(mutex_id: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_mutex_unlock",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -115,14 +127,18 @@ pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
             .tid,
         0
     );
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = process_inner
+        .mutex_list
+        .get(mutex_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
     drop(process_inner);
     drop(process);
     mutex.unlock();
-    0
+    Ok(0)
 }
 /// semaphore create syscall
-pub fn sys_semaphore_create(res_count: usize) -> isize {
+pub fn sys_semaphore_create(res_count: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_semaphore_create",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -152,10 +168,10 @@ pub fn sys_semaphore_create(res_count: usize) -> isize {
             .push(Some(Arc::new(Semaphore::new(res_count))));
         process_inner.semaphore_list.len() - 1
     };
-    id as isize
+    Ok(id as isize)
 }
 /// semaphore up syscall
-pub fn sys_semaphore_up(sem_id: usize) -> isize {
+pub fn sys_semaphore_up(sem_id: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_semaphore_up",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -179,13 +195,17 @@ pub fn sys_semaphore_up(sem_id: usize) -> isize {
             .tid,
             1
         );
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let sem = process_inner
+        .semaphore_list
+        .get(sem_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
     drop(process_inner);
     sem.up();
-    0
+    Ok(0)
 }
 /// semaphore down syscall
-pub fn sys_semaphore_down(sem_id: usize) -> isize {
+pub fn sys_semaphore_down(sem_id: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_semaphore_down",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -209,16 +229,20 @@ pub fn sys_semaphore_down(sem_id: usize) -> isize {
             .tid,
             1
         ) != 0 || sem_id > 2 {
-        return -0xDEAD;
+        return Err(Errno::EDEADLK);
     }
 
-    let sem = Arc::clone(process_inner.semaphore_list[sem_id].as_ref().unwrap());
+    let sem = process_inner
+        .semaphore_list
+        .get(sem_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
     drop(process_inner);
     sem.down();
-    0
+    Ok(0)
 }
 /// condvar create syscall
-pub fn sys_condvar_create() -> isize {
+pub fn sys_condvar_create() -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_condvar_create",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -247,10 +271,10 @@ pub fn sys_condvar_create() -> isize {
             .push(Some(Arc::new(Condvar::new())));
         process_inner.condvar_list.len() - 1
     };
-    id as isize
+    Ok(id as isize)
 }
 /// condvar signal syscall
-pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+pub fn sys_condvar_signal(condvar_id: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_condvar_signal",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -264,13 +288,17 @@ pub fn sys_condvar_signal(condvar_id: usize) -> isize {
     );
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
+    let condvar = process_inner
+        .condvar_list
+        .get(condvar_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
     drop(process_inner);
     condvar.signal();
-    0
+    Ok(0)
 }
 /// condvar wait syscall
-pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_condvar_wait",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -284,25 +312,96 @@ pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     );
     let process = current_process();
     let process_inner = process.inner_exclusive_access();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let condvar = process_inner
+        .condvar_list
+        .get(condvar_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
+    let mutex = process_inner
+        .mutex_list
+        .get(mutex_id)
+        .and_then(Option::clone)
+        .ok_or(Errno::EINVAL)?;
     drop(process_inner);
     condvar.wait(mutex);
-    0
+    Ok(0)
 }
 /// enable deadlock detection syscall
 ///
-/// YOUR JOB: Implement deadlock detection, but might not all in this syscall
-pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
-    trace!("kernel: sys_enable_deadlock_detect NOT IMPLEMENTED");
+pub fn sys_enable_deadlock_detect(enabled: usize) -> SysResult {
+    trace!("kernel: sys_enable_deadlock_detect");
 
     if enabled == 0 || enabled == 1 {
         let process = current_process();
         let mut process_inner = process.inner_exclusive_access();
         process_inner.deadlock_detect = enabled == 1;
 
-        return 0;
+        return Ok(0);
+    }
+
+    Err(Errno::EINVAL)
+}
+
+/// futex operation: wait if the futex word still holds `val`
+const FUTEX_WAIT: usize = 0;
+/// futex operation: wake waiters
+const FUTEX_WAKE: usize = 1;
+/// futex operation: wake waiters and move the rest to another futex
+const FUTEX_REQUEUE: usize = 3;
+/// futex operation: FUTEX_REQUEUE if the futex word still holds `val3`
+const FUTEX_CMP_REQUEUE: usize = 4;
+/// futex flag: the futex is private to the process, which changes nothing here
+const FUTEX_PRIVATE_FLAG: usize = 128;
+/// futex flag: the timeout is measured against CLOCK_REALTIME
+const FUTEX_CLOCK_REALTIME: usize = 256;
+
+/// The key of the futex word at `uaddr`, which must be 4-byte aligned
+fn futex_key(uaddr: usize) -> SysResult<PhysAddr> {
+    if !uaddr.is_multiple_of(core::mem::size_of::<u32>()) {
+        return Err(Errno::EINVAL);
     }
+    Ok(user_phys_addr(uaddr)?)
+}
 
-    -1
+/// futex syscall
+pub fn sys_futex(
+    uaddr: usize,
+    op: usize,
+    val: usize,
+    timeout_or_val2: usize,
+    uaddr2: usize,
+    val3: u32,
+) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_futex",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let key = futex_key(uaddr)?;
+    match op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+        FUTEX_WAIT => {
+            let expire_ms = if timeout_or_val2 == 0 {
+                None
+            } else {
+                let timeout = copy_from_user(timeout_or_val2 as *const TimeSpec)?;
+                Some(get_time_ms() + timeout.as_ms().ok_or(Errno::EINVAL)?)
+            };
+            if copy_from_user(uaddr as *const u32)? != val as u32 {
+                return Err(Errno::EAGAIN);
+            }
+            match futex_wait(key, expire_ms) {
+                FutexWakeup::Woken => Ok(0),
+                FutexWakeup::TimedOut => Err(Errno::ETIMEDOUT),
+                FutexWakeup::Interrupted => Err(Errno::EINTR),
+            }
+        }
+        FUTEX_WAKE => Ok(futex_wake(key, val) as isize),
+        op @ (FUTEX_REQUEUE | FUTEX_CMP_REQUEUE) => {
+            let key2 = futex_key(uaddr2)?;
+            if op == FUTEX_CMP_REQUEUE && copy_from_user(uaddr as *const u32)? != val3 {
+                return Err(Errno::EAGAIN);
+            }
+            Ok(futex_requeue(key, val, key2, timeout_or_val2) as isize)
+        }
+        _ => Err(Errno::ENOSYS),
+    }
 }
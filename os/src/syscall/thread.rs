@@ -1,11 +1,15 @@
 use crate::{
     mem::kernel_token,
-    process::{add_task, current_task, TaskControlBlock},
+    process::{add_task, copy_to_user, current_process, current_task},
     trap::{trap_handler, TrapContext},
 };
+use super::{Errno, SysResult};
 use alloc::sync::Arc;
 /// thread create syscall
-pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+///
+/// A thread made by clone with CLONE_THREAD, which then starts over at
+/// `entry` on its own user stack with `arg` in a0.
+pub fn sys_thread_create(entry: usize, arg: usize) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_thread_create",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -17,32 +21,13 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
             .unwrap()
             .tid
     );
-    let task = current_task().unwrap();
-    let process = task.process.upgrade().unwrap();
-    // create a new thread
-    let new_task = Arc::new(TaskControlBlock::new(
-        Arc::clone(&process),
-        task.inner_exclusive_access()
-            .res
-            .as_ref()
-            .unwrap()
-            .ustack_base,
-        true,
-    ));
-    // add new task to scheduler
-    add_task(Arc::clone(&new_task));
+    let new_task = current_process().clone_thread();
+    // a new thread inherits the blocked mask of its creator
+    new_task.inner_exclusive_access().signal_mask =
+        current_task().unwrap().inner_exclusive_access().signal_mask;
     let new_task_inner = new_task.inner_exclusive_access();
     let new_task_res = new_task_inner.res.as_ref().unwrap();
     let new_task_tid = new_task_res.tid;
-    let mut process_inner = process.inner_exclusive_access();
-    // init semaphore locker
-    process_inner.locker.init();
-    // add new thread to current process
-    let tasks = &mut process_inner.tasks;
-    while tasks.len() < new_task_tid + 1 {
-        tasks.push(None);
-    }
-    tasks[new_task_tid] = Some(Arc::clone(&new_task));
     let new_task_trap_cx = new_task_inner.get_trap_cx();
     *new_task_trap_cx = TrapContext::app_init_context(
         entry,
@@ -52,10 +37,13 @@ pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
         trap_handler as usize,
     );
     (*new_task_trap_cx).x[10] = arg;
-    new_task_tid as isize
+    drop(new_task_inner);
+    // only now may another hart pick the thread up
+    add_task(new_task);
+    Ok(new_task_tid as isize)
 }
 /// get current thread id syscall
-pub fn sys_gettid() -> isize {
+pub fn sys_gettid() -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_gettid",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -67,21 +55,38 @@ pub fn sys_gettid() -> isize {
             .unwrap()
             .tid
     );
-    current_task()
+    Ok(current_task()
         .unwrap()
         .inner_exclusive_access()
         .res
         .as_ref()
         .unwrap()
-        .tid as isize
+        .tid as isize)
+}
+
+/// set_tid_address syscall
+///
+/// `tidptr` is cleared when the calling thread exits, as with
+/// CLONE_CHILD_CLEARTID. Return the tid of the caller.
+pub fn sys_set_tid_address(tidptr: *mut i32) -> SysResult {
+    trace!(
+        "kernel:pid[{}] sys_set_tid_address",
+        current_task().unwrap().process.upgrade().unwrap().getpid()
+    );
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.clear_child_tid = tidptr as usize;
+    Ok(task_inner.res.as_ref().unwrap().tid as isize)
 }
 
 /// wait for a thread to exit syscall
 ///
-/// thread does not exist, return -1
-/// thread has not exited yet, return -2
-/// otherwise, return thread's exit code
-pub fn sys_waittid(tid: usize) -> i32 {
+/// thread does not exist, return -ESRCH
+/// thread is the caller itself, return -EDEADLK
+/// thread has not exited yet, return -EAGAIN
+/// otherwise, store thread's exit code to `exit_code` unless it is null
+/// and return 0
+pub fn sys_waittid(tid: usize, exit_code: *mut i32) -> SysResult {
     trace!(
         "kernel:pid[{}] tid[{}] sys_waittid",
         current_task().unwrap().process.upgrade().unwrap().getpid(),
@@ -95,28 +100,25 @@ pub fn sys_waittid(tid: usize) -> i32 {
     );
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
-    let task_inner = task.inner_exclusive_access();
-    let mut process_inner = process.inner_exclusive_access();
     // a thread cannot wait for itself
-    if task_inner.res.as_ref().unwrap().tid == tid {
-        return -1;
+    if task.inner_exclusive_access().res.as_ref().unwrap().tid == tid {
+        return Err(Errno::EDEADLK);
     }
-    let mut exit_code: Option<i32> = None;
-    let waited_task = process_inner.tasks[tid].as_ref();
-    if let Some(waited_task) = waited_task {
-        if let Some(waited_exit_code) = waited_task.inner_exclusive_access().exit_code {
-            exit_code = Some(waited_exit_code);
-        }
-    } else {
-        // waited thread does not exist
-        return -1;
+    let process_inner = process.inner_exclusive_access();
+    let waited_task = process_inner.tasks.get(tid).and_then(Option::as_ref);
+    // waited thread does not exist
+    let waited_task = Arc::clone(waited_task.ok_or(Errno::ESRCH)?);
+    // waited thread has not exited
+    let waited_exit_code = waited_task.inner_exclusive_access().exit_code.ok_or(Errno::EAGAIN)?;
+    drop(process_inner);
+    if !exit_code.is_null() {
+        copy_to_user(exit_code, &waited_exit_code)?;
     }
-    if let Some(exit_code) = exit_code {
-        // dealloc the exited thread
-        process_inner.tasks[tid] = None;
-        exit_code
-    } else {
-        // waited thread has not exited
-        -2
+    // dealloc the exited thread, unless another thread has joined it meanwhile
+    let mut process_inner = process.inner_exclusive_access();
+    match process_inner.tasks.get_mut(tid) {
+        Some(slot) if slot.as_ref().is_some_and(|task| Arc::ptr_eq(task, &waited_task)) => *slot = None,
+        _ => return Err(Errno::ESRCH),
     }
+    Ok(0)
 }
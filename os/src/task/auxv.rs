@@ -0,0 +1,97 @@
+//! The auxiliary vector placed above envp on a new program's stack
+//!
+//! Entry types follow Linux `include/uapi/linux/auxvec.h`; the C runtime
+//! reads them to find its program headers, the page size and so on.
+
+use crate::config::PAGE_SIZE;
+use crate::mem::ElfInfo;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// end of vector
+pub const AT_NULL: usize = 0;
+/// program headers for program
+pub const AT_PHDR: usize = 3;
+/// size of program header entry
+pub const AT_PHENT: usize = 4;
+/// number of program headers
+pub const AT_PHNUM: usize = 5;
+/// system page size
+pub const AT_PAGESZ: usize = 6;
+/// base address of interpreter
+pub const AT_BASE: usize = 7;
+/// flags
+pub const AT_FLAGS: usize = 8;
+/// entry point of program
+pub const AT_ENTRY: usize = 9;
+/// real uid
+pub const AT_UID: usize = 11;
+/// effective uid
+pub const AT_EUID: usize = 12;
+/// real gid
+pub const AT_GID: usize = 13;
+/// effective gid
+pub const AT_EGID: usize = 14;
+/// arch dependent hints at CPU capabilities
+pub const AT_HWCAP: usize = 16;
+/// frequency at which times() increments
+pub const AT_CLKTCK: usize = 17;
+/// secure mode boolean
+pub const AT_SECURE: usize = 23;
+/// address of 16 random bytes
+pub const AT_RANDOM: usize = 25;
+/// filename of program
+pub const AT_EXECFN: usize = 31;
+
+/// clock ticks per second reported to user space, as on Linux
+const USER_HZ: usize = 100;
+
+/// The ISA extensions of the hart, one bit per letter: rv64imafdc
+const HWCAP_RISCV: usize = {
+    const fn ext(c: u8) -> usize {
+        1 << (c - b'a')
+    }
+    ext(b'i') | ext(b'm') | ext(b'a') | ext(b'f') | ext(b'd') | ext(b'c')
+};
+
+/// The auxiliary vector of an image described by `info`, terminated by
+/// AT_NULL. `random` and `execfn` are user addresses already on the stack.
+pub fn auxv(info: &ElfInfo, random: usize, execfn: usize) -> Vec<(usize, usize)> {
+    vec![
+        (AT_PHDR, info.phdr),
+        (AT_PHENT, info.phent),
+        (AT_PHNUM, info.phnum),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_BASE, 0),
+        (AT_FLAGS, 0),
+        (AT_ENTRY, info.entry),
+        (AT_UID, 0),
+        (AT_EUID, 0),
+        (AT_GID, 0),
+        (AT_EGID, 0),
+        (AT_HWCAP, HWCAP_RISCV),
+        (AT_CLKTCK, USER_HZ),
+        (AT_SECURE, 0),
+        (AT_RANDOM, random),
+        (AT_EXECFN, execfn),
+        (AT_NULL, 0),
+    ]
+}
+
+/// 16 bytes for AT_RANDOM, good enough to seed stack protectors and
+/// pointer guards: there is no entropy source besides the timer.
+pub fn random_bytes(seed: usize) -> [u8; 16] {
+    // splitmix64
+    let mut state = (crate::time::get_time() ^ seed.rotate_left(32)) as u64;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&next().to_le_bytes());
+    bytes[8..].copy_from_slice(&next().to_le_bytes());
+    bytes
+}
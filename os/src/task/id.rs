@@ -1,9 +1,9 @@
 //! Allocator for pid, task user resource, kernel stack using a simple recycle strategy.
 
-use super::ProcessControlBlock;
+use super::{AddressSpace, ProcessControlBlock};
 use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
-use crate::mem::{MapPermission, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::mem::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::sync::SpinNoIrqLock;
 use alloc::{
     sync::{Arc, Weak},
     vec::Vec,
@@ -33,6 +33,19 @@ impl RecycleAllocator {
             self.current - 1
         }
     }
+    /// mark `id` as allocated, whether it is fresh or recycled
+    pub fn take(&mut self, id: usize) {
+        if id >= self.current {
+            self.recycled.extend(self.current..id);
+            self.current = id + 1;
+        } else {
+            self.recycled.retain(|i| *i != id);
+        }
+    }
+    /// the items in use
+    pub fn allocated(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.current).filter(move |id| !self.recycled.contains(id))
+    }
     /// deallocate an item
     pub fn dealloc(&mut self, id: usize) {
         assert!(id < self.current);
@@ -47,11 +60,11 @@ impl RecycleAllocator {
 
 lazy_static! {
     /// Glocal allocator for pid
-    static ref PID_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    static ref PID_ALLOCATOR: SpinNoIrqLock<RecycleAllocator> =
+        SpinNoIrqLock::new(RecycleAllocator::new());
     /// Global allocator for kernel stack
-    static ref KSTACK_ALLOCATOR: UPSafeCell<RecycleAllocator> =
-        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    static ref KSTACK_ALLOCATOR: SpinNoIrqLock<RecycleAllocator> =
+        SpinNoIrqLock::new(RecycleAllocator::new());
 }
 
 /// The idle task's pid is 0
@@ -62,13 +75,13 @@ pub struct PidHandle(pub usize);
 
 /// Allocate a pid for a process
 pub fn pid_alloc() -> PidHandle {
-    PidHandle(PID_ALLOCATOR.exclusive_access().alloc())
+    PidHandle(PID_ALLOCATOR.lock().alloc())
 }
 
 impl Drop for PidHandle {
     fn drop(&mut self) {
         // trace!("drop pid {}", self.0);
-        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 
@@ -84,13 +97,14 @@ pub struct KernelStack(pub usize);
 
 /// Allocate a kernel stack for a task
 pub fn kstack_alloc() -> KernelStack {
-    let kstack_id = KSTACK_ALLOCATOR.exclusive_access().alloc();
+    let kstack_id = KSTACK_ALLOCATOR.lock().alloc();
     let (kstack_bottom, kstack_top) = kernel_stack_position(kstack_id);
-    KERNEL_SPACE.exclusive_access().insert_framed_area(
+    KERNEL_SPACE.lock().insert_framed_area(
         kstack_bottom.into(),
         kstack_top.into(),
         MapPermission::R | MapPermission::W,
-    );
+    )
+    .expect("out of frames");
     KernelStack(kstack_id)
 }
 
@@ -99,9 +113,9 @@ impl Drop for KernelStack {
         let (kernel_stack_bottom, _) = kernel_stack_position(self.0);
         let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
         KERNEL_SPACE
-            .exclusive_access()
+            .lock()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
-        KSTACK_ALLOCATOR.exclusive_access().dealloc(self.0);
+        KSTACK_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 
@@ -127,21 +141,38 @@ impl KernelStack {
 }
 
 /// User Resource for a task
+///
+/// The user stack and trap context of a thread sit in a slot of the address
+/// space it runs in. Slots are numbered separately from tids because an
+/// address space may be shared by several processes (CLONE_VM).
 pub struct TaskUserRes {
     /// task id
     pub tid: usize,
+    /// slot of the user stack and trap context in the address space
+    pub slot: usize,
     /// user stack base
     pub ustack_base: usize,
     /// process belongs to
     pub process: Weak<ProcessControlBlock>,
 }
-/// Return the bottom addr (low addr) of the trap context for a task
-fn trap_cx_bottom_from_tid(tid: usize) -> usize {
-    TRAP_CONTEXT_BASE - tid * PAGE_SIZE
+/// Return the bottom addr (low addr) of the trap context in a slot
+fn trap_cx_bottom_from_slot(slot: usize) -> usize {
+    TRAP_CONTEXT_BASE - slot * PAGE_SIZE
+}
+/// Return the bottom addr (high addr) of the user stack in a slot
+fn ustack_bottom_from_slot(ustack_base: usize, slot: usize) -> usize {
+    ustack_base + slot * (PAGE_SIZE + USER_STACK_SIZE)
 }
-/// Return the bottom addr (high addr) of the user stack for a task
-fn ustack_bottom_from_tid(ustack_base: usize, tid: usize) -> usize {
-    ustack_base + tid * (PAGE_SIZE + USER_STACK_SIZE)
+
+/// Remove the user stacks and trap contexts of all slots of `vm` but
+/// `keep` from `memory_set`, a fresh copy of the memory set of `vm`
+pub fn remove_other_slots(memory_set: &mut MemorySet, vm: &AddressSpace, ustack_base: usize, keep: usize) {
+    for slot in vm.slots.allocated().filter(|slot| *slot != keep) {
+        let ustack_bottom_va: VirtAddr = ustack_bottom_from_slot(ustack_base, slot).into();
+        memory_set.remove_area_with_start_vpn(ustack_bottom_va.into());
+        let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_slot(slot).into();
+        memory_set.remove_area_with_start_vpn(trap_cx_bottom_va.into());
+    }
 }
 
 impl TaskUserRes {
@@ -151,9 +182,13 @@ impl TaskUserRes {
         ustack_base: usize,
         alloc_user_res: bool,
     ) -> Self {
-        let tid = process.inner_exclusive_access().alloc_tid();
+        let mut process_inner = process.inner_exclusive_access();
+        let tid = process_inner.alloc_tid();
+        let slot = process_inner.vm.lock().slots.alloc();
+        drop(process_inner);
         let task_user_res = Self {
             tid,
+            slot,
             ustack_base,
             process: Arc::downgrade(&process),
         };
@@ -164,44 +199,60 @@ impl TaskUserRes {
         // println!("task_user_res allocated");
         task_user_res
     }
+    /// Take over the user stack and trap context already mapped in `slot`,
+    /// as the only thread of a process forked into a copied address space
+    pub fn inherit(process: Arc<ProcessControlBlock>, ustack_base: usize, slot: usize) -> Self {
+        let mut process_inner = process.inner_exclusive_access();
+        let tid = process_inner.alloc_tid();
+        process_inner.vm.lock().slots.take(slot);
+        drop(process_inner);
+        Self {
+            tid,
+            slot,
+            ustack_base,
+            process: Arc::downgrade(&process),
+        }
+    }
     /// Allocate user resource for a task
     pub fn alloc_user_res(&self) {
         let process = self.process.upgrade().unwrap();
-        let mut process_inner = process.inner_exclusive_access();
+        let process_inner = process.inner_exclusive_access();
+        let mut memory_set = process_inner.memory_set();
         // alloc user stack
-        let ustack_bottom = ustack_bottom_from_tid(self.ustack_base, self.tid);
+        let ustack_bottom = ustack_bottom_from_slot(self.ustack_base, self.slot);
         let ustack_top = ustack_bottom + USER_STACK_SIZE;
         // println!("alloc trap_cx");
-        process_inner.memory_set.insert_framed_area(
+        memory_set.insert_lazy_area(
             ustack_bottom.into(),
             ustack_top.into(),
             MapPermission::R | MapPermission::W | MapPermission::U,
         );
         // println!("trap_cx allocated");
         // alloc trap_cx
-        let trap_cx_bottom = trap_cx_bottom_from_tid(self.tid);
+        let trap_cx_bottom = trap_cx_bottom_from_slot(self.slot);
         let trap_cx_top = trap_cx_bottom + PAGE_SIZE;
-        process_inner.memory_set.insert_framed_area(
+        memory_set.insert_framed_area(
             trap_cx_bottom.into(),
             trap_cx_top.into(),
             MapPermission::R | MapPermission::W,
-        );
+        )
+        .expect("out of frames");
     }
     /// Deallocate user resource for a task
     fn dealloc_user_res(&self) {
         // dealloc tid
         let process = self.process.upgrade().unwrap();
-        let mut process_inner = process.inner_exclusive_access();
+        let process_inner = process.inner_exclusive_access();
+        let mut vm = process_inner.vm.lock();
         // dealloc ustack manually
-        let ustack_bottom_va: VirtAddr = ustack_bottom_from_tid(self.ustack_base, self.tid).into();
-        process_inner
-            .memory_set
+        let ustack_bottom_va: VirtAddr = ustack_bottom_from_slot(self.ustack_base, self.slot).into();
+        vm.memory_set
             .remove_area_with_start_vpn(ustack_bottom_va.into());
         // dealloc trap_cx manually
-        let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_tid(self.tid).into();
-        process_inner
-            .memory_set
+        let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_slot(self.slot).into();
+        vm.memory_set
             .remove_area_with_start_vpn(trap_cx_bottom_va.into());
+        vm.slots.dealloc(self.slot);
     }
 
     #[allow(unused)]
@@ -220,20 +271,20 @@ impl TaskUserRes {
         let mut process_inner = process.inner_exclusive_access();
         process_inner.dealloc_tid(self.tid);
     }
-    /// The bottom usr vaddr (low addr) of the trap context for a task with tid
+    /// The bottom usr vaddr (low addr) of the trap context for a task
     pub fn trap_cx_user_va(&self) -> usize {
-        trap_cx_bottom_from_tid(self.tid)
+        trap_cx_bottom_from_slot(self.slot)
     }
-    /// The physical page number(ppn) of the trap context for a task with tid
+    /// The physical page number(ppn) of the trap context for a task
     pub fn trap_cx_ppn(&self) -> PhysPageNum {
         let process = self.process.upgrade().unwrap();
         let process_inner = process.inner_exclusive_access();
-        let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_tid(self.tid).into();
-        process_inner
-            .memory_set
+        let trap_cx_bottom_va: VirtAddr = trap_cx_bottom_from_slot(self.slot).into();
+        let pte = process_inner
+            .memory_set()
             .translate(trap_cx_bottom_va.into())
-            .unwrap()
-            .ppn()
+            .unwrap();
+        pte.ppn()
     }
     /// the bottom addr (low addr) of the user stack for a task
     pub fn ustack_base(&self) -> usize {
@@ -241,7 +292,7 @@ impl TaskUserRes {
     }
     /// the top addr (high addr) of the user stack for a task
     pub fn ustack_top(&self) -> usize {
-        ustack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_SIZE
+        ustack_bottom_from_slot(self.ustack_base, self.slot) + USER_STACK_SIZE
     }
 }
 
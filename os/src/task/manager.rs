@@ -3,130 +3,141 @@
 //! It is only used to manage processes and schedule process based on ready queue.
 //! Other CPU process monitoring functions are in Processor.
 
+use super::scheduler::{new_scheduler, Scheduler};
 use super::{ProcessControlBlock, TaskControlBlock, TaskStatus};
-use crate::sync::UPSafeCell;
-use alloc::collections::{BTreeMap, VecDeque};
+use crate::sync::SpinNoIrqLock;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 ///A array of `TaskControlBlock` that is thread-safe
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
-
-    /// The stopping task, leave a reference so that the kernel stack will not be recycled when switching tasks
-    stop_task: Option<Arc<TaskControlBlock>>,
+    /// The ready tasks, ordered by the scheduling policy
+    scheduler: Box<dyn Scheduler>,
 }
 
-/// A simple FIFO scheduler.
 impl TaskManager {
     ///Creat an empty TaskManager
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
-            stop_task: None,
+            scheduler: new_scheduler(),
         }
     }
     /// Add process back to ready queue
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.add(task);
     }
     /// Fetch a task from ready queue
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        for (index, task) in self.ready_queue.iter().enumerate() {
-            let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
-            let process = task.process.upgrade().unwrap();
-            let mut process_inner = process.inner_exclusive_access();
-
-            if !process_inner.locker.finish[tid] {
-                process_inner.locker.alloc(tid);
-
-                self.ready_queue.remove(index);
-                return Some(self.ready_queue[index].clone());
-            }
-        }
-
-        self.ready_queue.pop_front()
+        self.scheduler.fetch()
     }
     /// Remove a task
     pub fn remove(&mut self, task: Arc<TaskControlBlock>) {
-        if let Some((id, f_task)) = self
-            .ready_queue
-            .iter()
-            .enumerate()
-            .find(|(_, t)| Arc::as_ptr(t) == Arc::as_ptr(&task))
-        {
-            let tid = f_task.inner_exclusive_access().res.as_ref().unwrap().tid;
-            let process = task.process.upgrade().unwrap();
-            let mut process_inner = process.inner_exclusive_access();
-            process_inner.locker.finish(tid);
-
-            self.ready_queue.remove(id);
-        }
+        self.scheduler.remove(&task);
     }
-    /// Add a task to stopping task
-    pub fn add_stop(&mut self, task: Arc<TaskControlBlock>) {
-        // NOTE: as the last stopping task has completely stopped (not
-        // using kernel stack any more, at least in the single-core
-        // case) so that we can simply replace it;
-        self.stop_task = Some(task);
+    /// Account a timer tick to the running `task`, return true if it should
+    /// give up the CPU
+    pub fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        self.scheduler.tick(task)
     }
-
 }
 
 lazy_static! {
     /// TASK_MANAGER instance through lazy_static!
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    pub static ref TASK_MANAGER: SpinNoIrqLock<TaskManager> =
+        SpinNoIrqLock::new(TaskManager::new());
     /// PID2PCB instance (map of pid to pcb)
-    pub static ref PID2PCB: UPSafeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
-        unsafe { UPSafeCell::new(BTreeMap::new()) };
+    pub static ref PID2PCB: SpinNoIrqLock<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        SpinNoIrqLock::new(BTreeMap::new());
 }
 
 /// Add a task to ready queue
 pub fn add_task(task: Arc<TaskControlBlock>) {
     //trace!("kernel: TaskManager::add_task");
-    TASK_MANAGER.exclusive_access().add(task);
+    TASK_MANAGER.lock().add(task);
 }
 
-/// Wake up a task
+/// Wake up a blocked task
+///
+/// Tasks that are not blocked any more, or whose process has exited or
+/// replaced its image in the meantime, are left alone. A task still running on another hart is about
+/// to block, so its next [`super::block_current_and_run_next`] returns at once.
 pub fn wakeup_task(task: Arc<TaskControlBlock>) {
     trace!("kernel: TaskManager::wakeup_task");
     let mut task_inner = task.inner_exclusive_access();
+    if task_inner.res.is_none() || task_inner.killed {
+        return;
+    }
+    match task_inner.task_status {
+        TaskStatus::Blocked => {
+            task_inner.task_status = TaskStatus::Ready;
+            task_inner.interruptible = false;
+            drop(task_inner);
+            add_task(task);
+        }
+        TaskStatus::Running => task_inner.wakeup_pending = true,
+        _ => {}
+    }
+}
+
+/// Wake up `task` if it sleeps until an event or a signal, for a signal
+/// sent to its process. The caller holds the lock of that process, so the
+/// task cannot be about to block, see
+/// [`super::block_current_interruptible_and_run_next`]
+pub fn interrupt_task(task: &Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if !task_inner.interruptible || task_inner.killed {
+        return;
+    }
     task_inner.task_status = TaskStatus::Ready;
+    task_inner.interruptible = false;
     drop(task_inner);
-    add_task(task);
+    add_task(Arc::clone(task));
 }
 
 /// Remove a task from the ready queue
 pub fn remove_task(task: Arc<TaskControlBlock>) {
     //trace!("kernel: TaskManager::remove_task");
-    TASK_MANAGER.exclusive_access().remove(task);
+    TASK_MANAGER.lock().remove(task);
 }
 
 /// Fetch a task out of the ready queue
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     //trace!("kernel: TaskManager::fetch_task");
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
 }
 
-/// Set a task to stop-wait status, waiting for its kernel stack out of use.
-pub fn add_stopping_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add_stop(task);
+/// Account a timer tick to the running `task`, return true if its time slice
+/// is used up
+pub fn tick_task(task: &Arc<TaskControlBlock>) -> bool {
+    TASK_MANAGER.lock().tick(task)
 }
 
 /// Get process by pid
 pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
-    let map = PID2PCB.exclusive_access();
+    let map = PID2PCB.lock();
     map.get(&pid).map(Arc::clone)
 }
 
+/// The processes in process group `pgid`
+pub fn process_group(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB
+        .lock()
+        .values()
+        .filter(|process| process.inner_exclusive_access().pgid == pgid)
+        .map(Arc::clone)
+        .collect()
+}
+
 /// Insert item(pid, pcb) into PID2PCB map (called by do_fork AND ProcessControlBlock::new)
 pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
-    PID2PCB.exclusive_access().insert(pid, process);
+    PID2PCB.lock().insert(pid, process);
 }
 
 /// Remove item(pid, _some_pcb) from PDI2PCB map (called by exit_current_and_run_next)
 pub fn remove_from_pid2process(pid: usize) {
-    let mut map = PID2PCB.exclusive_access();
+    let mut map = PID2PCB.lock();
     if map.remove(&pid).is_none() {
         panic!("cannot find pid {} in pid2task!", pid);
     }
@@ -4,11 +4,13 @@
 //! (such as syscall or clock interrupt).
 //! By suspending or exiting the current task, you can
 //! modify the task state, manage the task queue through TASK_MANAGER (in task/manager.rs) ,
-//! and switch the control flow through PROCESSOR (in task/processor.rs) .
+//! and switch the control flow through the processor of the current hart
+//! (in task/processor.rs) .
 //!
 //! Be careful when you see [`__switch_task`]. Control flow around this function
 //! might not be what you expect.
 
+mod auxv;
 mod context;
 mod id;
 mod manager;
@@ -18,28 +20,45 @@ mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
-mod stride;
+mod scheduler;
 
 use self::id::TaskUserRes;
-use crate::{time::get_time_ms, vfs::{open_file, OpenFlags}};
-use manager::add_stopping_task;
+use self::signal::MAX_SIG;
+use crate::config::SIGRETURN_TRAMPOLINE;
+use crate::mem::{
+    frame_alloc, BadAddress, FileBacking, MapPermission, MemorySet, MmapFlags, PageFault, PhysAddr,
+    UserBuffer, VirtAddr,
+};
+use crate::syscall::{Errno, SysResult};
+use crate::{time::get_time_ms, vfs::{open_file, read_mapped_page, sync_write_back, OpenFlags}};
+use crate::sync::{futex_cancel, futex_wake, SpinNoIrqLock};
 use crate::time::remove_timer;
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::hint::spin_loop;
+use core::sync::atomic::Ordering;
 use lazy_static::*;
-use manager::fetch_task;
-use process::ProcessControlBlock;
+use manager::{fetch_task, tick_task};
+use process::{AddressSpace, ProcessControlBlock, ProcessControlBlockInner};
+use task::TaskControlBlockInner;
 use switch::__switch_task;
 
 pub use context::TaskContext;
 pub use id::{kstack_alloc, pid_alloc, KernelStack, PidHandle, IDLE_PID};
 #[allow(unused_imports)]
-pub use manager::{add_task, pid2process, remove_from_pid2process, remove_task, wakeup_task};
+pub use manager::{
+    add_task, interrupt_task, pid2process, process_group, remove_from_pid2process, remove_task,
+    wakeup_task,
+};
 #[allow(unused_imports)]
 pub use processor::{
     current_kstack_top, current_process, current_task, current_trap_cx, current_trap_cx_user_va,
-    current_user_token, run_tasks, schedule, take_current_task,
+    current_user_token, hart_id, kick_task, run_tasks, schedule, take_current_task,
+};
+pub use signal::{
+    SignalAction, SignalActionFlags, SignalActions, SignalDefault, SignalFlags, SignalFrame,
+    SignalInfo, SIG_BLOCK, SIG_DFL, SIG_IGN, SIG_SETMASK, SIG_UNBLOCK,
 };
-pub use signal::SignalFlags;
+pub use process::{CloneFlags, FdEntry};
 pub use task::{TaskControlBlock, TaskStatus, task_watch_syscall};
 
 /// Make current task suspended and switch to the next task
@@ -52,6 +71,7 @@ pub fn suspend_current_and_run_next() {
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     // Change status to Ready
     task_inner.task_status = TaskStatus::Ready;
+    task_inner.charge_system_time();
     drop(task_inner);
     // ---- release current TCB
 
@@ -61,13 +81,54 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// Account a timer tick to the current task and switch to the next task if
+/// it has used up its time slice
+pub fn tick_current_and_run_next() {
+    let task = current_task().unwrap();
+    if tick_task(&task) {
+        drop(task);
+        suspend_current_and_run_next();
+    }
+}
+
 /// Make current task blocked and switch to the next task.
 pub fn block_current_and_run_next() {
-    let task = take_current_task().unwrap();
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    // woken up on another hart between queueing itself and getting here
+    if core::mem::take(&mut task_inner.wakeup_pending) {
+        return;
+    }
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.task_status = TaskStatus::Blocked;
+    task_inner.charge_system_time();
+    drop(task_inner);
+    take_current_task();
+    schedule(task_cx_ptr);
+}
+
+/// Like [`block_current_and_run_next`], but a signal for the process wakes
+/// the task up too, or keeps it from blocking if one is pending already.
+/// The caller checks its condition and for pending signals again.
+pub fn block_current_interruptible_and_run_next() {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    // the signal is checked under the process lock, which senders hold to
+    // wake up the sleepers, so it cannot come in before the task blocks
+    let process_inner = process.inner_exclusive_access();
     let mut task_inner = task.inner_exclusive_access();
+    let woken = core::mem::take(&mut task_inner.wakeup_pending);
+    if woken || signal_pending(&process_inner, &task_inner) {
+        return;
+    }
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     task_inner.task_status = TaskStatus::Blocked;
+    task_inner.interruptible = true;
+    task_inner.charge_system_time();
     drop(task_inner);
+    drop(process_inner);
+    drop(process);
+    take_current_task();
     schedule(task_cx_ptr);
 }
 
@@ -87,42 +148,69 @@ pub fn fetch_task_info() -> TaskInfo {
 }
 
 /// mmap operation
-pub fn current_task_memset_mmap(start: usize, len: usize, port: usize) -> isize {
-    // There must be an application running.
-    let task = current_task().unwrap();
-    // ---- access current TCB exclusively
-    let task_inner = task.inner_exclusive_access();
-    let process = task_inner.res.as_ref().unwrap().process.upgrade().unwrap();
-    let mut process_inner = process.inner_exclusive_access();
-    let ms = &mut process_inner.memory_set;
-    ms.mmap(start, len, port)
+pub fn current_task_memset_mmap(
+    start: usize,
+    len: usize,
+    permission: MapPermission,
+    flags: MmapFlags,
+    file: Option<FileBacking>,
+) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mut ms = process_inner.memory_set();
+    match ms.mmap(start, len, permission, flags, file) {
+        Some(start) => Ok(start as isize),
+        None => Err(Errno::ENOMEM),
+    }
+}
+
+/// msync operation
+pub fn current_task_memset_msync(start: usize, len: usize) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    if !process_inner.memory_set().msync(start, len) {
+        return Err(Errno::EINVAL);
+    }
+    drop(process_inner);
+    sync_write_back();
+    Ok(0)
 }
 
 /// munmap operation
-pub fn current_task_memset_munmap(start: usize, len: usize) -> isize {
-    // There must be an application running.
-    let task = current_task().unwrap();
-    // ---- access current TCB exclusively
-    let task_inner = task.inner_exclusive_access();
-    let process = task_inner.res.as_ref().unwrap().process.upgrade().unwrap();
-    let mut process_inner = process.inner_exclusive_access();
-    let ms = &mut process_inner.memory_set;
-    ms.munmap(start, len)
+pub fn current_task_memset_munmap(start: usize, len: usize) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mut ms = process_inner.memory_set();
+    if !ms.munmap(start, len) {
+        return Err(Errno::EINVAL);
+    }
+    drop(ms);
+    drop(process_inner);
+    // the pages are gone, their content reaches the file now
+    sync_write_back();
+    Ok(0)
+}
+
+/// mprotect operation
+pub fn current_task_memset_mprotect(start: usize, len: usize, permission: MapPermission) -> SysResult {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mut ms = process_inner.memory_set();
+    // a shared mapping writes through to the file
+    if permission.contains(MapPermission::W) && ms.maps_read_only_file(start, len) {
+        return Err(Errno::EACCES);
+    }
+    if ms.mprotect(start, len, permission) {
+        Ok(0)
+    } else {
+        Err(Errno::ENOMEM)
+    }
 }
 
 /// spawn operation
-pub fn current_task_spawn(path: &str) -> isize {
-    // There must be an application running.
-    let task = current_task().unwrap();
-    // ---- access current TCB exclusively
-    let task_inner = task.inner_exclusive_access();
-    let process = task_inner.res.as_ref().unwrap().process.upgrade().unwrap();
-    let process = match process.spawn(path) {
-        Some(process) => process,
-        None => return -1,
-    };
-    let new_pid = process.getpid();
-    new_pid as isize
+pub fn current_task_spawn(path: &str) -> SysResult {
+    let process = current_process().spawn(path).ok_or(Errno::ENOENT)?;
+    Ok(process.getpid() as isize)
 }
 
 /// Exit the current 'Running' task and run the next task in task list.
@@ -133,25 +221,42 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     );
     // take from Processor
     let task = take_current_task().unwrap();
-    let mut task_inner = task.inner_exclusive_access();
     let process = task.process.upgrade().unwrap();
+    // the process inner is always locked before a task inner, so collect
+    // what we need and release the task inner first
+    let mut task_inner = task.inner_exclusive_access();
     let tid = task_inner.res.as_ref().unwrap().tid;
     // record exit code
     task_inner.exit_code = Some(exit_code);
-    task_inner.res = None;
-    // here we do not remove the thread since we are still using the kstack
-    // it will be deallocated when sys_waittid is called
+    task_inner.charge_system_time();
+    let (utime, stime) = (task_inner.utime, task_inner.stime);
+    let clear_child_tid = task_inner.clear_child_tid;
+    let res = task_inner.res.take();
     drop(task_inner);
-
-    // Move the task to stop-wait status, to avoid kernel stack from being freed
-    if tid == 0 {
-        add_stopping_task(task);
-    } else {
-        drop(task);
+    // CLONE_CHILD_CLEARTID: tell whoever joins this thread that it is gone
+    if clear_child_tid != 0 {
+        let process_inner = process.inner_exclusive_access();
+        let mut memory_set = process_inner.memory_set();
+        if memory_set.write_user(clear_child_tid as *mut i32, &0).is_ok() {
+            if let Ok(key) = memory_set.user_phys_addr(clear_child_tid, true) {
+                futex_wake(key, 1);
+            }
+        }
+    }
+    drop(res);
+    {
+        let mut process_inner = process.inner_exclusive_access();
+        process_inner.utime += utime;
+        process_inner.stime += stime;
     }
+
+    // here we do not remove the thread since we are still using the kstack
+    // it will be deallocated when sys_waittid is called; run_tasks holds
+    // another reference until we are off the kstack
     // however, if this is the main thread of current process
-    // the process should terminate at once
-    if tid == 0 {
+    // the process should terminate at once, unless another thread is
+    // replacing the image and stops this one anyway
+    if tid == 0 && kill_other_threads(&process, &task) {
         let pid = process.getpid();
         if pid == IDLE_PID {
             println!(
@@ -171,49 +276,73 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         // record exit code of main process
         process_inner.exit_code = exit_code;
 
-        {
-            // move all child processes under init process
-            let mut initproc_inner = INITPROC.inner_exclusive_access();
-            for child in process_inner.children.iter() {
-                child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-                initproc_inner.children.push(child.clone());
-            }
-        }
+        let parent = process_inner.parent.as_ref().and_then(|parent| parent.upgrade());
+        // the children move to init once this process is unlocked: init
+        // locks itself before its children in wait4
+        let children = core::mem::take(&mut process_inner.children);
 
         // deallocate user res (including tid/trap_cx/ustack) of all threads
         // it has to be done before we dealloc the whole memory_set
         // otherwise they will be deallocated twice
         let mut recycle_res = Vec::<TaskUserRes>::new();
-        for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
-            let task = task.as_ref().unwrap();
-            // if other tasks are Ready in TaskManager or waiting for a timer to be
-            // expired, we should remove them.
-            //
-            // Mention that we do not need to consider Mutex/Semaphore since they
-            // are limited in a single process. Therefore, the blocked tasks are
-            // removed when the PCB is deallocated.
-            trace!("kernel: exit_current_and_run_next .. remove_inactive_task");
-            remove_inactive_task(Arc::clone(&task));
+        let (mut utime, mut stime) = (0, 0);
+        for task in process_inner.tasks.iter().flatten() {
+            // the other threads are off the harts for good by now, see
+            // kill_other_threads
             let mut task_inner = task.inner_exclusive_access();
             if let Some(res) = task_inner.res.take() {
                 recycle_res.push(res);
             }
+            // exited threads have been accounted already
+            if task_inner.exit_code.is_none() {
+                utime += task_inner.utime;
+                stime += task_inner.stime;
+            }
         }
+        process_inner.utime += utime;
+        process_inner.stime += stime;
         // dealloc_tid and dealloc_user_res require access to PCB inner, so we
         // need to collect those user res first, then release process_inner
         // for now to avoid deadlock/double borrow problem.
         drop(process_inner);
         recycle_res.clear();
 
+        let mut orphan_zombie = false;
+        {
+            // move all child processes under init process
+            let mut initproc_inner = INITPROC.inner_exclusive_access();
+            for child in children {
+                let mut child_inner = child.inner_exclusive_access();
+                child_inner.parent = Some(Arc::downgrade(&INITPROC));
+                orphan_zombie |= child_inner.is_zombie;
+                drop(child_inner);
+                initproc_inner.children.push(child);
+            }
+        }
+        // init has to reap the zombies it adopted
+        if orphan_zombie {
+            INITPROC.wait_queue.notify_all();
+        }
+
         let mut process_inner = process.inner_exclusive_access();
-        process_inner.children.clear();
-        // deallocate other data in user space i.e. program code/data section
-        process_inner.memory_set.recycle_data_pages();
-        // drop file descriptors
-        process_inner.fd_table.clear();
+        // deallocate other data in user space i.e. program code/data section,
+        // unless other processes still run in the address space
+        if Arc::strong_count(&process_inner.vm) == 1 {
+            process_inner.memory_set().recycle_data_pages();
+        }
+        // drop file descriptors, which other processes may still share
+        process_inner.files = Arc::new(SpinNoIrqLock::new(Vec::new()));
         // remove all tasks
         process_inner.tasks.clear();
+        drop(process_inner);
+
+        // tell the parent, which may be sleeping in wait4
+        if let Some(parent) = parent {
+            parent.inner_exclusive_access().signals |= SignalFlags::SIGCHLD;
+            parent.wait_queue.notify_all();
+        }
     }
+    drop(task);
     drop(process);
     // we do not have to save task context
     let mut _unused = TaskContext::zero_init();
@@ -229,7 +358,7 @@ lazy_static! {
         let inode = open_file("/bin/shell_syscall.elf", OpenFlags::RDONLY).unwrap();
         let v = inode.read_all();
         println!("Read size: {}", v.len());
-        ProcessControlBlock::new(v.as_slice())
+        ProcessControlBlock::new(v.as_slice()).expect("initproc is not an ELF image")
     };
 }
 
@@ -238,18 +367,361 @@ pub fn add_initproc() {
     let _initproc = INITPROC.clone();
 }
 
-/// Check if the current task has any signal to handle
-pub fn check_signals_of_current() -> Option<(i32, &'static str)> {
+/// Resolve a fault of the current process at `va` for an `access`. The page
+/// of a file mapping is read with the address space unlocked, since the file
+/// system may sleep on the disk.
+pub fn handle_user_fault(va: VirtAddr, access: MapPermission) -> Result<(), PageFault> {
     let process = current_process();
-    let process_inner = process.inner_exclusive_access();
-    process_inner.signals.check_error()
+    let mut result = process.inner_exclusive_access().memory_set().handle_page_fault(va, access);
+    while result == Err(PageFault::NotLoaded) {
+        let source = process.inner_exclusive_access().memory_set().file_page(va);
+        let Some(source) = source else {
+            // loaded by another thread meanwhile
+            result = process.inner_exclusive_access().memory_set().handle_page_fault(va, access);
+            continue;
+        };
+        let frame = frame_alloc().ok_or(PageFault::OutOfMemory)?;
+        read_mapped_page(&source.0, source.1, &frame);
+        result = process
+            .inner_exclusive_access()
+            .memory_set()
+            .fill_page(va, access, &source, frame);
+    }
+    result
 }
 
-/// Add signal to the current task
-pub fn current_add_signal(signal: SignalFlags) {
+/// Run `f` on the address space of the current process. When `f` runs into
+/// a page of a file mapping which has not been read yet, the page is read
+/// with the address space unlocked and `f` runs again.
+fn with_user_memory<T>(mut f: impl FnMut(&mut MemorySet) -> Result<T, BadAddress>) -> Result<T, BadAddress> {
     let process = current_process();
+    loop {
+        let (result, unloaded) = {
+            let process_inner = process.inner_exclusive_access();
+            let mut memory_set = process_inner.memory_set();
+            let result = f(&mut memory_set);
+            (result, memory_set.take_unloaded())
+        };
+        match (result, unloaded) {
+            (Err(BadAddress), Some((va, access))) => {
+                handle_user_fault(va, access).map_err(|_| BadAddress)?;
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// Read a `T` from the user space of the current process
+pub fn copy_from_user<T: Copy>(src: *const T) -> Result<T, BadAddress> {
+    with_user_memory(|memory_set| memory_set.read_user(src))
+}
+
+/// Write `value` to the user space of the current process
+pub fn copy_to_user<T>(dst: *mut T, value: &T) -> Result<(), BadAddress> {
+    with_user_memory(|memory_set| memory_set.write_user(dst, value))
+}
+
+/// Check a buffer in the user space of the current process, which the kernel
+/// reads from (or writes to if `write`) on behalf of user space
+pub fn user_buffer(ptr: usize, len: usize, write: bool) -> Result<UserBuffer, BadAddress> {
+    with_user_memory(|memory_set| memory_set.user_buffer(ptr, len, write))
+}
+
+/// The physical address behind `va` in the user space of the current process,
+/// copy-on-write pages are broken first so the address stays the same
+pub fn user_phys_addr(va: usize) -> Result<PhysAddr, BadAddress> {
+    with_user_memory(|memory_set| memory_set.user_phys_addr(va, true))
+}
+
+/// Copy a `\0` terminated string from the user space of the current process
+pub fn user_str(ptr: *const u8) -> Result<String, BadAddress> {
+    with_user_memory(|memory_set| memory_set.read_user_str(ptr))
+}
+
+/// Add a signal directed at the current thread
+pub fn current_add_signal(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.signals |= signal;
+}
+
+/// Send `signal` to `process`, waking up its threads which sleep until a
+/// signal arrives, in wait4, a pipe, a futex or a terminal, so that it is
+/// handled. SIGCONT and SIGKILL also resume the threads of a stopped process.
+pub fn send_signal(process: &Arc<ProcessControlBlock>, signal: SignalFlags) {
     let mut process_inner = process.inner_exclusive_access();
     process_inner.signals |= signal;
+    for task in process_inner.tasks.iter().flatten() {
+        interrupt_task(task);
+    }
+    drop(process_inner);
+    if signal.intersects(SignalFlags::SIGCONT | SignalFlags::SIGKILL) {
+        process.stop_queue.notify_all();
+    }
+}
+
+/// Send `signal` to every process in process group `pgid`, return false if
+/// the group is empty
+pub fn signal_process_group(pgid: usize, signal: SignalFlags) -> bool {
+    let group = process_group(pgid);
+    for process in group.iter() {
+        send_signal(process, signal);
+    }
+    !group.is_empty()
+}
+
+/// Raise a signal caused by a synchronous fault of the current thread.
+///
+/// Like Linux, a fault signal that is blocked or ignored falls back to its
+/// default action, since returning to the faulting instruction would only fault again.
+pub fn current_force_signal(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let process_inner = process.inner_exclusive_access();
+    let mut task_inner = task.inner_exclusive_access();
+    let signum = signal.lowest_signum().unwrap();
+    let mut signal_actions = process_inner.signal_actions();
+    let action = &mut signal_actions.table[signum];
+    if task_inner.signal_mask.contains(signal) || action.handler == SIG_IGN {
+        *action = SignalAction::default();
+        task_inner.signal_mask.remove(signal);
+    }
+    task_inner.signals |= signal;
+}
+
+/// Deliver pending signals of the current thread before it returns to user mode.
+///
+/// Default actions are carried out right here. For a user handler, a
+/// [`SignalFrame`] is pushed onto the user stack and the trap context is
+/// redirected to the handler, which returns through the sigreturn trampoline.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let process = task.process.upgrade().unwrap();
+        let mut process_inner = process.inner_exclusive_access();
+        let mut task_inner = task.inner_exclusive_access();
+        let blocked = task_inner.signal_mask - SignalFlags::unmaskable();
+        let pending = (task_inner.signals | process_inner.signals) - blocked;
+        let signum = match pending.lowest_signum() {
+            Some(signum) => signum,
+            None => {
+                // nothing left that the ppoll or pselect6 mask lets through
+                if let Some(mask) = task_inner.saved_signal_mask.take() {
+                    task_inner.signal_mask = mask;
+                }
+                return;
+            }
+        };
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        // signals directed at this thread are consumed first
+        if task_inner.signals.contains(signal) {
+            task_inner.signals.remove(signal);
+        } else {
+            process_inner.signals.remove(signal);
+        }
+        let action = process_inner.signal_actions().table[signum];
+        if SignalFlags::unmaskable().contains(signal) || action.handler == SIG_DFL {
+            drop(task_inner);
+            drop(process_inner);
+            match signal.default_action() {
+                SignalDefault::Ignore | SignalDefault::Continue => continue,
+                SignalDefault::Stop => {
+                    drop(process);
+                    drop(task);
+                    stop_current_until_continued(signum);
+                    continue;
+                }
+                SignalDefault::Terminate => {
+                    drop(process);
+                    drop(task);
+                    terminate_current(signal);
+                }
+            }
+        }
+        if action.handler == SIG_IGN {
+            continue;
+        }
+        // run the user handler
+        if action.flags.contains(SignalActionFlags::SA_RESETHAND) {
+            process_inner.signal_actions().table[signum] = SignalAction::default();
+        }
+        // the handler runs with the mask of the interrupted ppoll or
+        // pselect6, sigreturn goes back to the one from before the call
+        let old_mask = task_inner.saved_signal_mask.take().unwrap_or(task_inner.signal_mask);
+        let mut handler_mask = task_inner.signal_mask | action.mask;
+        if !action.flags.contains(SignalActionFlags::SA_NODEFER) {
+            handler_mask |= signal;
+        }
+        task_inner.signal_mask = handler_mask - SignalFlags::unmaskable();
+        let trap_cx = task_inner.get_trap_cx();
+        let frame_ptr = (trap_cx.x[2] - core::mem::size_of::<SignalFrame>()) & !0xf;
+        let frame = SignalFrame {
+            info: SignalInfo::new(signum, 0),
+            regs: trap_cx.x,
+            sepc: trap_cx.sepc,
+            mask: old_mask,
+        };
+        drop(task_inner);
+        drop(process_inner);
+        if copy_to_user(frame_ptr as *mut SignalFrame, &frame).is_err() {
+            drop(task);
+            drop(process);
+            terminate_current(SignalFlags::SIGSEGV);
+        }
+        trap_cx.x[1] = SIGRETURN_TRAMPOLINE;
+        trap_cx.x[2] = frame_ptr;
+        trap_cx.x[10] = signum;
+        if action.flags.contains(SignalActionFlags::SA_SIGINFO) {
+            // siginfo sits at the bottom of the frame
+            trap_cx.x[11] = frame_ptr;
+        } else {
+            trap_cx.x[11] = 0;
+        }
+        trap_cx.x[12] = 0;
+        trap_cx.sepc = action.handler;
+        return;
+    }
+}
+
+/// Pop the signal frame pushed by [`handle_signals`] and restore the
+/// interrupted context. Return false if the frame can not be read.
+pub fn current_sigreturn() -> bool {
+    let trap_cx = current_trap_cx();
+    let frame_ptr = trap_cx.x[2] as *const SignalFrame;
+    let frame = match copy_from_user(frame_ptr) {
+        Ok(frame) => frame,
+        Err(_) => return false,
+    };
+    trap_cx.x = frame.regs;
+    trap_cx.sepc = frame.sepc;
+    current_task().unwrap().inner_exclusive_access().signal_mask =
+        frame.mask - SignalFlags::unmaskable();
+    true
+}
+
+/// Is a signal pending for the current thread that should interrupt a
+/// sleep, i.e. one that is neither blocked nor ignored?
+pub fn current_signal_pending() -> bool {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    current_signal_pending_locked(&process_inner)
+}
+
+/// Like [`current_signal_pending`], for a caller that holds the lock of the
+/// current process already
+pub fn current_signal_pending_locked(process_inner: &ProcessControlBlockInner) -> bool {
+    let task = current_task().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    signal_pending(process_inner, &task_inner)
+}
+
+/// Is a signal pending for the thread of `task_inner` that is neither
+/// blocked nor ignored?
+fn signal_pending(process_inner: &ProcessControlBlockInner, task_inner: &TaskControlBlockInner) -> bool {
+    let blocked = task_inner.signal_mask - SignalFlags::unmaskable();
+    let pending = (task_inner.signals | process_inner.signals) - blocked;
+    (1..=MAX_SIG).any(|signum| {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        let handler = process_inner.signal_actions().table[signum].handler;
+        let ignored = handler == SIG_IGN
+            || (handler == SIG_DFL && signal.default_action() == SignalDefault::Ignore);
+        pending.contains(signal) && !ignored
+    })
+}
+
+/// Stop the current thread because of signal `signum` until SIGCONT (or
+/// SIGKILL) arrives, letting the parent know through wait4 and SIGCHLD
+fn stop_current_until_continued(signum: usize) {
+    let process = current_process();
+    let parent = {
+        let mut process_inner = process.inner_exclusive_access();
+        process_inner.stop_signal = Some(signum);
+        process_inner.parent.as_ref().and_then(|parent| parent.upgrade())
+    };
+    if let Some(parent) = parent {
+        let mut parent_inner = parent.inner_exclusive_access();
+        let action = parent_inner.signal_actions().table[SignalFlags::SIGCHLD.lowest_signum().unwrap()];
+        if !action.flags.contains(SignalActionFlags::SA_NOCLDSTOP) {
+            parent_inner.signals |= SignalFlags::SIGCHLD;
+        }
+        drop(parent_inner);
+        parent.wait_queue.notify_all();
+    }
+    loop {
+        let task = current_task().unwrap();
+        let process_inner = process.inner_exclusive_access();
+        let task_inner = task.inner_exclusive_access();
+        let pending = task_inner.signals | process_inner.signals;
+        if pending.intersects(SignalFlags::SIGCONT | SignalFlags::SIGKILL) {
+            break;
+        }
+        drop(task_inner);
+        drop(task);
+        // queue up before the lock is released, so a SIGCONT sent meanwhile
+        // is not missed
+        process.stop_queue.wait_unlock(process_inner);
+    }
+    process.inner_exclusive_access().stop_signal = None;
+}
+
+/// Terminate the current process because of `signal`
+fn terminate_current(signal: SignalFlags) -> ! {
+    let (exit_code, msg) = signal.check_error().unwrap();
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    println!("[kernel] pid {}: {}", process.getpid(), msg);
+    let mut process_inner = process.inner_exclusive_access();
+    // the first fatal signal is the one reported to the parent
+    if process_inner.term_signal == 0 {
+        process_inner.term_signal = signal.lowest_signum().unwrap();
+    }
+    drop(process_inner);
+    if task.inner_exclusive_access().res.as_ref().unwrap().tid != 0 {
+        // the main thread takes the whole process down on its way back to user
+        send_signal(&process, SignalFlags::SIGKILL);
+    }
+    drop(process);
+    drop(task);
+    exit_current_and_run_next(exit_code);
+    unreachable!("terminated task is scheduled again");
+}
+
+/// Stop the threads of `process` other than `current` for good, so that
+/// their user res can be released.
+///
+/// They are taken off the ready queue and never scheduled again. A thread
+/// running on another hart is kicked and exits on its way back to user mode;
+/// this returns once none of them is on a hart any more. Returns false without
+/// touching them if another thread got here first and stops `current` as well.
+pub fn kill_other_threads(process: &Arc<ProcessControlBlock>, current: &Arc<TaskControlBlock>) -> bool {
+    let process_inner = process.inner_exclusive_access();
+    if current.inner_exclusive_access().killed {
+        return false;
+    }
+    let others: Vec<_> = process_inner
+        .tasks
+        .iter()
+        .flatten()
+        .filter(|task| !Arc::ptr_eq(task, current))
+        .cloned()
+        .collect();
+    for task in others.iter() {
+        task.inner_exclusive_access().killed = true;
+        remove_inactive_task(Arc::clone(task));
+    }
+    // the threads need the process lock on their way out
+    drop(process_inner);
+    for task in others.iter() {
+        if task.on_cpu.load(Ordering::Acquire) {
+            kick_task(task);
+        }
+        // a kick may miss a thread that was just being switched to, the
+        // next timer tick brings it into the kernel anyway
+        while task.on_cpu.load(Ordering::Acquire) {
+            spin_loop();
+        }
+    }
+    true
 }
 
 /// the inactive(blocked) tasks are removed when the PCB is deallocated.(called by exit_current_and_run_next)
@@ -257,4 +729,5 @@ pub fn remove_inactive_task(task: Arc<TaskControlBlock>) {
     remove_task(Arc::clone(&task));
     trace!("kernel: remove_inactive_task .. remove_timer");
     remove_timer(Arc::clone(&task));
+    futex_cancel(&task);
 }
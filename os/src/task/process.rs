@@ -1,45 +1,133 @@
 //! Implementation of  [`ProcessControlBlock`]
 
-use super::id::RecycleAllocator;
+use super::id::{remove_other_slots, RecycleAllocator, TaskUserRes};
 use super::manager::insert_into_pid2process;
 use super::TaskControlBlock;
-use super::{add_task, SignalFlags};
+use super::{add_task, current_force_signal, current_task, exit_current_and_run_next};
+use super::{kill_other_threads, SignalActions, SignalFlags};
 use super::{pid_alloc, PidHandle};
+use super::auxv::{auxv, random_bytes};
+use crate::config::{USER_HEAP_BASE, USER_STACK_SIZE};
 use crate::loader::get_bin_data_by_name;
 use crate::vfs::{File, Stdin, Stdout};
-use crate::mem::{translated_refmut, MemorySet, VirtAddr, KERNEL_SPACE};
-use crate::sync::{Condvar, Mutex, Semaphore, UPSafeCell};
+use crate::mem::{BadAddress, ElfError, ElfInfo, MemorySet, VirtAddr, KERNEL_SPACE};
+use crate::sync::{Condvar, Mutex, Semaphore, SpinNoIrqGuard, SpinNoIrqLock, WaitQueue};
+use crate::syscall::{Errno, SysResult};
 use crate::trap::{trap_handler, TrapContext};
 use alloc::string::String;
+use bitflags::*;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
-use core::cell::RefMut;
+use core::mem::size_of;
+
+bitflags! {
+    /// Flags of clone, the low byte holds the signal sent to the parent
+    /// when the child exits
+    pub struct CloneFlags: usize {
+        /// Share the address space
+        const CLONE_VM             = 0x0000_0100;
+        /// Share the file descriptor table
+        const CLONE_FILES          = 0x0000_0400;
+        /// Share the signal actions
+        const CLONE_SIGHAND        = 0x0000_0800;
+        /// Create a thread of the same process
+        const CLONE_THREAD         = 0x0001_0000;
+        /// Set the thread pointer (tp) of the child
+        const CLONE_SETTLS         = 0x0008_0000;
+        /// Store the child tid at `ptid` in the parent
+        const CLONE_PARENT_SETTID  = 0x0010_0000;
+        /// Clear the tid at `ctid` in the child when it exits
+        const CLONE_CHILD_CLEARTID = 0x0020_0000;
+    }
+}
 
 /// Process Control Block
 pub struct ProcessControlBlock {
     /// immutable
     pub pid: PidHandle,
     /// mutable
-    inner: UPSafeCell<ProcessControlBlockInner>,
+    inner: SpinNoIrqLock<ProcessControlBlockInner>,
+    /// the threads of this process waiting for children to change state
+    pub wait_queue: WaitQueue,
+    /// the threads of this process stopped until SIGCONT or SIGKILL
+    pub stop_queue: WaitQueue,
+}
+
+/// An open file descriptor
+#[derive(Clone)]
+pub struct FdEntry {
+    /// the open file, shared with the descriptors dup'ed or forked from this one
+    pub file: Arc<dyn File + Send + Sync>,
+    /// close the descriptor on execve, FD_CLOEXEC
+    pub cloexec: bool,
+}
+
+impl FdEntry {
+    /// A descriptor for `file`
+    pub fn new(file: Arc<dyn File + Send + Sync>, cloexec: bool) -> Self {
+        Self { file, cloexec }
+    }
+}
+
+/// A file descriptor table, shared by the processes cloned with CLONE_FILES
+pub type FdTable = Vec<Option<FdEntry>>;
+
+/// An address space, shared by the processes cloned with CLONE_VM
+pub struct AddressSpace {
+    /// memory set
+    pub memory_set: MemorySet,
+    /// slots of the user stacks and trap contexts of the threads running
+    /// in this address space
+    pub slots: RecycleAllocator,
+    /// Heap bottom
+    pub heap_bottom: usize,
+    /// Program break
+    pub program_brk: usize,
+}
+
+impl AddressSpace {
+    /// Wrap `memory_set` fresh from an ELF image, which has an empty heap
+    pub fn new(memory_set: MemorySet) -> Self {
+        Self {
+            memory_set,
+            slots: RecycleAllocator::new(),
+            heap_bottom: USER_HEAP_BASE,
+            program_brk: USER_HEAP_BASE,
+        }
+    }
 }
 
 /// Inner of Process Control Block
 pub struct ProcessControlBlockInner {
     /// is zombie?
     pub is_zombie: bool,
-    /// memory set(address space)
-    pub memory_set: MemorySet,
+    /// address space
+    pub vm: Arc<SpinNoIrqLock<AddressSpace>>,
     /// parent process
     pub parent: Option<Weak<ProcessControlBlock>>,
     /// children process
     pub children: Vec<Arc<ProcessControlBlock>>,
+    /// process group, signalled as a whole by the terminal
+    pub pgid: usize,
+    /// working directory, an absolute path without `.` or `..`
+    pub cwd: String,
     /// exit code
     pub exit_code: i32,
+    /// the signal which terminated the process, 0 if it exited by itself
+    pub term_signal: usize,
+    /// the signal which stopped the process, until reported to the parent
+    pub stop_signal: Option<usize>,
+    /// user time of the exited threads, in microseconds
+    pub utime: usize,
+    /// system time of the exited threads, in microseconds
+    pub stime: usize,
     /// file descriptor table
-    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    pub files: Arc<SpinNoIrqLock<FdTable>>,
     /// signal flags
     pub signals: SignalFlags,
+    /// signal actions
+    pub sighand: Arc<SpinNoIrqLock<SignalActions>>,
     /// tasks(also known as threads)
     pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
     /// task resource allocator
@@ -54,10 +142,6 @@ pub struct ProcessControlBlockInner {
     pub deadlock_detect: bool,
     /// mutex and semaphore locker
     pub locker: ProcessLocker,
-    /// Heap bottom
-    pub heap_bottom: usize,
-    /// Program break
-    pub program_brk: usize
 }
 
 /// Locker of Process Control Block
@@ -76,16 +160,44 @@ impl ProcessControlBlockInner {
     #[allow(unused)]
     /// get the address of app's page table
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.memory_set().token()
+    }
+    /// the memory set of the address space
+    pub fn memory_set(&self) -> SpinNoIrqGuard<'_, MemorySet> {
+        SpinNoIrqGuard::map(self.vm.lock(), |vm| &mut vm.memory_set)
+    }
+    /// the file descriptor table
+    pub fn fd_table(&self) -> SpinNoIrqGuard<'_, FdTable> {
+        self.files.lock()
+    }
+    /// the signal actions
+    pub fn signal_actions(&self) -> SpinNoIrqGuard<'_, SignalActions> {
+        self.sighand.lock()
     }
     /// allocate a new file descriptor
     pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+        self.alloc_fd_from(0)
+    }
+    /// allocate the lowest free file descriptor not below `min`
+    pub fn alloc_fd_from(&mut self, min: usize) -> usize {
+        let mut fd_table = self.fd_table();
+        if let Some(fd) = (min..fd_table.len()).find(|fd| fd_table[*fd].is_none()) {
             fd
         } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+            let fd = fd_table.len().max(min);
+            fd_table.resize(fd + 1, None);
+            fd
+        }
+    }
+    /// put a new thread into the task list at its tid
+    pub fn attach_task(&mut self, task: Arc<TaskControlBlock>) {
+        let tid = task.inner_exclusive_access().res.as_ref().unwrap().tid;
+        while self.tasks.len() < tid + 1 {
+            self.tasks.push(None);
         }
+        self.tasks[tid] = Some(task);
+        // init semaphore locker
+        self.locker.init();
     }
     /// allocate a new task id
     pub fn alloc_tid(&mut self) -> usize {
@@ -103,48 +215,62 @@ impl ProcessControlBlockInner {
     pub fn get_task(&self, tid: usize) -> Arc<TaskControlBlock> {
         self.tasks[tid].as_ref().unwrap().clone()
     }
+    /// the status reported by wait4 for a zombie process
+    pub fn wait_status(&self) -> i32 {
+        if self.term_signal != 0 {
+            (self.term_signal & 0x7f) as i32
+        } else {
+            (self.exit_code & 0xff) << 8
+        }
+    }
 }
 
 impl ProcessControlBlock {
     /// inner_exclusive_access
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, ProcessControlBlockInner> {
+        self.inner.lock()
     }
     /// new process from elf file
-    pub fn new(elf_data: &[u8]) -> Arc<Self> {
+    pub fn new(elf_data: &[u8]) -> Result<Arc<Self>, ElfError> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, ustack_base, elf_info) = MemorySet::from_elf(elf_data)?;
         // allocate a pid
         let pid_handle = pid_alloc();
+        let pgid = pid_handle.0;
         let process = Arc::new(Self {
             pid: pid_handle,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                    deadlock_detect: false,
-                    locker: ProcessLocker::new(),
-                    heap_bottom: ustack_base,
-                    program_brk: ustack_base
-                })
-            },
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                vm: Arc::new(SpinNoIrqLock::new(AddressSpace::new(memory_set))),
+                parent: None,
+                children: Vec::new(),
+                pgid,
+                cwd: "/".into(),
+                exit_code: 0,
+                term_signal: 0,
+                stop_signal: None,
+                utime: 0,
+                stime: 0,
+                files: Arc::new(SpinNoIrqLock::new(vec![
+                    // 0 -> stdin
+                    Some(FdEntry::new(Arc::new(Stdin), false)),
+                    // 1 -> stdout
+                    Some(FdEntry::new(Arc::new(Stdout), false)),
+                    // 2 -> stderr
+                    Some(FdEntry::new(Arc::new(Stdout), false)),
+                ])),
+                signals: SignalFlags::empty(),
+                sighand: Arc::new(SpinNoIrqLock::new(SignalActions::default())),
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                deadlock_detect: false,
+                locker: ProcessLocker::new(),
+            }),
+            wait_queue: WaitQueue::new(),
+            stop_queue: WaitQueue::new(),
         });
         println!("kernel: ProcessControlBlock::new ustack_base: {:#x}", ustack_base);
         // create a main thread, we should allocate ustack and trap_cx here
@@ -159,10 +285,14 @@ impl ProcessControlBlock {
         let ustack_top = task_inner.res.as_ref().unwrap().ustack_top();
         let kstack_top = task.kstack.get_top();
         drop(task_inner);
+        // started without arguments or environment
+        let user_sp = process
+            .init_user_stack(ustack_top, &[], &[], &elf_info)
+            .expect("an empty initial stack fits");
         *trap_cx = TrapContext::app_init_context(
-            entry_point,
-            ustack_top,
-            KERNEL_SPACE.exclusive_access().token(),
+            elf_info.entry,
+            user_sp,
+            KERNEL_SPACE.lock().token(),
             kstack_top,
             trap_handler as usize,
         );
@@ -174,175 +304,332 @@ impl ProcessControlBlock {
         insert_into_pid2process(process.getpid(), Arc::clone(&process));
         // add main thread to scheduler
         add_task(task);
-        process
+        Ok(process)
     }
 
-    /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
+    /// Replace the image of this process, called by one of its threads.
+    ///
+    /// All other threads are terminated and the calling thread carries on as
+    /// the main thread. The new image starts with the standard initial stack:
+    /// argc, then the NULL-terminated argv and envp arrays, then the
+    /// auxiliary vector. Fails before touching the old image, with E2BIG if
+    /// `args` and `envs` take more than a quarter of the user stack or with
+    /// ENOEXEC if `elf_data` is no ELF image.
+    pub fn exec(
+        self: &Arc<Self>,
+        elf_data: &[u8],
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> SysResult<()> {
         trace!("kernel: exec");
-        assert_eq!(self.inner_exclusive_access().thread_count(), 1);
+        let strings_size: usize = args.iter().chain(envs.iter()).map(|s| s.len() + 1).sum();
+        let pointers_size = (args.len() + envs.len() + 3) * size_of::<usize>();
+        if strings_size + pointers_size > USER_STACK_SIZE / 4 {
+            return Err(Errno::E2BIG);
+        }
         // memory_set with elf program headers/trampoline/trap context/user stack
         trace!("kernel: exec .. MemorySet::from_elf");
-        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
-        let new_token = memory_set.token();
+        let (memory_set, ustack_base, elf_info) = MemorySet::from_elf(elf_data)?;
+        // terminate the other threads and collect the user res of all
+        // threads, which must be released before the old memory_set goes
+        trace!("kernel: exec .. terminate other threads");
+        let task = current_task().unwrap();
+        if !kill_other_threads(self, &task) {
+            // another thread is replacing the image or taking the process down
+            drop(task);
+            exit_current_and_run_next(0);
+            unreachable!("stopped thread is scheduled again");
+        }
+        let mut recycle_res = Vec::<TaskUserRes>::new();
+        let mut inner = self.inner_exclusive_access();
+        let (mut utime, mut stime) = (0, 0);
+        for other in inner.tasks.iter().flatten() {
+            if Arc::ptr_eq(other, &task) {
+                continue;
+            }
+            let mut other_inner = other.inner_exclusive_access();
+            if let Some(res) = other_inner.res.take() {
+                recycle_res.push(res);
+                utime += other_inner.utime;
+                stime += other_inner.stime;
+            }
+        }
+        inner.utime += utime;
+        inner.stime += stime;
+        inner.tasks.clear();
+        // threads blocked in the sync primitives of the old image go with them
+        inner.mutex_list.clear();
+        inner.semaphore_list.clear();
+        inner.condvar_list.clear();
+        inner.locker = ProcessLocker::new();
+        inner.locker.init();
+        recycle_res.extend(task.inner_exclusive_access().res.take());
+        drop(inner);
+        recycle_res.clear();
         // substitute memory_set
         trace!("kernel: exec .. substitute memory_set");
-        self.inner_exclusive_access().memory_set = memory_set;
-        // then we alloc user resource for main thread again
+        // the new image gets its own address space, fd table and signal
+        // actions even if they were shared
+        let mut inner = self.inner_exclusive_access();
+        inner.vm = Arc::new(SpinNoIrqLock::new(AddressSpace::new(memory_set)));
+        let mut fd_table = inner.fd_table().clone();
+        // and the descriptors opened with close-on-exec are gone from it
+        for entry in fd_table.iter_mut() {
+            if entry.as_ref().is_some_and(|entry| entry.cloexec) {
+                *entry = None;
+            }
+        }
+        inner.files = Arc::new(SpinNoIrqLock::new(fd_table));
+        let mut signal_actions = inner.signal_actions().clone();
+        // caught signals are reset, ignored ones stay ignored
+        signal_actions.reset_handlers();
+        inner.sighand = Arc::new(SpinNoIrqLock::new(signal_actions));
+        // all tids are free again, the calling thread becomes tid 0
+        inner.task_res_allocator = RecycleAllocator::new();
+        inner.tasks.push(Some(Arc::clone(&task)));
+        drop(inner);
+        // then we alloc user resource for the thread again
         // since memory_set has been changed
         trace!("kernel: exec .. alloc user resource for main thread again");
-        let task = self.inner_exclusive_access().get_task(0);
+        let res = TaskUserRes::new(Arc::clone(self), ustack_base, true);
         let mut task_inner = task.inner_exclusive_access();
-        task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
-        task_inner.res.as_mut().unwrap().alloc_user_res();
-        task_inner.trap_cx_ppn = task_inner.res.as_mut().unwrap().trap_cx_ppn();
-        // push arguments on user stack
+        task_inner.trap_cx_ppn = res.trap_cx_ppn();
+        let ustack_top = res.ustack_top();
+        task_inner.res = Some(res);
+        // the stack is written under the process lock, which comes first
+        drop(task_inner);
+        // push arguments, environment and auxv on user stack
         trace!("kernel: exec .. push arguments on user stack");
-        let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
-        user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
-        let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    new_token,
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(new_token, p as *mut u8) = *c;
-                p += 1;
+        let user_sp = match self.init_user_stack(ustack_top, &args, &envs, &elf_info) {
+            Ok(user_sp) => user_sp,
+            Err(_) => {
+                // the size was checked up front; should the stack be too
+                // small after all, there is no old image to return to
+                current_force_signal(SignalFlags::SIGSEGV);
+                return Ok(());
             }
-            *translated_refmut(new_token, p as *mut u8) = 0;
-        }
-        // make the user_sp aligned to 8B for k210 platform
-        user_sp -= user_sp % core::mem::size_of::<usize>();
+        };
         // initialize trap_cx
         trace!("kernel: exec .. initialize trap_cx");
-        let mut trap_cx = TrapContext::app_init_context(
-            entry_point,
+        let trap_cx = TrapContext::app_init_context(
+            elf_info.entry,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             task.kstack.get_top(),
             trap_handler as usize,
         );
-        trap_cx.x[10] = args.len();
-        trap_cx.x[11] = argv_base;
-        *task_inner.get_trap_cx() = trap_cx;
+        *task.inner_exclusive_access().get_trap_cx() = trap_cx;
+        Ok(())
+    }
+
+    /// Build the initial stack of a new image below `ustack_top` and return
+    /// the user sp, which points at argc and is 16-byte aligned:
+    ///
+    /// ```text
+    /// sp -> argc
+    ///       argv[0..argc], NULL
+    ///       envp[..], NULL
+    ///       auxv pairs, AT_NULL
+    ///       (padding)
+    ///       argument and environment strings, 16 AT_RANDOM bytes
+    /// ```
+    ///
+    /// Fails if it does not fit the user stack.
+    fn init_user_stack(
+        &self,
+        ustack_top: usize,
+        args: &[String],
+        envs: &[String],
+        elf_info: &ElfInfo,
+    ) -> Result<usize, BadAddress> {
+        let inner = self.inner_exclusive_access();
+        let mut user_sp = ustack_top;
+        let push_bytes = |user_sp: &mut usize, bytes: &[u8]| {
+            *user_sp -= bytes.len();
+            inner.memory_set().write_user_bytes(*user_sp, bytes)?;
+            Ok(*user_sp)
+        };
+        let random = push_bytes(&mut user_sp, &random_bytes(self.getpid()))?;
+        let push_strs = |user_sp: &mut usize, strs: &[String]| {
+            let mut ptrs = strs
+                .iter()
+                .map(|s| push_bytes(user_sp, &[s.as_bytes(), &[0]].concat()))
+                .collect::<Result<Vec<usize>, BadAddress>>()?;
+            ptrs.push(0);
+            Ok::<_, BadAddress>(ptrs)
+        };
+        let envp = push_strs(&mut user_sp, envs)?;
+        let argv = push_strs(&mut user_sp, args)?;
+        let execfn = argv[0];
+        let mut words = vec![args.len()];
+        words.extend(argv);
+        words.extend(envp);
+        for (key, value) in auxv(elf_info, random, execfn) {
+            words.push(key);
+            words.push(value);
+        }
+        user_sp -= words.len() * size_of::<usize>();
+        user_sp &= !0xf;
+        let bytes = unsafe {
+            core::slice::from_raw_parts(words.as_ptr() as *const u8, words.len() * size_of::<usize>())
+        };
+        inner.memory_set().write_user_bytes(user_sp, bytes)?;
+        Ok(user_sp)
     }
 
     /// Spawn
     pub fn spawn(self: &Arc<Self>, path: &str) -> Option<Arc<Self>> {
         let name = path;
         // load elf from file system
-        let ret =ProcessControlBlock::new(get_bin_data_by_name(name).unwrap());
+        let ret = ProcessControlBlock::new(get_bin_data_by_name(name)?).ok()?;
         let mut parent_inner = self.inner_exclusive_access();
         parent_inner.children.push(ret.clone());
         Some(ret)
     }
 
-    /// Only support processes with a single thread.
-    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
-        trace!("kernel: fork");
+    /// Create a child process, for clone without CLONE_THREAD; fork is
+    /// clone without flags.
+    ///
+    /// Only the calling thread is duplicated and it becomes the main thread
+    /// of the child. With CLONE_VM, CLONE_FILES or CLONE_SIGHAND the child
+    /// shares the address space, fd table or signal actions of this
+    /// process, otherwise it gets a copy. A copied address space keeps only
+    /// the user stack and trap context of the calling thread. The caller
+    /// finishes the trap context of the main thread and then hands it to
+    /// the scheduler with `add_task`. Fails with ENOMEM if the frames for
+    /// the shared mappings run out.
+    pub fn clone_process(self: &Arc<Self>, flags: CloneFlags) -> SysResult<Arc<Self>> {
+        trace!("kernel: clone_process");
+        let current = current_task().unwrap();
+        let current_inner = current.inner_exclusive_access();
+        let current_res = current_inner.res.as_ref().unwrap();
+        let (slot, ustack_base) = (current_res.slot, current_res.ustack_base);
+        let trap_cx = current_inner.get_trap_cx().clone();
+        drop(current_inner);
         let mut parent = self.inner_exclusive_access();
-        assert_eq!(parent.thread_count(), 1);
-        // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        let vm = if flags.contains(CloneFlags::CLONE_VM) {
+            Arc::clone(&parent.vm)
+        } else {
+            // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
+            let mut parent_vm = parent.vm.lock();
+            let mut memory_set =
+                MemorySet::from_existed_user(&mut parent_vm.memory_set).map_err(|_| Errno::ENOMEM)?;
+            // then drop what belongs to the other threads
+            remove_other_slots(&mut memory_set, &parent_vm, ustack_base, slot);
+            let vm = AddressSpace {
+                memory_set,
+                slots: RecycleAllocator::new(),
+                heap_bottom: parent_vm.heap_bottom,
+                program_brk: parent_vm.program_brk,
+            };
+            Arc::new(SpinNoIrqLock::new(vm))
+        };
+        let files = if flags.contains(CloneFlags::CLONE_FILES) {
+            Arc::clone(&parent.files)
+        } else {
+            Arc::new(SpinNoIrqLock::new(parent.fd_table().clone()))
+        };
+        let sighand = if flags.contains(CloneFlags::CLONE_SIGHAND) {
+            Arc::clone(&parent.sighand)
+        } else {
+            Arc::new(SpinNoIrqLock::new(parent.signal_actions().clone()))
+        };
         // alloc a pid
         let pid = pid_alloc();
-        // copy fd table
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        for fd in parent.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
-        }
         // create child process pcb
         let child = Arc::new(Self {
             pid,
-            inner: unsafe {
-                UPSafeCell::new(ProcessControlBlockInner {
-                    is_zombie: false,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
-                    tasks: Vec::new(),
-                    task_res_allocator: RecycleAllocator::new(),
-                    mutex_list: Vec::new(),
-                    semaphore_list: Vec::new(),
-                    condvar_list: Vec::new(),
-                    deadlock_detect: false,
-                    locker: ProcessLocker::new(),
-                    heap_bottom: parent.heap_bottom,
-                    program_brk: parent.program_brk
-                })
-            },
+            inner: SpinNoIrqLock::new(ProcessControlBlockInner {
+                is_zombie: false,
+                vm,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                pgid: parent.pgid,
+                cwd: parent.cwd.clone(),
+                exit_code: 0,
+                term_signal: 0,
+                stop_signal: None,
+                utime: 0,
+                stime: 0,
+                files,
+                signals: SignalFlags::empty(),
+                sighand,
+                tasks: Vec::new(),
+                task_res_allocator: RecycleAllocator::new(),
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                deadlock_detect: false,
+                locker: ProcessLocker::new(),
+            }),
+            wait_queue: WaitQueue::new(),
+            stop_queue: WaitQueue::new(),
         });
         // add child
         parent.children.push(Arc::clone(&child));
+        drop(parent);
         // create main thread of child process
-        let task = Arc::new(TaskControlBlock::new(
-            Arc::clone(&child),
-            parent
-                .get_task(0)
-                .inner_exclusive_access()
-                .res
-                .as_ref()
-                .unwrap()
-                .ustack_base(),
+        let res = if flags.contains(CloneFlags::CLONE_VM) {
+            // a slot of its own in the shared address space
+            TaskUserRes::new(Arc::clone(&child), ustack_base, true)
+        } else {
             // here we do not allocate trap_cx or ustack again
-            // but mention that we allocate a new kstack here
-            false,
-        ));
+            TaskUserRes::inherit(Arc::clone(&child), ustack_base, slot)
+        };
+        // but mention that we allocate a new kstack here
+        let task = Arc::new(TaskControlBlock::with_res(Arc::clone(&child), res));
         // attach task to child process
-        let mut child_inner = child.inner_exclusive_access();
-        child_inner.locker.init();
-        child_inner.tasks.push(Some(Arc::clone(&task)));
-        drop(child_inner);
-        // modify kstack_top in trap_cx of this thread
+        child.inner_exclusive_access().attach_task(Arc::clone(&task));
+        // copy the trap_cx of the calling thread, with the kstack_top of
+        // the new thread
         let task_inner = task.inner_exclusive_access();
-        let trap_cx = task_inner.get_trap_cx();
-        trap_cx.kernel_sp = task.kstack.get_top();
+        *task_inner.get_trap_cx() = trap_cx;
+        task_inner.get_trap_cx().kernel_sp = task.kstack.get_top();
         drop(task_inner);
         insert_into_pid2process(child.getpid(), Arc::clone(&child));
-        // add this thread to scheduler
-        add_task(task);
-        child
+        Ok(child)
     }
+
+    /// Create a thread in this process, for clone with CLONE_THREAD. It
+    /// starts as a copy of the calling thread on a new kernel stack, with a
+    /// user stack and trap context of its own, which the caller finishes
+    /// before it hands the thread to the scheduler with `add_task`.
+    pub fn clone_thread(self: &Arc<Self>) -> Arc<TaskControlBlock> {
+        let current = current_task().unwrap();
+        let ustack_base = current.inner_exclusive_access().res.as_ref().unwrap().ustack_base;
+        let trap_cx = current.inner_exclusive_access().get_trap_cx().clone();
+        let task = Arc::new(TaskControlBlock::new(Arc::clone(self), ustack_base, true));
+        self.inner_exclusive_access().attach_task(Arc::clone(&task));
+        let task_inner = task.inner_exclusive_access();
+        *task_inner.get_trap_cx() = trap_cx;
+        task_inner.get_trap_cx().kernel_sp = task.kstack.get_top();
+        drop(task_inner);
+        task
+    }
+
     /// get pid
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
     /// change the location of the program break. return None if failed.
     pub fn change_program_brk(&self, size: i32) -> Option<usize> {
-        let mut inner = self.inner_exclusive_access();
-        let heap_bottom = inner.heap_bottom;
-        let old_break = inner.program_brk;
-        let new_brk = inner.program_brk as isize + size as isize;
+        let inner = self.inner_exclusive_access();
+        let mut vm = inner.vm.lock();
+        let heap_bottom = vm.heap_bottom;
+        let old_break = vm.program_brk;
+        let new_brk = vm.program_brk as isize + size as isize;
         if new_brk < heap_bottom as isize {
             return None;
         }
         let result = if size < 0 {
-            inner
-                .memory_set
+            vm.memory_set
                 .shrink_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
         } else {
-            inner
-                .memory_set
+            vm.memory_set
                 .append_to(VirtAddr(heap_bottom), VirtAddr(new_brk as usize))
         };
         if result {
-            inner.program_brk = new_brk as usize;
+            vm.program_brk = new_brk as usize;
             Some(old_break)
         } else {
             None
@@ -395,4 +682,11 @@ impl ProcessLocker {
     pub fn finish(&mut self, id: usize) {
         self.finish[id] = true;
     }
+
+    /// Grant what thread `id` asked for, once it gets the CPU
+    pub fn schedule(&mut self, id: usize) {
+        if self.finish.get(id) == Some(&false) {
+            self.alloc(id);
+        }
+    }
 }
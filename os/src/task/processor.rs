@@ -7,11 +7,17 @@
 use super::__switch_task;
 use super::{fetch_task, TaskStatus};
 use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
-use crate::sync::UPSafeCell;
-use crate::time::get_time_ms;
+use crate::config::MAX_HARTS;
+use crate::sbi::send_ipi;
+use crate::sync::SpinNoIrqLock;
+use crate::time::{get_time_ms, get_time_us};
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use core::arch::asm;
+use core::hint::spin_loop;
+use core::sync::atomic::Ordering;
 use lazy_static::*;
+use riscv::register::sstatus;
 
 /// Processor management structure
 pub struct Processor {
@@ -46,46 +52,95 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// PROCESSORS: one processor per hart, indexed by hart id
+    static ref PROCESSORS: [SpinNoIrqLock<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| SpinNoIrqLock::new(Processor::new()));
+}
+
+/// The id of the hart we are running on, which the kernel keeps in `tp`
+pub fn hart_id() -> usize {
+    let hart_id: usize;
+    unsafe { asm!("mv {}, tp", out(reg) hart_id) };
+    hart_id
+}
+
+/// The processor of the current hart
+fn current_processor() -> &'static SpinNoIrqLock<Processor> {
+    &PROCESSORS[hart_id()]
 }
 
 ///The main part of process execution and scheduling
 ///Loop `fetch_task` to get the process that needs to run, and switch the process through `__switch_task`
 pub fn run_tasks() {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        let mut processor = current_processor().lock();
         if let Some(task) = fetch_task() {
+            let tid = task.inner_exclusive_access().res.as_ref().map(|res| res.tid);
+            if let (Some(tid), Some(process)) = (tid, task.process.upgrade()) {
+                process.inner_exclusive_access().locker.schedule(tid);
+            }
+            // the task may have been put back to the ready queue by another
+            // hart which has not finished saving its context yet
+            while task.on_cpu.load(Ordering::Acquire) {
+                spin_loop();
+            }
+            task.on_cpu.store(true, Ordering::Relaxed);
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let mut task_inner = task.inner_exclusive_access();
+            // stopped by another thread after it was fetched, see kill_other_threads
+            if task_inner.killed {
+                drop(task_inner);
+                task.on_cpu.store(false, Ordering::Release);
+                continue;
+            }
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
             if task_inner.time == 0 {
                 task_inner.time = get_time_ms()
             }
+            task_inner.time_mark = get_time_us();
             // release coming task_inner manually
             drop(task_inner);
-            // release coming task TCB manually
-            processor.current = Some(task);
+            processor.current = Some(Arc::clone(&task));
             // release processor manually
             drop(processor);
             unsafe {
                 __switch_task(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // the task has switched back to us and is off its kernel stack,
+            // which `task` kept alive in case it exited
+            task.on_cpu.store(false, Ordering::Release);
         } else {
-            warn!("no tasks available in run_tasks");
+            drop(processor);
+            // let pending interrupts in: the timers and the disk wake sleeping tasks
+            unsafe {
+                sstatus::set_sie();
+                spin_loop();
+                sstatus::clear_sie();
+            }
+        }
+    }
+}
+
+/// Make `task` trap into the kernel if it runs on another hart right now
+pub fn kick_task(task: &Arc<TaskControlBlock>) {
+    for (hart, processor) in PROCESSORS.iter().enumerate() {
+        let running = processor.lock().current.as_ref().is_some_and(|current| Arc::ptr_eq(current, task));
+        if running && hart != hart_id() {
+            send_ipi(hart);
         }
     }
 }
 
 /// Get current task through take, leaving a None in its place
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().lock().take_current()
 }
 
 /// Get a copy of the current task
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().lock().current()
 }
 
 /// get current process
@@ -125,7 +180,7 @@ pub fn current_kstack_top() -> usize {
 
 /// Return to idle control flow for new scheduling
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = current_processor().lock();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     unsafe {
@@ -0,0 +1,44 @@
+//! Round-robin scheduling
+
+use super::Scheduler;
+use crate::process::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// Runs ready tasks in the order they became ready, one tick each
+pub struct FifoScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl FifoScheduler {
+    /// Create an empty scheduler
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for FifoScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        match self.ready_queue.iter().position(|t| Arc::ptr_eq(t, task)) {
+            Some(index) => {
+                self.ready_queue.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+}
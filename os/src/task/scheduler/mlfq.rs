@@ -0,0 +1,108 @@
+//! Multi-level feedback queue scheduling
+//!
+//! Tasks start in the highest queue and move one queue down once they have
+//! used up the allotment of their queue, which doubles with every level, so
+//! interactive tasks that block early stay ahead of CPU-bound ones. All
+//! tasks are moved back to the top queue every [`BOOST_TICKS`] ticks so
+//! that none of them starves.
+
+use super::Scheduler;
+use crate::process::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// The number of queues
+const LEVELS: usize = 3;
+/// Ticks between two priority boosts
+const BOOST_TICKS: usize = 100;
+
+/// Ticks a task may run at `level` before it is moved down
+fn allotment(level: usize) -> usize {
+    1 << level
+}
+
+/// Runs the first task of the highest non-empty queue
+pub struct MlfqScheduler {
+    queues: [VecDeque<Arc<TaskControlBlock>>; LEVELS],
+    /// ticks since the last priority boost
+    ticks: usize,
+    /// the number of priority boosts so far
+    epoch: usize,
+}
+
+impl MlfqScheduler {
+    /// Create an empty scheduler
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            ticks: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Move every task back to the top queue
+    fn boost(&mut self) {
+        self.ticks = 0;
+        self.epoch += 1;
+        let (top, lower) = self.queues.split_at_mut(1);
+        for queue in lower {
+            top[0].extend(queue.drain(..));
+        }
+        for task in top[0].iter() {
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.sched.level = 0;
+            task_inner.sched.ticks = 0;
+            task_inner.sched.epoch = self.epoch;
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut task_inner = task.inner_exclusive_access();
+        // running or blocked during the last boost
+        if task_inner.sched.epoch != self.epoch {
+            task_inner.sched.level = 0;
+            task_inner.sched.ticks = 0;
+            task_inner.sched.epoch = self.epoch;
+        }
+        let level = task_inner.sched.level;
+        drop(task_inner);
+        self.queues[level].push_back(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        for queue in self.queues.iter_mut() {
+            if let Some(index) = queue.iter().position(|t| Arc::ptr_eq(t, task)) {
+                queue.remove(index);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        self.ticks += 1;
+        if self.ticks >= BOOST_TICKS {
+            self.boost();
+        }
+        let mut task_inner = task.inner_exclusive_access();
+        let sched = &mut task_inner.sched;
+        if sched.epoch != self.epoch {
+            sched.level = 0;
+            sched.ticks = 0;
+            sched.epoch = self.epoch;
+        }
+        sched.ticks += 1;
+        if sched.ticks < allotment(sched.level) {
+            return false;
+        }
+        sched.level = (sched.level + 1).min(LEVELS - 1);
+        sched.ticks = 0;
+        true
+    }
+}
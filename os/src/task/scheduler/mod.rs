@@ -0,0 +1,76 @@
+//! Scheduling policies behind the ready queue
+//!
+//! [`super::manager::TaskManager`] keeps its ready tasks in a [`Scheduler`].
+//! The policy is chosen at build time: the `sched-fifo` or `sched-mlfq`
+//! cargo feature selects round-robin or a multi-level feedback queue,
+//! otherwise stride scheduling is used so that priorities set through
+//! `sys_set_priority` decide the share of CPU time.
+
+mod fifo;
+mod mlfq;
+mod stride;
+
+use super::TaskControlBlock;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+
+pub use fifo::FifoScheduler;
+pub use mlfq::MlfqScheduler;
+pub use stride::StrideScheduler;
+
+/// The priority of a new task
+pub const DEFAULT_PRIORITY: usize = 16;
+
+/// A scheduling policy
+pub trait Scheduler: Send {
+    /// Make a ready task available to [`Scheduler::fetch`]
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    /// Take the task to run next
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+    /// Drop a task which is no longer runnable, return false if it was not
+    /// in the scheduler
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool;
+    /// A timer tick passed while `task` was running, return true if it has
+    /// used up its time slice and should give up the CPU
+    fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool;
+}
+
+/// Per-task scheduling state, kept in the TCB so that it survives while the
+/// task is running or blocked
+#[derive(Copy, Clone, Debug)]
+pub struct SchedEntity {
+    /// priority, at least 2; a larger value gets a larger share of the CPU
+    pub priority: usize,
+    /// stride scheduling: the virtual time the task has consumed
+    pub pass: usize,
+    /// MLFQ: the queue the task belongs to, 0 being the highest priority
+    pub level: usize,
+    /// MLFQ: ticks used of the allotment at `level`
+    pub ticks: usize,
+    /// MLFQ: the priority boost `level` refers to
+    pub epoch: usize,
+}
+
+impl SchedEntity {
+    /// Scheduling state of a new task
+    pub fn new() -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            pass: 0,
+            level: 0,
+            ticks: 0,
+            epoch: 0,
+        }
+    }
+}
+
+/// The scheduler selected by cargo features
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    if cfg!(feature = "sched-fifo") {
+        Box::new(FifoScheduler::new())
+    } else if cfg!(feature = "sched-mlfq") {
+        Box::new(MlfqScheduler::new())
+    } else {
+        Box::new(StrideScheduler::new())
+    }
+}
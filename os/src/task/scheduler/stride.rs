@@ -0,0 +1,105 @@
+//! Stride scheduling
+//!
+//! Every task advances its pass by `BIG_STRIDE / priority` each time it is
+//! scheduled, and the task with the smallest pass runs next, so the CPU
+//! share of a task is proportional to its priority.
+
+use super::Scheduler;
+use crate::process::TaskControlBlock;
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+
+/// The stride of a task with priority 1; priorities are at least 2, so no
+/// two passes in the heap are more than `BIG_STRIDE / 2` apart
+const BIG_STRIDE: usize = 1 << 20;
+
+/// Compare passes, allowing them to wrap around
+fn pass_cmp(a: usize, b: usize) -> Ordering {
+    (a.wrapping_sub(b) as isize).cmp(&0)
+}
+
+/// A ready task ordered by pass, then by the order it was added
+struct StrideEntry {
+    pass: usize,
+    seq: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for StrideEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StrideEntry {}
+
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StrideEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, the smallest pass has to come out first
+        pass_cmp(other.pass, self.pass).then(other.seq.cmp(&self.seq))
+    }
+}
+
+/// Runs the ready task with the smallest pass
+pub struct StrideScheduler {
+    heap: BinaryHeap<StrideEntry>,
+    /// pass of the task scheduled last, where new and woken tasks start
+    min_pass: usize,
+    seq: usize,
+}
+
+impl StrideScheduler {
+    /// Create an empty scheduler
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            min_pass: 0,
+            seq: 0,
+        }
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut task_inner = task.inner_exclusive_access();
+        // a task which slept must not make up for the time it did not run
+        if pass_cmp(task_inner.sched.pass, self.min_pass) == Ordering::Less {
+            task_inner.sched.pass = self.min_pass;
+        }
+        let pass = task_inner.sched.pass;
+        drop(task_inner);
+        self.seq = self.seq.wrapping_add(1);
+        self.heap.push(StrideEntry {
+            pass,
+            seq: self.seq,
+            task,
+        });
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let StrideEntry { pass, task, .. } = self.heap.pop()?;
+        self.min_pass = pass;
+        let mut task_inner = task.inner_exclusive_access();
+        let stride = BIG_STRIDE / task_inner.sched.priority;
+        task_inner.sched.pass = pass.wrapping_add(stride);
+        drop(task_inner);
+        Some(task)
+    }
+
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        let len = self.heap.len();
+        self.heap.retain(|entry| !Arc::ptr_eq(&entry.task, task));
+        self.heap.len() != len
+    }
+
+    fn tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+}
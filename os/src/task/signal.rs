@@ -1,24 +1,143 @@
-//! Signal flags and function for convert signal flag to integer & string
+//! Signal flags, signal actions and the signal frame pushed onto the user stack
+//!
+//! The layout of [`SignalFlags`] follows the Linux `sigset_t`: signal `n` is
+//! stored in bit `n - 1`, so masks can be exchanged with user space directly.
 
 use bitflags::*;
 
+/// The largest signal number supported
+pub const MAX_SIG: usize = 31;
+
+/// Default signal handler
+pub const SIG_DFL: usize = 0;
+/// Ignore the signal
+pub const SIG_IGN: usize = 1;
+
+/// `how` of sigprocmask: add the set to the blocked mask
+pub const SIG_BLOCK: usize = 0;
+/// `how` of sigprocmask: remove the set from the blocked mask
+pub const SIG_UNBLOCK: usize = 1;
+/// `how` of sigprocmask: replace the blocked mask
+pub const SIG_SETMASK: usize = 2;
+
 bitflags! {
     /// Signal flags
-    pub struct SignalFlags: u32 {
+    pub struct SignalFlags: u64 {
+        /// Hangup
+        const SIGHUP    = 1 << 0;
         /// Interrupt
-        const SIGINT    = 1 << 2;
+        const SIGINT    = 1 << 1;
+        /// Quit
+        const SIGQUIT   = 1 << 2;
         /// Illegal instruction
-        const SIGILL    = 1 << 4;
+        const SIGILL    = 1 << 3;
+        /// Trace trap
+        const SIGTRAP   = 1 << 4;
         /// Abort
-        const SIGABRT   = 1 << 6;
+        const SIGABRT   = 1 << 5;
+        /// Bus error
+        const SIGBUS    = 1 << 6;
         /// Floating point exception
-        const SIGFPE    = 1 << 8;
+        const SIGFPE    = 1 << 7;
+        /// Kill, can not be caught or ignored
+        const SIGKILL   = 1 << 8;
+        /// User defined signal 1
+        const SIGUSR1   = 1 << 9;
         /// Segmentation fault
-        const SIGSEGV   = 1 << 11;
+        const SIGSEGV   = 1 << 10;
+        /// User defined signal 2
+        const SIGUSR2   = 1 << 11;
+        /// Write to a pipe with no reader
+        const SIGPIPE   = 1 << 12;
+        /// Alarm clock
+        const SIGALRM   = 1 << 13;
+        /// Termination
+        const SIGTERM   = 1 << 14;
+        /// Stack fault
+        const SIGSTKFLT = 1 << 15;
+        /// Child stopped or terminated
+        const SIGCHLD   = 1 << 16;
+        /// Continue if stopped
+        const SIGCONT   = 1 << 17;
+        /// Stop, can not be caught or ignored
+        const SIGSTOP   = 1 << 18;
+        /// Stop typed at terminal
+        const SIGTSTP   = 1 << 19;
+        /// Terminal input for background process
+        const SIGTTIN   = 1 << 20;
+        /// Terminal output for background process
+        const SIGTTOU   = 1 << 21;
+        /// Urgent condition on socket
+        const SIGURG    = 1 << 22;
+        /// CPU time limit exceeded
+        const SIGXCPU   = 1 << 23;
+        /// File size limit exceeded
+        const SIGXFSZ   = 1 << 24;
+        /// Virtual alarm clock
+        const SIGVTALRM = 1 << 25;
+        /// Profiling alarm clock
+        const SIGPROF   = 1 << 26;
+        /// Window size change
+        const SIGWINCH  = 1 << 27;
+        /// I/O now possible
+        const SIGIO     = 1 << 28;
+        /// Power failure
+        const SIGPWR    = 1 << 29;
+        /// Bad system call
+        const SIGSYS    = 1 << 30;
     }
 }
 
+/// What the kernel does for a signal whose handler is [`SIG_DFL`]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SignalDefault {
+    /// Terminate the process
+    Terminate,
+    /// Ignore the signal
+    Ignore,
+    /// Stop the process until SIGCONT
+    Stop,
+    /// Continue a stopped process
+    Continue,
+}
+
 impl SignalFlags {
+    /// The flag of signal number `signum`, or None if it is out of range
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            None
+        } else {
+            Self::from_bits(1 << (signum - 1))
+        }
+    }
+
+    /// The lowest signal number in this set
+    pub fn lowest_signum(&self) -> Option<usize> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.bits.trailing_zeros() as usize + 1)
+        }
+    }
+
+    /// Signals which can be neither blocked nor caught
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+
+    /// The default action of this signal
+    pub fn default_action(&self) -> SignalDefault {
+        if self.intersects(Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH) {
+            SignalDefault::Ignore
+        } else if self.intersects(Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU) {
+            SignalDefault::Stop
+        } else if self.contains(Self::SIGCONT) {
+            SignalDefault::Continue
+        } else {
+            SignalDefault::Terminate
+        }
+    }
+
     /// convert signal flag to integer & string
     pub fn check_error(&self) -> Option<(i32, &'static str)> {
         if self.contains(Self::SIGINT) {
@@ -29,11 +148,126 @@ impl SignalFlags {
             Some((-6, "Aborted, SIGABRT=6"))
         } else if self.contains(Self::SIGFPE) {
             Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
+        } else if self.contains(Self::SIGKILL) {
+            Some((-9, "Killed, SIGKILL=9"))
         } else if self.contains(Self::SIGSEGV) {
             Some((-11, "Segmentation Fault, SIGSEGV=11"))
         } else {
             // warn!("[kernel] signalflags check_error  {:?}", self);
-            None
+            self.lowest_signum().map(|signum| (-(signum as i32), "Terminated by signal"))
+        }
+    }
+}
+
+bitflags! {
+    /// Flags of a signal action (`sa_flags`)
+    pub struct SignalActionFlags: usize {
+        /// Do not notify on child stop
+        const SA_NOCLDSTOP = 1;
+        /// Do not create zombies on child exit
+        const SA_NOCLDWAIT = 2;
+        /// Handler takes (signum, siginfo, context)
+        const SA_SIGINFO   = 4;
+        /// Use the alternate signal stack
+        const SA_ONSTACK   = 0x0800_0000;
+        /// Restart interrupted syscalls
+        const SA_RESTART   = 0x1000_0000;
+        /// Do not block the signal while its handler runs
+        const SA_NODEFER   = 0x4000_0000;
+        /// Reset the handler to SIG_DFL once delivered
+        const SA_RESETHAND = 0x8000_0000;
+    }
+}
+
+/// A signal action, laid out as the riscv64 Linux `struct sigaction`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SignalAction {
+    /// Handler address, or [`SIG_DFL`] / [`SIG_IGN`]
+    pub handler: usize,
+    /// Action flags
+    pub flags: SignalActionFlags,
+    /// Signals additionally blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            flags: SignalActionFlags::empty(),
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// Per-process signal action table, indexed by signal number
+#[derive(Clone)]
+pub struct SignalActions {
+    /// actions, entry 0 is unused
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
         }
     }
 }
+
+impl SignalActions {
+    /// Reset caught signals to their default action, as required across exec
+    pub fn reset_handlers(&mut self) {
+        for action in self.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+}
+
+/// Minimal `siginfo_t` passed to SA_SIGINFO handlers
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SignalInfo {
+    /// signal number
+    pub signo: i32,
+    /// errno value
+    pub errno: i32,
+    /// signal code
+    pub code: i32,
+    _pad: i32,
+    /// faulting address for SIGSEGV/SIGBUS/SIGILL
+    pub addr: usize,
+    _rest: [usize; 13],
+}
+
+impl SignalInfo {
+    /// Create siginfo for `signum`
+    pub fn new(signum: usize, addr: usize) -> Self {
+        Self {
+            signo: signum as i32,
+            errno: 0,
+            code: 0,
+            _pad: 0,
+            addr,
+            _rest: [0; 13],
+        }
+    }
+}
+
+/// The frame pushed onto the user stack before running a handler,
+/// popped again by `sys_sigreturn`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SignalFrame {
+    /// siginfo handed to SA_SIGINFO handlers
+    pub info: SignalInfo,
+    /// interrupted general purpose registers
+    pub regs: [usize; 32],
+    /// interrupted pc
+    pub sepc: usize,
+    /// blocked mask to restore
+    pub mask: SignalFlags,
+}
@@ -1,13 +1,15 @@
 //! Types related to task management & Functions for completely changing TCB
 
 use super::id::TaskUserRes;
-use super::stride::Stride;
-use super::{current_task, kstack_alloc, KernelStack, ProcessControlBlock, TaskContext};
+use super::scheduler::SchedEntity;
+use super::{current_task, kstack_alloc, KernelStack, ProcessControlBlock, SignalFlags, TaskContext};
 use crate::config::MAX_SYSCALL_NUM;
+use crate::time::get_time_us;
 use crate::trap::TrapContext;
-use crate::{mem::PhysPageNum, sync::UPSafeCell};
+use crate::mem::PhysPageNum;
+use crate::sync::{SpinNoIrqGuard, SpinNoIrqLock};
 use alloc::sync::{Arc, Weak};
-use core::cell::RefMut;
+use core::sync::atomic::AtomicBool;
 
 /// Task control block structure
 pub struct TaskControlBlock {
@@ -16,34 +18,27 @@ pub struct TaskControlBlock {
     /// Kernel stack corresponding to PID
     pub kstack: KernelStack,
     /// mutable
-    inner: UPSafeCell<TaskControlBlockInner>,
-    /// Priority stride
-    pub stride: Stride,
+    inner: SpinNoIrqLock<TaskControlBlockInner>,
+    /// Whether a hart is running the task or still switching away from it
+    pub on_cpu: AtomicBool,
 }
 
 impl TaskControlBlock {
     /// Get the mutable reference of the inner TCB
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> SpinNoIrqGuard<'_, TaskControlBlockInner> {
+        self.inner.lock()
     }
     /// Get the address of app's page table
     pub fn get_user_token(&self) -> usize {
         let process = self.process.upgrade().unwrap();
         let inner = process.inner_exclusive_access();
-        inner.memory_set.token()
+        inner.get_user_token()
     }
     /// Set priority
-    pub fn set_priority(self: &mut Arc<Self>, p: usize) -> usize {
-        unsafe { Arc::get_mut_unchecked(self).stride.set_priority(p) }
+    pub fn set_priority(&self, p: usize) -> usize {
+        self.inner_exclusive_access().sched.priority = p;
         p
     }
-
-    /// Accumulate stride
-    pub fn accumulate_stride(self: &mut Arc<Self>) {
-        unsafe {
-            Arc::get_mut_unchecked(self).stride.accumulate();
-        }
-    }
 }
 
 pub struct TaskControlBlockInner {
@@ -61,6 +56,32 @@ pub struct TaskControlBlockInner {
     pub time: usize,
     /// The numbers of syscall called by process
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
+    /// Pending signals directed at this thread
+    pub signals: SignalFlags,
+    /// Signals blocked by this thread
+    pub signal_mask: SignalFlags,
+    /// The mask to go back to once the signals a ppoll or pselect6 mask let
+    /// through have been delivered
+    pub saved_signal_mask: Option<SignalFlags>,
+    /// Time spent in user mode, in microseconds
+    pub utime: usize,
+    /// Time spent in the kernel on behalf of this thread, in microseconds
+    pub stime: usize,
+    /// When the thread last entered or left the kernel, or was scheduled
+    pub time_mark: usize,
+    /// User address cleared when the thread exits, see CLONE_CHILD_CLEARTID
+    /// and set_tid_address
+    pub clear_child_tid: usize,
+    /// State of the scheduling policy
+    pub sched: SchedEntity,
+    /// Set by a wakeup which came while the task was about to block
+    pub wakeup_pending: bool,
+    /// Set while the task sleeps until an event or a signal, see
+    /// [`super::block_current_interruptible_and_run_next`]
+    pub interruptible: bool,
+    /// Set when another thread stops this one for good, see
+    /// [`super::kill_other_threads`]; the task is not scheduled again
+    pub killed: bool,
 }
 
 impl TaskControlBlockInner {
@@ -74,7 +95,25 @@ impl TaskControlBlockInner {
     }
 
     fn watch_syscall(&mut self, syscall_id: usize) {
-        self.syscall_times[syscall_id] += 1
+        // unknown ids beyond the table fail with ENOSYS, uncounted
+        if let Some(times) = self.syscall_times.get_mut(syscall_id) {
+            *times += 1;
+        }
+    }
+
+    /// Charge the time since the last mark to user time, on a trap from user mode
+    pub fn charge_user_time(&mut self) {
+        let now = get_time_us();
+        self.utime += now - self.time_mark;
+        self.time_mark = now;
+    }
+
+    /// Charge the time since the last mark to system time, on returning to
+    /// user mode or giving up the CPU
+    pub fn charge_system_time(&mut self) {
+        let now = get_time_us();
+        self.stime += now - self.time_mark;
+        self.time_mark = now;
     }
 }
 
@@ -87,6 +126,10 @@ impl TaskControlBlock {
     ) -> Self {
         // println!("TaskControlBlock::new");
         let res = TaskUserRes::new(Arc::clone(&process), ustack_base, alloc_user_res);
+        Self::with_res(process, res)
+    }
+    /// Create a new task on the given user resource
+    pub fn with_res(process: Arc<ProcessControlBlock>, res: TaskUserRes) -> Self {
         // println!("TaskControlBlock::new1");
         let trap_cx_ppn = res.trap_cx_ppn();
         let kstack = kstack_alloc();
@@ -94,18 +137,27 @@ impl TaskControlBlock {
         Self {
             process: Arc::downgrade(&process),
             kstack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    res: Some(res),
-                    trap_cx_ppn,
-                    task_cx: TaskContext::goto_trap_return(kstack_top),
-                    task_status: TaskStatus::Ready,
-                    exit_code: None,
-                    time: 0,
-                    syscall_times: [0; MAX_SYSCALL_NUM]
-                })
-            },
-            stride: Stride::new()
+            inner: SpinNoIrqLock::new(TaskControlBlockInner {
+                res: Some(res),
+                trap_cx_ppn,
+                task_cx: TaskContext::goto_trap_return(kstack_top),
+                task_status: TaskStatus::Ready,
+                exit_code: None,
+                time: 0,
+                syscall_times: [0; MAX_SYSCALL_NUM],
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                saved_signal_mask: None,
+                utime: 0,
+                stime: 0,
+                time_mark: 0,
+                clear_child_tid: 0,
+                sched: SchedEntity::new(),
+                wakeup_pending: false,
+                interruptible: false,
+                killed: false,
+            }),
+            on_cpu: AtomicBool::new(false),
         }
     }
 
@@ -4,7 +4,7 @@ use core::cmp::Ordering;
 
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinNoIrqLock;
 use crate::process::{current_task, wakeup_task, TaskControlBlock};
 use alloc::collections::BinaryHeap;
 use alloc::sync::Arc;
@@ -68,8 +68,8 @@ impl Ord for TimerCondVar {
 
 lazy_static! {
     /// TIMERS: global instance: set of timer condvars
-    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondVar>> =
-        unsafe { UPSafeCell::new(BinaryHeap::<TimerCondVar>::new()) };
+    static ref TIMERS: SpinNoIrqLock<BinaryHeap<TimerCondVar>> =
+        SpinNoIrqLock::new(BinaryHeap::<TimerCondVar>::new());
 }
 
 #[allow(unused)]
@@ -79,7 +79,7 @@ pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
         "kernel:pid[{}] add_timer",
         current_task().unwrap().process.upgrade().unwrap().getpid()
     );
-    let mut timers = TIMERS.exclusive_access();
+    let mut timers = TIMERS.lock();
     timers.push(TimerCondVar { expire_ms, task });
 }
 
@@ -87,7 +87,7 @@ pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
 pub fn remove_timer(task: Arc<TaskControlBlock>) {
     //trace!("kernel:pid[{}] remove_timer", current_task().unwrap().process.upgrade().unwrap().getpid());
     trace!("kernel: remove_timer");
-    let mut timers = TIMERS.exclusive_access();
+    let mut timers = TIMERS.lock();
     let mut temp = BinaryHeap::<TimerCondVar>::new();
     for condvar in timers.drain() {
         if Arc::as_ptr(&task) != Arc::as_ptr(&condvar.task) {
@@ -99,15 +99,10 @@ pub fn remove_timer(task: Arc<TaskControlBlock>) {
     trace!("kernel: remove_timer END");
 }
 
-#[allow(unused)]
 /// Check if the timer has expired
 pub fn check_timer() {
-    trace!(
-        "kernel:pid[{}] check_timer",
-        current_task().unwrap().process.upgrade().unwrap().getpid()
-    );
     let current_ms = get_time_ms();
-    let mut timers = TIMERS.exclusive_access();
+    let mut timers = TIMERS.lock();
     while let Some(timer) = timers.peek() {
         if timer.expire_ms <= current_ms {
             wakeup_task(Arc::clone(&timer.task));
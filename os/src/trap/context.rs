@@ -2,7 +2,7 @@
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 ///trap context structure containing sstatus, sepc and registers
 pub struct TrapContext {
     /// General-Purpose Register x0-31
@@ -17,6 +17,8 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     /// Virtual address of trap handler entry point in kernel
     pub trap_handler: usize,
+    /// Kernel tp, i.e. the id of the hart the application runs on
+    pub kernel_tp: usize,
 }
 
 impl TrapContext {
@@ -42,6 +44,7 @@ impl TrapContext {
             kernel_satp,  // addr of page table
             kernel_sp,    // kernel stack
             trap_handler, // addr of trap_handler function
+            kernel_tp: 0, // filled in by __restore
         };
         cx.set_sp(sp); // app's user stack pointer
         cx // return initial Trap Context of app
@@ -11,20 +11,26 @@
 //! It then calls different functionality based on what exactly the exception
 //! was. For example, timer interrupts trigger process preemption, and syscalls go
 //! to [`syscall()`].
+//!
+//! Traps taken in the kernel go through `__alltraps_k` to [`trap_from_kernel()`]
+//! instead, which only expects the interrupts an idle hart lets in.
 
 mod context;
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT_BASE};
+use crate::config::TRAMPOLINE;
+use crate::driver::irq_handler;
+use crate::mem::{MapPermission, PageFault};
 use crate::syscall::syscall;
 use crate::process::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    current_force_signal, current_task, current_trap_cx, current_trap_cx_user_va, current_user_token,
+    exit_current_and_run_next, handle_signals, handle_user_fault, tick_current_and_run_next, SignalFlags,
 };
-use crate::time::set_next_trigger;
+use crate::time::{check_timer, set_next_trigger};
 use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sie, sip, stval, stvec,
 };
 
 global_asm!(include_str!("trap.S"));
@@ -35,8 +41,11 @@ pub fn init() {
 }
 
 fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __alltraps_k();
+    }
     unsafe {
-        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
+        stvec::write(__alltraps_k as usize, TrapMode::Direct);
     }
 }
 
@@ -53,10 +62,25 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// enable software interrupts in supervisor mode, which other harts send to
+/// kick a thread out of user mode
+pub fn enable_software_interrupt() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
+
 /// trap handler
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    {
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.charge_user_time();
+        // a wakeup left over from an earlier trap must not cut a later sleep short
+        task_inner.wakeup_pending = false;
+    }
     let scause = scause::read();
     let stval = stval::read();
     // trace!("into {:?}", scause.cause());
@@ -66,55 +90,102 @@ pub fn trap_handler() -> ! {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12], cx.x[13]]);
-            // cx is changed during sys_exec, so we have to call it again
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            // cx is changed during sys_execve, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        cause if resolve_page_fault(cause, stval) => {
+            // copy-on-write or lazily allocated page is ready, retry the access,
+            // unless SIGBUS is on its way
+        }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
         | Trap::Exception(Exception::InstructionPageFault)
         | Trap::Exception(Exception::LoadFault)
         | Trap::Exception(Exception::LoadPageFault) => {
-            println!(
-                "[kernel] trap_handler:  {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
+            trace!(
+                "[kernel] trap_handler:  {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, raise SIGSEGV.",
                 scause.cause(),
                 stval,
                 current_trap_cx().sepc,
             );
-            // page fault exit code
-            exit_current_and_run_next(-2);
+            current_force_signal(SignalFlags::SIGSEGV);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            // illegal instruction exit code
-            exit_current_and_run_next(-3);
+            trace!("[kernel] IllegalInstruction in application, raise SIGILL.");
+            current_force_signal(SignalFlags::SIGILL);
+        }
+        Trap::Exception(Exception::Breakpoint) => {
+            current_force_signal(SignalFlags::SIGTRAP);
+        }
+        Trap::Exception(Exception::InstructionMisaligned)
+        | Trap::Exception(Exception::StoreMisaligned) => {
+            current_force_signal(SignalFlags::SIGBUS);
+        }
+        Trap::Exception(_) => {
+            trace!("[kernel] {:?} in application, raise SIGILL.", scause.cause());
+            current_force_signal(SignalFlags::SIGILL);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
-            suspend_current_and_run_next();
+            check_timer();
+            tick_current_and_run_next();
         }
-        _ => {
-            panic!(
-                "Unsupported trap {:?}, stval = {:#x}!",
-                scause.cause(),
-                stval
-            );
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            irq_handler();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // kicked by another hart, trap_return looks at what it wants
+            unsafe { sip::clear_ssoft() };
+        }
+        Trap::Interrupt(_) => {
+            warn!("[kernel] ignore unexpected interrupt {:?}", scause.cause());
         }
     }
     //println!("before trap_return");
     trap_return();
 }
 
+/// Try to resolve a user page fault through the current address space,
+/// return false if the access is not allowed. Running out of frames for an
+/// allowed access raises SIGBUS instead of taking the kernel down.
+fn resolve_page_fault(cause: Trap, stval: usize) -> bool {
+    let access = match cause {
+        Trap::Exception(Exception::StorePageFault) => MapPermission::W,
+        Trap::Exception(Exception::LoadPageFault) => MapPermission::R,
+        Trap::Exception(Exception::InstructionPageFault) => MapPermission::X,
+        _ => return false,
+    };
+    match handle_user_fault(stval.into(), access) {
+        Ok(()) => true,
+        Err(PageFault::OutOfMemory) => {
+            current_force_signal(SignalFlags::SIGBUS);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
 #[no_mangle]
 /// return to user space
+/// deliver pending signals first, which may redirect the trap context to a handler,
 /// set the new addr of __restore asm function in TRAMPOLINE page,
 /// set the reg a0 = trap_cx_ptr, reg a1 = phy addr of usr page table,
 /// finally, jump to new addr of __restore asm function
 pub fn trap_return() -> ! {
+    // another thread is taking the process down or replacing its image
+    if current_task().unwrap().inner_exclusive_access().killed {
+        exit_current_and_run_next(0);
+    }
+    handle_signals();
+    current_task().unwrap().inner_exclusive_access().charge_system_time();
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT_BASE;
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
@@ -135,13 +206,33 @@ pub fn trap_return() -> ! {
 }
 
 #[no_mangle]
-/// handle trap from kernel
-/// Unimplement: traps/interrupts/exceptions from kernel mode
-/// Todo: Chapter 9: I/O device
-pub fn trap_from_kernel() -> ! {
+/// handle trap from kernel, entered through `__alltraps_k` which saves the
+/// interrupted registers on the current kernel stack.
+/// The kernel only enables interrupts while a hart idles, so faults here are bugs
+pub fn trap_from_kernel() {
     use riscv::register::sepc;
-    trace!("stval = {:#x}, sepc = {:#x}", stval::read(), sepc::read());
-    panic!("a trap {:?} from kernel!", scause::read().cause());
+    match scause::read().cause() {
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            irq_handler();
+        }
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // nothing to preempt, but the timers still have to fire
+            set_next_trigger();
+            check_timer();
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            // a kick meant for a thread which is gone from this hart already
+            unsafe { sip::clear_ssoft() };
+        }
+        cause => {
+            panic!(
+                "a trap {:?} from kernel, stval = {:#x}, sepc = {:#x}!",
+                cause,
+                stval::read(),
+                sepc::read()
+            );
+        }
+    }
 }
 
 pub use context::TrapContext;
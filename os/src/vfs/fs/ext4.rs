@@ -1,3 +1,4 @@
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::*;
@@ -24,6 +25,9 @@ const DISK_BLOCK_SIZE: usize = 512;
 // The block size of the file system
 pub const BLOCK_SIZE: usize = 4096;
 
+// The inode of the root directory
+const ROOT_INODE_NUM: u32 = 2;
+
 impl BlockDevice for DiskAdapter {
     fn read_offset(&self, offset: usize) -> Vec<u8> {
         let mut disk = self.inner.borrow_mut();
@@ -90,7 +94,10 @@ impl Ext4FileSystem {
             inner: RefCell::new(disk),
         });
         let inner = Ext4::open(block_device);
-        let root = Arc::new(Ext4FileWrapper::new(inner.clone()));
+        let open_inodes = Arc::new(Mutex::new(OpenInodes::default()));
+        let mut root_file = Ext4File::new();
+        root_file.inode = ROOT_INODE_NUM;
+        let root = Arc::new(Ext4FileWrapper::new(inner.clone(), open_inodes, root_file));
         Self {
             inner: inner.clone(),
             root_dir: root,
@@ -109,34 +116,99 @@ impl VfsOps for Ext4FileSystem {
     }
 }
 
+// How often each inode is open. An inode that loses its last link while
+// open is freed on its last release instead
+#[derive(Default)]
+struct OpenInodes {
+    counts: BTreeMap<u32, usize>,
+    // unlinked inodes waiting for their last release
+    orphans: BTreeSet<u32>,
+}
+
 pub struct Ext4FileWrapper {
     ext4_file: Mutex<Ext4File>,
     ext4: Arc<Ext4>,
+    open_inodes: Arc<Mutex<OpenInodes>>,
 }
 
 unsafe impl Send for Ext4FileWrapper {}
 unsafe impl Sync for Ext4FileWrapper {}
 
 impl Ext4FileWrapper {
-    fn new(ext4: Arc<Ext4>) -> Self {
+    fn new(ext4: Arc<Ext4>, open_inodes: Arc<Mutex<OpenInodes>>, ext4_file: Ext4File) -> Self {
         Self {
-            ext4_file: Mutex::new(Ext4File::new()),
+            ext4_file: Mutex::new(ext4_file),
             ext4: ext4,
+            open_inodes,
         }
     }
+
+    /// A node of the same file system for an opened `ext4_file`
+    fn sibling(&self, ext4_file: Ext4File) -> VfsNodeRef {
+        Arc::new(Self::new(self.ext4.clone(), self.open_inodes.clone(), ext4_file))
+    }
+
+    fn open_path(&self, path: &str) -> VfsResult<Ext4File> {
+        let mut ext4_file = Ext4File::new();
+        let r = self.ext4.ext4_open(&mut ext4_file, path, "r+", false);
+
+        if let Err(e) = r {
+            match e.error() {
+                Errnum::ENOENT => Err(VfsError::NotFound),
+                Errnum::EALLOCFIAL => Err(VfsError::InvalidInput(Some(format!("{:?}", e)))),
+                Errnum::ELINKFIAL => Err(VfsError::InvalidInput(Some(format!("{:?}", e)))),
+
+                _ => Err(VfsError::InvalidInput(Some(format!("{:?}", e)))),
+            }
+        } else {
+            Ok(ext4_file)
+        }
+    }
+
+    fn inode_ref(&self, inode: u32) -> Ext4InodeRef {
+        Ext4InodeRef::get_inode_ref(Arc::downgrade(&self.ext4), inode)
+    }
+
+    /// The inode of the directory holding `path`, and the name of `path` in it
+    fn parent_of<'a>(&self, path: &'a str) -> VfsResult<(u32, &'a str)> {
+        match path.trim_end_matches('/').rsplit_once('/') {
+            Some((parent, name)) if !parent.is_empty() => Ok((self.open_path(parent)?.inode, name)),
+            Some((_, name)) => Ok((ROOT_INODE_NUM, name)),
+            None => Ok((ROOT_INODE_NUM, path)),
+        }
+    }
+
+    /// Give the blocks and the inode itself back, once nothing refers to it
+    fn free_inode(&self, inode: u32) {
+        let mut inode_ref = self.inode_ref(inode);
+        self.ext4.ext4_fs_truncate_inode(&mut inode_ref, 0);
+        self.ext4.ext4_fs_free_inode(&mut inode_ref);
+    }
 }
 
 impl VfsNodeOps for Ext4FileWrapper {
     /// Do something when the node is opened.
     fn open(&self) -> VfsResult {
-        // log::info!("opening file");
-        // let mut ext4_file = self.ext4_file.lock();
-        // let r = self.ext4.ext4_open(&mut ext4_file, path, "r+", false);
+        let inode = self.ext4_file.lock().inode;
+        *self.open_inodes.lock().counts.entry(inode).or_insert(0) += 1;
         Ok(())
     }
 
     /// Do something when the node is closed.
     fn release(&self) -> VfsResult {
+        let inode = self.ext4_file.lock().inode;
+        let mut open_inodes = self.open_inodes.lock();
+        let Some(count) = open_inodes.counts.get_mut(&inode) else {
+            return Ok(());
+        };
+        *count -= 1;
+        if *count == 0 {
+            open_inodes.counts.remove(&inode);
+            if open_inodes.orphans.remove(&inode) {
+                drop(open_inodes);
+                self.free_inode(inode);
+            }
+        }
         Ok(())
     }
 
@@ -151,7 +223,9 @@ impl VfsNodeOps for Ext4FileWrapper {
         let blocks = root_inode_ref.inner.inode.blocks * 8;
         let (ty, perm) = map_imode(inode_mode as u16);
         drop(ext4_file);
-        Ok(VfsNodeAttr::new(perm, ty, size as _, blocks as _))
+        let mut attr = VfsNodeAttr::new(perm, ty, size as _, blocks as _);
+        attr.set_nlink(root_inode_ref.inner.inode.links_count as _);
+        Ok(attr)
     }
 
     // file operations:
@@ -220,24 +294,9 @@ impl VfsNodeOps for Ext4FileWrapper {
     ///
     /// Return the node if found.
     fn lookup(&self, path: &str) -> VfsResult<VfsNodeRef> {
-        let mut ext4_file = self.ext4_file.lock();
-        let r = self.ext4.ext4_open(&mut ext4_file, path, "r+", false);
-
-        if let Err(e) = r {
-            match e.error() {
-                Errnum::ENOENT => Err(VfsError::NotFound),
-                Errnum::EALLOCFIAL => Err(VfsError::InvalidInput(Some(format!("{:?}", e)))),
-                Errnum::ELINKFIAL => Err(VfsError::InvalidInput(Some(format!("{:?}", e)))),
-
-                _ => Err(VfsError::InvalidInput(Some(format!("{:?}", e)))),
-            }
-        } else {
-            drop(ext4_file);
-            // log::error!("file found");
-            // Create a Arc that wraps the self as a immutable reference
-            let file = Ext4FileWrapper::new(self.ext4.clone());
-            Ok(Arc::new(file))
-        }
+        // the node keeps the opened file, its inode above all
+        let ext4_file = self.open_path(path)?;
+        Ok(self.sibling(ext4_file))
     }
 
     /// Create a new node with the given `path` in the directory
@@ -254,18 +313,14 @@ impl VfsNodeOps for Ext4FileWrapper {
             VfsNodeType::Socket => DirEntryType::EXT4_DE_SOCK,
         };
 
-        let mut ext4file = self.ext4_file.lock();
-
         if types == DirEntryType::EXT4_DE_DIR {
             let _ = self.ext4.ext4_dir_mk(path);
-        } else {
-            let _ = self.ext4.ext4_open(&mut ext4file, path, "w+", true);
+            return self.lookup(path);
         }
 
-        drop(ext4file);
-
-        let file = Ext4FileWrapper::new(self.ext4.clone());
-        Ok(Arc::new(file))
+        let mut ext4file = Ext4File::new();
+        let _ = self.ext4.ext4_open(&mut ext4file, path, "w+", true);
+        Ok(self.sibling(ext4file))
     }
 
     /// Remove the node with the given `path` in the directory.
@@ -321,6 +376,69 @@ impl VfsNodeOps for Ext4FileWrapper {
         todo!()
     }
 
+    /// Add an entry for the inode of `src_path` at `dst_path`.
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let child = self.open_path(src_path)?.inode;
+        let mut child_ref = self.inode_ref(child);
+        if map_imode(child_ref.inner.inode.mode).0 == VfsNodeType::Dir {
+            return Err(VfsError::IsADirectory);
+        }
+        match self.open_path(dst_path) {
+            Ok(_) => return Err(VfsError::AlreadyExists),
+            Err(VfsError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+        let (parent, name) = self.parent_of(dst_path)?;
+        let mut parent_ref = self.inode_ref(parent);
+        let links = child_ref.inner.inode.links_count;
+        if self.ext4.ext4_link(&mut parent_ref, &mut child_ref, name, name.len() as u32) != 0 {
+            return Err(VfsError::IoError);
+        }
+        // set rather than count on the entry code to have counted it
+        child_ref.inner.inode.links_count = links + 1;
+        child_ref.write_back_inode();
+        Ok(())
+    }
+
+    /// Remove the entry at `path`, and the inode with its last link unless open.
+    fn unlink(&self, path: &str) -> VfsResult {
+        let child = self.open_path(path)?.inode;
+        let mut child_ref = self.inode_ref(child);
+        if map_imode(child_ref.inner.inode.mode).0 == VfsNodeType::Dir {
+            return Err(VfsError::IsADirectory);
+        }
+        let (parent, name) = self.parent_of(path)?;
+        let mut parent_ref = self.inode_ref(parent);
+        let links = child_ref.inner.inode.links_count.saturating_sub(1);
+        if self.ext4.ext4_unlink(&mut parent_ref, &mut child_ref, name, name.len() as u32) != 0 {
+            return Err(VfsError::IoError);
+        }
+        child_ref.inner.inode.links_count = links;
+        child_ref.write_back_inode();
+        if links == 0 {
+            let mut open_inodes = self.open_inodes.lock();
+            if open_inodes.counts.contains_key(&child) {
+                open_inodes.orphans.insert(child);
+            } else {
+                drop(open_inodes);
+                self.free_inode(child);
+            }
+        }
+        Ok(())
+    }
+
+    fn ino(&self) -> VfsResult<u64> {
+        Ok(self.ext4_file.lock().inode as u64)
+    }
+
+    fn is_dir(&self) -> VfsResult<bool> {
+        Ok(self.get_attr()?.is_dir())
+    }
+
+    fn is_file(&self) -> VfsResult<bool> {
+        Ok(self.get_attr()?.is_file())
+    }
+
     fn as_any(&self) -> &dyn core::any::Any {
         self as &dyn core::any::Any
     }
@@ -254,6 +254,20 @@ impl VfsNodeOps for DirWrapper<'static> {
         self.0.remove(path).map_err(as_vfs_err)
     }
 
+    fn link(&self, _src_path: &str, _dst_path: &str) -> DevResult {
+        // a FAT directory entry is the file itself, there is nothing to share
+        Err(VfsError::Unsupported)
+    }
+
+    fn unlink(&self, path: &str) -> DevResult {
+        debug!("unlink at fatfs: {}", path);
+        if self.lookup(path)?.is_dir()? {
+            return Err(VfsError::IsADirectory);
+        }
+        // without inodes the clusters go with the entry, even while open
+        self.remove(path)
+    }
+
     // fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> DevResult<usize> {
     //     let mut iter = self.0.iter().skip(start_idx);
     //     for (i, out_entry) in dirents.iter_mut().enumerate() {
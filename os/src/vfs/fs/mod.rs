@@ -17,14 +17,10 @@ use fat::FatFileSystem;
 use ext4::Ext4FileSystem;
 use super::err::{DevError, DevResult};
 use super::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
-use spin::Mutex;
 use crate::sync::LazyInit;
 
 use crate::{impl_vfs_dir_default, yy_err};
 
-static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
-static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
-
 struct MountPoint {
     path: &'static str,
     fs: Arc<dyn VfsOps>,
@@ -164,6 +160,26 @@ impl VfsNodeOps for RootDirectory {
             }
         })
     }
+
+    fn link(&self, src_path: &str, dst_path: &str) -> DevResult {
+        self.lookup_mounted_fs(src_path, |fs, rest_path| {
+            if rest_path.is_empty() {
+                yy_err!(PermissionDenied) // cannot link mount points
+            } else {
+                fs.root_dir().link(rest_path, dst_path)
+            }
+        })
+    }
+
+    fn unlink(&self, path: &str) -> DevResult {
+        self.lookup_mounted_fs(path, |fs, rest_path| {
+            if rest_path.is_empty() {
+                yy_err!(PermissionDenied) // cannot unlink mount points
+            } else {
+                fs.root_dir().unlink(rest_path)
+            }
+        })
+    }
 }
 
 pub fn init_rootfs(disk: &crate::vfs::Disk) {
@@ -186,15 +202,15 @@ pub fn init_rootfs(disk: &crate::vfs::Disk) {
     let root_dir = RootDirectory::new(main_fs);
 
     ROOT_DIR.init_by(Arc::new(root_dir));
-    CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
-    *CURRENT_DIR_PATH.lock() = "/".into();
 }
 
+/// The directory `path` starts from. The working directory belongs to the
+/// processes, so paths without a `dir` are taken from the root here
 fn parent_node_of(dir: Option<&VfsNodeRef>, path: &str) -> VfsNodeRef {
     if path.starts_with('/') {
         ROOT_DIR.clone()
     } else {
-        dir.cloned().unwrap_or_else(|| CURRENT_DIR.lock().clone())
+        dir.cloned().unwrap_or_else(|| ROOT_DIR.clone())
     }
 }
 
@@ -202,8 +218,7 @@ pub fn absolute_path(path: &str) -> DevResult<String> {
     if path.starts_with('/') {
         Ok(crate::vfs::paths::canonicalize(path))
     } else {
-        let path = CURRENT_DIR_PATH.lock().clone() + path;
-        Ok(crate::vfs::paths::canonicalize(&path))
+        Ok(crate::vfs::paths::canonicalize(&("/".to_string() + path)))
     }
 }
 
@@ -396,34 +411,6 @@ pub fn write_file_by_str(path: &str, offset: usize, data: &[u8]) -> DevResult<us
     write_file(&node, offset, data)
 }
 
-pub fn current_dir() -> DevResult<String> {
-    Ok(CURRENT_DIR_PATH.lock().clone())
-}
-
-pub fn set_current_dir(path: &str) -> DevResult {
-    let mut abs_path = absolute_path(path)?;
-    if !abs_path.ends_with('/') {
-        abs_path += "/";
-    }
-    if abs_path == "/" {
-        *CURRENT_DIR.lock() = ROOT_DIR.clone();
-        *CURRENT_DIR_PATH.lock() = "/".into();
-        return Ok(());
-    }
-
-    let node = lookup(None, &abs_path)?;
-    let attr = node.get_attr()?;
-    if !attr.is_dir() {
-        yy_err!(NotADirectory)
-    } else if !attr.perm().owner_executable() {
-        yy_err!(PermissionDenied)
-    } else {
-        *CURRENT_DIR.lock() = node;
-        *CURRENT_DIR_PATH.lock() = abs_path;
-        Ok(())
-    }
-}
-
 pub fn rename(old: &str, new: &str) -> DevResult {
     if parent_node_of(None, new).lookup(new).is_ok() {
         warn!("dst file already exist, now remove it");
@@ -477,11 +464,8 @@ pub fn fs_test() {
     assert_matches!(create_file_by_str("/", "/yes/no"), Ok(_));
     assert_matches!(create_dir_by_str("/", "/yes/yes"), Ok(()));
     assert_matches!(rename("/yes/no", "/yes/no2"), Ok(()));
-    println!("Current dir: {}", current_dir().unwrap());
-    assert_matches!(set_current_dir("/yes/yes"), Ok(()));
-    println!("Current dir changed to: {}", current_dir().unwrap());
-    assert_matches!(create_file_by_str("/", "no2"), Ok(_));
-    assert_matches!(remove_file_by_str("/", "no2"), Ok(()));
+    assert_matches!(create_file_by_str("/yes/yes", "no2"), Ok(_));
+    assert_matches!(remove_file_by_str("/yes/yes", "no2"), Ok(()));
     assert_matches!(remove_dir_by_str("/", "/yes/yes"), Ok(()));
     let bytes = b"Hello World in FAT32!\n";
     let bytes_len = bytes.len();
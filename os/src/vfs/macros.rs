@@ -50,6 +50,14 @@ macro_rules! impl_vfs_non_dir_default {
             $crate::yy_err!(NotADirectory)
         }
 
+        fn link(&self, _src_path: &str, _dst_path: &str) -> $crate::vfs::err::DevResult {
+            $crate::yy_err!(NotADirectory)
+        }
+
+        fn unlink(&self, _path: &str) -> $crate::vfs::err::DevResult {
+            $crate::yy_err!(NotADirectory)
+        }
+
         fn read_dir(
             &self
         ) -> Result<alloc::vec::Vec<VfsDirEntry>, crate::vfs::err::DevError> {
@@ -10,10 +10,12 @@ mod macros;
 use os::DISK_DEVICE;
 use os::Disk;
 use fs::init_rootfs;
-pub use os::{open_file, OpenFlags};
+pub use os::{is_dir, make_dir, open_file, remove_dir, OpenFlags};
 pub use os::{
-    link_file, Stat, make_pipe, Stdin, Stdout, File, unlink_file
+    link_file, Stat, make_pipe, Stdin, Stdout, File, unlink_file, CONSOLE, open_pty, PollEvents,
+    SeekFrom,
 };
+pub use os::{queue_write_back, read_mapped_page, sync_write_back};
 
 pub fn init_rootfs_on_disk() {
     init_rootfs(&DISK_DEVICE);
@@ -21,8 +23,8 @@ pub fn init_rootfs_on_disk() {
 
 use alloc::{sync::Arc, vec::Vec};
 pub use os::{BlockDevice, disk_device_test};
-use err::{DevError, DevResult};
-pub use paths::test_path_canonicalize;
+pub use err::{DevError, DevResult};
+pub use paths::{canonicalize, test_path_canonicalize};
 
 pub use fs::fs_test;
 
@@ -74,6 +76,9 @@ pub trait VfsNodeOps: Send + Sync {
     
     #[allow(unused)]
     /// Do something when the node is opened.
+    ///
+    /// Every open is paired with a [`release`](Self::release), a file
+    /// system may keep an unlinked node alive in between.
     fn open(&self) -> DevResult {
         Ok(())
     }
@@ -156,6 +161,17 @@ pub trait VfsNodeOps: Send + Sync {
         yy_err!(Unsupported)
     }
 
+    /// Create `dst_path` as another hard link to the file at `src_path`.
+    fn link(&self, _src_path: &str, _dst_path: &str) -> DevResult {
+        yy_err!(Unsupported)
+    }
+
+    /// Remove the directory entry of the file at `path`. The file goes with
+    /// its last link, or with the last release if it is open then.
+    fn unlink(&self, _path: &str) -> DevResult {
+        yy_err!(Unsupported)
+    }
+
     #[allow(unused)]
     /// Convert `&self` to [`&dyn Any`][1] that can use
     /// [`Any::downcast_ref`][2].
@@ -185,9 +201,4 @@ pub trait VfsNodeOps: Send + Sync {
     fn is_file(&self) -> DevResult<bool> {
         yy_err!(Unsupported)
     }
-
-    #[allow(unused)]
-    fn unlink(&self, _path: &str) -> DevResult {
-        yy_err!(Unsupported)
-    }
 }
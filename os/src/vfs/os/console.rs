@@ -1,7 +1,22 @@
-use crate::{mem::UserBuffer, process::suspend_current_and_run_next, sbi::{console_getchar, console_putchar}};
+use crate::driver::console::print_bytes;
+use crate::mem::UserBuffer;
+use crate::sync::WaitQueue;
+use crate::syscall::{Errno, SysResult};
 
-use super::{File, Stat};
+use super::tty::{Tty, TtyDevice};
+use super::{File, PollEvents, Stat};
 
+/// The screen behind the console terminal
+pub struct ConsoleDevice;
+
+impl TtyDevice for ConsoleDevice {
+    fn put(&self, bytes: &[u8]) {
+        print_bytes(bytes);
+    }
+}
+
+/// The console terminal, fed by the UART interrupt
+pub static CONSOLE: Tty<ConsoleDevice> = Tty::new(ConsoleDevice);
 
 /// stdin file for getting chars from console
 pub struct Stdin;
@@ -16,51 +31,24 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        // Read multiple chars from console
-        let mut read_size = 0;
-        for slice in user_buf.buffers.iter_mut() {
-            let mut i = 0;
-            while i < slice.len() {
-                let c = console_getchar();
-                if c == 0 {
-                    suspend_current_and_run_next();
-                    // EOF
-                    break;
-                } else if c == '\n' as usize || c == '\r' as usize {
-                    // newline
-                    console_putchar('\r' as usize);
-                    console_putchar('\n' as usize);
-                    break;
-                } else if c == '\x7f' as usize {
-                    if i > 0 {
-                        i -= 1;
-                        slice[i] = 0;
-                    }
-                    // delete char on left
-                    console_putchar('\x08' as usize);
-                    console_putchar(' ' as usize);
-                    console_putchar('\x08' as usize);
-                    continue;
-                } else {
-                    // echo
-                    console_putchar(c);
-                }
-                slice[i] = c as u8;
-                i += 1;
-            }
-            read_size += i;
-            // Print the slice as string
-            // println!("{}, read_size: {}", core::str::from_utf8(&slice[..i]).unwrap(), read_size);
-        }
-        read_size
-    }
-    fn write(&self, _user_buf: UserBuffer) -> usize {
-        panic!("Cannot write to stdin!");
+    fn read(&self, user_buf: UserBuffer) -> SysResult<usize> {
+        CONSOLE.read(user_buf)
+    }
+    fn write(&self, _user_buf: UserBuffer) -> SysResult<usize> {
+        Err(Errno::EBADF)
     }
     fn stat(&self) -> Option<Stat> {
         None
     }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        CONSOLE.ioctl(cmd, arg)
+    }
+    fn poll(&self) -> PollEvents {
+        CONSOLE.poll() - PollEvents::POLLOUT
+    }
+    fn poll_queue(&self) -> Option<&WaitQueue> {
+        Some(CONSOLE.poll_queue())
+    }
 }
 
 impl File for Stdout {
@@ -70,16 +58,20 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _user_buf: UserBuffer) -> usize {
-        panic!("Cannot read from stdout!");
+    fn read(&self, _user_buf: UserBuffer) -> SysResult<usize> {
+        Err(Errno::EBADF)
     }
-    fn write(&self, user_buf: UserBuffer) -> usize {
-        for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
-        }
-        user_buf.len()
+    fn write(&self, user_buf: UserBuffer) -> SysResult<usize> {
+        CONSOLE.write(user_buf)
     }
     fn stat(&self) -> Option<Stat> {
         None
     }
-}
\ No newline at end of file
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        CONSOLE.ioctl(cmd, arg)
+    }
+    /// the screen takes output right away
+    fn poll(&self) -> PollEvents {
+        PollEvents::POLLOUT
+    }
+}
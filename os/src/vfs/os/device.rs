@@ -1,7 +1,7 @@
 use core::any::Any;
 use core::cmp::min;
 use crate::driver::block::BLOCK_DEVICE;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinNoIrqLock;
 use crate::vfs::err::{DevResult, DevError};
 use alloc::sync::Arc;
 use lazy_static::*;
@@ -19,6 +19,8 @@ pub trait BlockDevice: Send + Sync + Any {
     fn block_count(&self) -> usize;
     /// Get the block size in bytes
     fn block_size(&self) -> usize;
+    /// Complete the requests the device has finished, called from its interrupt
+    fn handle_irq(&self) {}
 }
 
 #[derive(Clone)]
@@ -26,7 +28,7 @@ pub trait BlockDevice: Send + Sync + Any {
 pub struct Disk {
     // Vulnerability: mutable reference to a trait object
     dev: &'static dyn BlockDevice,
-    info: UPSafeCell<Info>
+    info: SpinNoIrqLock<Info>
 }
 
 #[derive(Clone)]
@@ -43,64 +45,62 @@ impl Disk {
     pub fn new(dev: &'static dyn BlockDevice) -> Self {
         Self {
             dev: dev,
-            info: unsafe {
-                UPSafeCell::new(Info {
-                    block_id: 0,
-                    offset: 0,
-                    block_size: dev.block_size(),
-                    block_count: dev.block_count()
-                })
-            }
+            info: SpinNoIrqLock::new(Info {
+                block_id: 0,
+                offset: 0,
+                block_size: dev.block_size(),
+                block_count: dev.block_count()
+            })
         }
     }
 
     /// Get the size of the disk.
     pub fn size(self: &Arc<Self>) -> u64 {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         (info.block_count * info.block_size) as u64
     }
 
     /// Get the size of the disk.
     pub fn size_self(&self) -> u64 {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         (info.block_count * info.block_size) as u64
     }
 
     /// Get the size of the disk.
     pub fn block_size(self: &Arc<Self>) -> u64 {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         info.block_size as u64
     }
 
     /// Get the position of the cursor.
     pub fn position(self: &Arc<Self>) -> u64 {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         info.block_id * info.block_size as u64 + info.offset as u64
     }
 
     /// Get the position of the cursor.
     pub fn position_self(&self) -> u64 {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         info.block_id * info.block_size as u64 + info.offset as u64
     }
 
     /// Set the position of the cursor.
     pub fn set_position(self: &Arc<Self>, pos: u64) {
-        let mut info = self.info.exclusive_access();
+        let mut info = self.info.lock();
         info.block_id = pos / info.block_size as u64;
         info.offset = pos as usize % info.block_size;
     }
 
     /// Set the position of the cursor.
     pub fn set_position_self(&self, pos: u64) {
-        let mut info = self.info.exclusive_access();
+        let mut info = self.info.lock();
         info.block_id = pos / info.block_size as u64;
         info.offset = pos as usize % info.block_size;
     }
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one(self: &mut Arc<Self>, buf: &mut [u8]) -> DevResult<usize> {
-        let mut info = self.info.exclusive_access();
+        let mut info = self.info.lock();
         let position = info.block_id * info.block_size as u64 + info.offset as u64;
         let size = (info.block_count * info.block_size) as u64;
         let buf = if position as usize + buf.len() >= size as usize {
@@ -142,7 +142,7 @@ impl Disk {
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one(self: &mut Arc<Self>, buf: &[u8]) -> DevResult<usize> {
-        let mut info = self.info.exclusive_access();
+        let mut info = self.info.lock();
         let write_size = if info.offset == 0 && buf.len() >= info.block_size {
             // whole block
             if !self.dev.write_block(info.block_id as usize, &buf[0..info.block_size]) {
@@ -176,7 +176,8 @@ impl Disk {
 
     /// Write within one block, returns the number of bytes written.
     pub fn write_one_self(&mut self, buf: &[u8]) -> DevResult<usize> {
-        let mut info = self.info.exclusive_access();
+        // no other reference exists, so the disk may sleep without a lock held
+        let info = self.info.get_mut();
         let write_size = if info.offset == 0 && buf.len() >= info.block_size {
             // whole block
             if !self.dev.write_block(info.block_id as usize, &buf[0..info.block_size]) {
@@ -210,7 +211,8 @@ impl Disk {
 
     /// Read within one block, returns the number of bytes read.
     pub fn read_one_self(&mut self, buf: &mut [u8]) -> DevResult<usize> {
-        let mut info = self.info.exclusive_access();
+        // no other reference exists, so the disk may sleep without a lock held
+        let info = self.info.get_mut();
         let position = info.block_id * info.block_size as u64 + info.offset as u64;
         let size = (info.block_count * info.block_size) as u64;
         let buf = if position as usize + buf.len() >= size as usize {
@@ -253,7 +255,7 @@ impl Disk {
     /// Read a single block starting from the specified offset.
     #[allow(unused)]
     pub fn read_offset(&mut self, offset: usize) -> Vec<u8> {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         let block_id = offset / info.block_size;
         let mut block_data = vec![0u8; info.block_size];
         assert!(self.dev
@@ -264,7 +266,7 @@ impl Disk {
     /// Write single block starting from the specified offset.
     #[allow(unused)]
     pub fn write_offset(&mut self, offset: usize, buf: &[u8]) -> DevResult<usize> {
-        let info = self.info.exclusive_access();
+        let info = self.info.lock();
         assert!(
             buf.len() == info.block_size,
             "Buffer length must be equal to BLOCK_SIZE"
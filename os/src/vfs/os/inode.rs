@@ -1,8 +1,10 @@
-use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
-use crate::mem::UserBuffer;
+use crate::config::PAGE_SIZE;
+use crate::mem::{FrameTracker, UserBuffer};
+use crate::syscall::{Errno, SysResult};
 
 #[allow(dead_code)]
 /// trait File for all file types
@@ -12,11 +14,91 @@ pub trait File: Send + Sync {
     /// the file writable?
     fn writable(&self) -> bool;
     /// read from the file to buf, return the number of bytes read
-    fn read(&self, buf: UserBuffer) -> usize;
+    fn read(&self, buf: UserBuffer) -> SysResult<usize>;
     /// write to the file from buf, return the number of bytes written
-    fn write(&self, buf: UserBuffer) -> usize;
+    fn write(&self, buf: UserBuffer) -> SysResult<usize>;
     /// stat of file
     fn stat(&self) -> Option<Stat>;
+    /// the vfs node behind the file, for mapping it into memory
+    fn node(&self) -> Option<VfsNodeRef> {
+        None
+    }
+    /// read from the given offset without moving the file offset
+    fn pread(&self, _offset: usize, _buf: UserBuffer) -> SysResult<usize> {
+        Err(Errno::ESPIPE)
+    }
+    /// write at the given offset without moving the file offset
+    fn pwrite(&self, _offset: usize, _buf: UserBuffer) -> SysResult<usize> {
+        Err(Errno::ESPIPE)
+    }
+    /// move the file offset, return the new one
+    fn seek(&self, _pos: SeekFrom) -> SysResult<usize> {
+        Err(Errno::ESPIPE)
+    }
+    /// read `linux_dirent64` records of a directory into buf, resuming where
+    /// the last call stopped, return the number of bytes filled
+    fn getdents(&self, _buf: UserBuffer) -> SysResult<usize> {
+        Err(Errno::ENOTDIR)
+    }
+    /// the absolute path the file was opened by, for the *at syscalls
+    fn path(&self) -> Option<&str> {
+        None
+    }
+    /// device specific control, only terminals understand any command so far
+    fn ioctl(&self, _cmd: usize, _arg: usize) -> SysResult {
+        Err(Errno::ENOTTY)
+    }
+    /// the status flags of the open file (APPEND, NONBLOCK) for F_GETFL
+    fn status_flags(&self) -> OpenFlags {
+        OpenFlags::empty()
+    }
+    /// replace the status flags F_SETFL may change, files that keep none ignore them
+    fn set_status_flags(&self, _flags: OpenFlags) {}
+    /// file specific fcntl commands, only pipes understand any so far
+    fn fcntl(&self, _cmd: usize, _arg: usize) -> SysResult {
+        Err(Errno::EINVAL)
+    }
+    /// the events the file is ready for right now, regular files never block
+    fn poll(&self) -> PollEvents {
+        PollEvents::POLLIN | PollEvents::POLLOUT
+    }
+    /// the queue notified whenever [`File::poll`] may change, None if it never does
+    fn poll_queue(&self) -> Option<&WaitQueue> {
+        None
+    }
+}
+
+/// Where [`File::seek`] moves the offset to, the whence of lseek
+#[derive(Copy, Clone, Debug)]
+pub enum SeekFrom {
+    /// SEEK_SET: to the given offset
+    Start(usize),
+    /// SEEK_CUR: relative to the current offset
+    Current(isize),
+    /// SEEK_END: relative to the end of the file
+    End(isize),
+    /// SEEK_DATA: to the first data at or after the given offset
+    Data(usize),
+    /// SEEK_HOLE: to the first hole at or after the given offset
+    Hole(usize),
+}
+
+bitflags! {
+    /// Readiness of a file, as in the Linux `struct pollfd`
+    pub struct PollEvents: u16 {
+        /// there is data to read
+        const POLLIN   = 0x1;
+        /// there is urgent data to read
+        const POLLPRI  = 0x2;
+        /// writing would not block
+        const POLLOUT  = 0x4;
+        /// error condition, always reported
+        const POLLERR  = 0x8;
+        /// the other end is gone, always reported
+        const POLLHUP  = 0x10;
+        /// the descriptor is not open, always reported
+        const POLLNVAL = 0x20;
+    }
 }
 
 
@@ -49,10 +131,10 @@ bitflags! {
     }
 }
 
-use crate::sync::UPSafeCell;
+use crate::sync::{Mutex, MutexBlocking, SpinNoIrqLock, WaitQueue};
 use crate::vfs::fs::ROOT_DIR;
 
-use crate::vfs::VfsNodeRef;
+use crate::vfs::{DevError, DevResult, VfsNodeRef};
 
 use super::structs::VfsNodeType;
 
@@ -63,33 +145,43 @@ use super::structs::VfsNodeType;
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    /// the absolute path the inode was opened by
+    path: String,
+    inner: SpinNoIrqLock<OSInodeInner>,
 }
 
 #[allow(dead_code)]
-/// The OS inode inner in 'UPSafeCell'
+/// The OS inode inner in 'SpinNoIrqLock'
  pub struct OSInodeInner {
     offset: usize,
+    /// APPEND and NONBLOCK, see [`OpenFlags::STATUS`]
+    status: OpenFlags,
     inode: Arc<VfsNodeRef>,
 }
 
 #[allow(dead_code)]
 impl OSInode {
     /// create a new inode in memory
-    pub fn new(readable: bool, writable: bool, inode: Arc<VfsNodeRef>) -> Self {
+    pub fn new(readable: bool, writable: bool, inode: Arc<VfsNodeRef>, path: String) -> Self {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            path,
+            inner: SpinNoIrqLock::new(OSInodeInner {
+                offset: 0,
+                status: OpenFlags::empty(),
+                inode,
+            }),
         }
     }
     /// read all data from the inode
     pub fn read_all(&self) -> Vec<u8> {
-        let inner = self.inner.exclusive_access();
+        let _fs = lock_fs();
+        let (inode, _) = self.cursor();
         let mut buffer = [0u8; 512];
         let mut offset = 0;
         let mut v: Vec<u8> = Vec::new();
-        while let Ok(size) = inner.inode.read_at(offset, &mut buffer) {
+        while let Ok(size) = inode.read_at(offset, &mut buffer) {
             offset += size as u64;
             v.extend_from_slice(&buffer[..size]);
             if size < buffer.len() {
@@ -99,16 +191,48 @@ impl OSInode {
         v
     }
 
-    /// check if the inode is flag deleted
-    pub fn is_deleted(&self, _name: &str) -> bool {
-        // self.inner.exclusive_access().inode.is_removed(name)
-        false
+    /// Read from `offset` of `inode` into `buf` until it is full or the file ends
+    fn read_inode(inode: &VfsNodeRef, mut offset: usize, mut buf: UserBuffer) -> SysResult<usize> {
+        if inode.is_dir()? {
+            return Err(Errno::EISDIR);
+        }
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inode.read_at(offset as u64, slice).unwrap_or(0);
+            if read_size == 0 {
+                break;
+            }
+            offset += read_size;
+            total_read_size += read_size;
+        }
+        Ok(total_read_size)
     }
 
-    /// check if the inode is a link
-    pub fn is_link(&self) -> bool {
-        // self.inner.exclusive_access().inode.is_link()
-        false
+    /// Write all of `buf` to `inode` at `offset`, less if the disk is full
+    fn write_inode(inode: &VfsNodeRef, mut offset: usize, buf: UserBuffer) -> SysResult<usize> {
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let write_size = inode.write_at(offset as u64, slice)?;
+            offset += write_size;
+            total_write_size += write_size;
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        Ok(total_write_size)
+    }
+
+    /// The inode and the offset, copied out so that the disk may sleep
+    /// without the inner lock held. `FS_LOCK` keeps the offset ours meanwhile
+    fn cursor(&self) -> (Arc<VfsNodeRef>, usize) {
+        let inner = self.inner.lock();
+        (Arc::clone(&inner.inode), inner.offset)
+    }
+}
+
+impl Drop for OSInode {
+    fn drop(&mut self) {
+        RELEASED.lock().push(Arc::clone(&self.inner.lock().inode));
     }
 }
 
@@ -122,58 +246,195 @@ bitflags! {
         /// read and write
         const RDWR = 1 << 1;
         /// create new file
-        const CREATE = 1 << 9;
+        const CREATE = 0o100;
+        /// with CREATE, fail if the file exists
+        const EXCL = 0o200;
         /// truncate file size to 0
-        const TRUNC = 1 << 10;
+        const TRUNC = 0o1000;
+        /// every write goes to the end of the file
+        const APPEND = 0o2000;
+        /// fail with EAGAIN instead of blocking, pipes only
+        const NONBLOCK = 0o4000;
+        /// fail unless the path names a directory
+        const DIRECTORY = 0o200000;
+        /// close the descriptor on execve
+        const CLOEXEC = 0o2000000;
     }
 }
 
 #[allow(dead_code)]
 impl OpenFlags {
+    /// The flags F_SETFL may change
+    pub const STATUS: Self = Self::from_bits_truncate(Self::APPEND.bits | Self::NONBLOCK.bits);
+
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
+        if self.contains(Self::RDWR) {
+            (true, true)
         } else if self.contains(Self::WRONLY) {
             (false, true)
         } else {
-            (true, true)
+            (true, false)
+        }
+    }
+    /// The access mode of a file that is `readable` and `writable`
+    pub fn access_mode(readable: bool, writable: bool) -> Self {
+        match (readable, writable) {
+            (true, true) => Self::RDWR,
+            (false, true) => Self::WRONLY,
+            _ => Self::RDONLY,
         }
     }
 }
 
+/// `d_ino`, `d_off`, `d_reclen` and `d_type` of a `linux_dirent64`, the name follows
+const DIRENT64_HEADER_SIZE: usize = 19;
+
 lazy_static! {
     static ref ROOT_INODE: Arc<VfsNodeRef> = Arc::new(ROOT_DIR.as_ref().main_fs.root_dir());
 }
 
+lazy_static! {
+    /// Serializes file system operations between tasks: the file systems below
+    /// keep their state in cells which are not safe to share. Holders sleep on
+    /// the disk, so it is a blocking mutex rather than a spinlock
+    static ref FS_LOCK: MutexBlocking = MutexBlocking::new();
+}
+
+/// Holds `FS_LOCK` until dropped
+struct FsGuard;
+
+impl Drop for FsGuard {
+    fn drop(&mut self) {
+        FS_LOCK.unlock();
+    }
+}
+
+fn lock_fs() -> FsGuard {
+    FS_LOCK.lock();
+    // pages go back before their nodes may be released
+    let writes = core::mem::take(&mut *WRITE_BACK.lock());
+    for (node, offset, frame) in writes {
+        write_mapped_page(&node, offset, &frame);
+    }
+    let released = core::mem::take(&mut *RELEASED.lock());
+    for inode in released {
+        inode.release().ok();
+    }
+    FsGuard
+}
+
+lazy_static! {
+    /// Nodes of closed files, waiting to be released. The last reference to
+    /// a file may go with spinlocks held, where `FS_LOCK` cannot be taken,
+    /// so the release is left to the next holder
+    static ref RELEASED: SpinNoIrqLock<Vec<Arc<VfsNodeRef>>> = SpinNoIrqLock::new(Vec::new());
+    /// Dirty pages of shared file mappings with the node and offset they
+    /// belong to. They are unmapped with the address space locked, so like
+    /// the releases the writes are left to the next holder of `FS_LOCK`
+    static ref WRITE_BACK: SpinNoIrqLock<Vec<(VfsNodeRef, usize, Arc<FrameTracker>)>> =
+        SpinNoIrqLock::new(Vec::new());
+}
+
+/// Read the page of a file mapping at `offset` of `node` into `frame`, the
+/// part past the end of the file stays zeroed
+pub fn read_mapped_page(node: &VfsNodeRef, offset: usize, frame: &FrameTracker) {
+    let _fs = lock_fs();
+    let bytes = frame.ppn.get_bytes_array();
+    let mut read = 0;
+    while read < PAGE_SIZE {
+        match node.read_at((offset + read) as u64, &mut bytes[read..]) {
+            Ok(size) if size > 0 => read += size,
+            _ => break,
+        }
+    }
+}
+
+/// Write a page of a file mapping back to `node` at `offset`, without
+/// growing the file
+fn write_mapped_page(node: &VfsNodeRef, offset: usize, frame: &FrameTracker) {
+    let size = node.get_attr().map_or(0, |attr| attr.size() as usize);
+    if offset >= size {
+        return;
+    }
+    let len = PAGE_SIZE.min(size - offset);
+    if node.write_at(offset as u64, &frame.ppn.get_bytes_array()[..len]).is_err() {
+        warn!("mmap: failed to write back a page at offset {:#x}", offset);
+    }
+}
+
+/// Queue the page `frame` of a shared file mapping to be written back to
+/// `node` at `offset`, see [`sync_write_back`]
+pub fn queue_write_back(node: VfsNodeRef, offset: usize, frame: Arc<FrameTracker>) {
+    WRITE_BACK.lock().push((node, offset, frame));
+}
+
+/// Write the queued pages of shared file mappings back now, rather than
+/// with the next file system operation
+pub fn sync_write_back() {
+    drop(lock_fs());
+}
+
 #[allow(unused)]
-/// Open a file
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+/// Open a file by its absolute path
+pub fn open_file(name: &str, flags: OpenFlags) -> DevResult<Arc<OSInode>> {
+    let _fs = lock_fs();
     let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Ok(inode) = ROOT_INODE.lookup(name) {
-            // clear size
-            inode.clear().unwrap();
-            Some(Arc::new(OSInode::new(readable, writable, inode.into())))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name, VfsNodeType::File)
-                .map_or(None, |inode| Some(Arc::new(OSInode::new(readable, writable, inode.into()))))
+    let inode = match ROOT_INODE.lookup(name) {
+        Ok(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) => {
+            return crate::yy_err!(AlreadyExists);
         }
-    } else {
-        let ll = ROOT_INODE.lookup(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear().unwrap();
+        Ok(inode) => {
+            if inode.is_dir()? {
+                // directories are only read through getdents64
+                if writable {
+                    return crate::yy_err!(IsADirectory);
+                }
+            } else if flags.contains(OpenFlags::DIRECTORY) {
+                return crate::yy_err!(NotADirectory);
+            } else if flags.contains(OpenFlags::TRUNC) && writable {
+                // clear size
+                inode.clear()?;
             }
-            Arc::new(OSInode::new(readable, writable, inode.clone().into()))
-        });
-        match ll {
-            Ok(inode) => Some(inode),
-            _ => None,
+            inode
+        }
+        Err(DevError::NotFound) if flags.contains(OpenFlags::CREATE) => {
+            // create file
+            ROOT_INODE.create(name, VfsNodeType::File)?
         }
+        Err(err) => return Err(err),
+    };
+    // paired with the release when the OSInode goes
+    inode.open()?;
+    let file = OSInode::new(readable, writable, inode.into(), name.into());
+    file.set_status_flags(flags);
+    Ok(Arc::new(file))
+}
+
+/// Whether the node at the absolute `path` is a directory
+pub fn is_dir(path: &str) -> DevResult<bool> {
+    let _fs = lock_fs();
+    ROOT_INODE.lookup(path)?.is_dir()
+}
+
+/// Create a directory at the absolute `path`
+pub fn make_dir(path: &str) -> DevResult {
+    let _fs = lock_fs();
+    if ROOT_INODE.lookup(path).is_ok() {
+        return crate::yy_err!(AlreadyExists);
     }
+    ROOT_INODE.create(path, VfsNodeType::Dir)?;
+    Ok(())
+}
+
+/// Remove the directory at the absolute `path`
+pub fn remove_dir(path: &str) -> DevResult {
+    let _fs = lock_fs();
+    if !ROOT_INODE.lookup(path)?.is_dir()? {
+        return crate::yy_err!(NotADirectory);
+    }
+    ROOT_INODE.remove(path)
 }
 
 impl File for OSInode {
@@ -183,109 +444,144 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut total_read_size = 0usize;
-        for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset as u64, *slice).unwrap_or(0);
-            if read_size == 0 {
+    fn read(&self, buf: UserBuffer) -> SysResult<usize> {
+        let _fs = lock_fs();
+        let (inode, offset) = self.cursor();
+        let read_size = Self::read_inode(&inode, offset, buf)?;
+        self.inner.lock().offset = offset + read_size;
+        Ok(read_size)
+    }
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        let _fs = lock_fs();
+        let (inode, mut offset) = self.cursor();
+        if self.status_flags().contains(OpenFlags::APPEND) {
+            // the file lock keeps others from writing between the two
+            offset = inode.get_attr()?.size() as usize;
+        }
+        let write_size = Self::write_inode(&inode, offset, buf)?;
+        self.inner.lock().offset = offset + write_size;
+        Ok(write_size)
+    }
+    fn status_flags(&self) -> OpenFlags {
+        self.inner.lock().status
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        self.inner.lock().status = flags & OpenFlags::STATUS;
+    }
+    fn pread(&self, offset: usize, buf: UserBuffer) -> SysResult<usize> {
+        let _fs = lock_fs();
+        let (inode, _) = self.cursor();
+        Self::read_inode(&inode, offset, buf)
+    }
+    fn pwrite(&self, offset: usize, buf: UserBuffer) -> SysResult<usize> {
+        let _fs = lock_fs();
+        let (inode, _) = self.cursor();
+        Self::write_inode(&inode, offset, buf)
+    }
+    /// The offset of a directory counts entries, so only absolute and relative
+    /// seeks make sense there
+    fn seek(&self, pos: SeekFrom) -> SysResult<usize> {
+        let _fs = lock_fs();
+        let (inode, offset) = self.cursor();
+        let is_dir = inode.is_dir()?;
+        let size = inode.get_attr()?.size() as usize;
+        let new_offset = match pos {
+            SeekFrom::Start(start) => Some(start),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+            SeekFrom::End(_) | SeekFrom::Data(_) | SeekFrom::Hole(_) if is_dir => None,
+            SeekFrom::End(delta) => size.checked_add_signed(delta),
+            // without holes, all of the file is data and its end is the only hole
+            SeekFrom::Data(start) if start < size => Some(start),
+            SeekFrom::Hole(start) if start < size => Some(size),
+            SeekFrom::Data(_) | SeekFrom::Hole(_) => return Err(Errno::ENXIO),
+        };
+        let new_offset = new_offset.ok_or(Errno::EINVAL)?;
+        self.inner.lock().offset = new_offset;
+        Ok(new_offset)
+    }
+    fn node(&self) -> Option<VfsNodeRef> {
+        Some(VfsNodeRef::clone(&self.inner.lock().inode))
+    }
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+    /// The offset of a directory counts entries rather than bytes
+    fn getdents(&self, mut buf: UserBuffer) -> SysResult<usize> {
+        let _fs = lock_fs();
+        let (inode, offset) = self.cursor();
+        if !inode.is_dir()? {
+            return Err(Errno::ENOTDIR);
+        }
+        let entries = inode.read_dir()?;
+        let mut records = Vec::new();
+        let mut next = offset;
+        for entry in entries.iter().skip(offset) {
+            let name = entry.name_as_bytes();
+            let start = records.len();
+            let reclen = (DIRENT64_HEADER_SIZE + name.len() + 1).next_multiple_of(8);
+            if start + reclen > buf.len() {
                 break;
             }
-            inner.offset += read_size;
-            total_read_size += read_size;
+            next += 1;
+            // entries do not carry their inode number, and 0 would hide them
+            let ino = inode
+                .lookup(entry.name_as_str())
+                .and_then(|node| node.ino())
+                .unwrap_or(next as u64);
+            records.extend_from_slice(&ino.to_ne_bytes());
+            records.extend_from_slice(&(next as i64).to_ne_bytes());
+            records.extend_from_slice(&(reclen as u16).to_ne_bytes());
+            records.push(entry.entry_type() as u8);
+            records.extend_from_slice(name);
+            records.resize(start + reclen, 0);
         }
-        total_read_size
-    }
-    fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let mut total_write_size = 0usize;
-        for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset as u64, *slice).unwrap_or(0);
-            assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
-            total_write_size += write_size;
+        if records.is_empty() && next < entries.len() {
+            // not even one record fits
+            return Err(Errno::EINVAL);
+        }
+        let mut src = records.as_slice();
+        for slice in buf.buffers.iter_mut() {
+            let n = slice.len().min(src.len());
+            slice[..n].copy_from_slice(&src[..n]);
+            src = &src[n..];
         }
-        total_write_size
+        self.inner.lock().offset = next;
+        Ok(records.len())
     }
     fn stat(&self) -> Option<Stat> {
-        let inner = self.inner.exclusive_access();
+        let _fs = lock_fs();
+        let (inode, _) = self.cursor();
 
         Some(Stat {
             dev: 0,
-            ino: inner.inode.ino().unwrap().into(),
+            ino: inode.ino().unwrap_or(0),
             mode: {
-                match inner.inode.is_dir() {
+                match inode.is_dir() {
                     Ok(true) => StatMode::DIR,
                     Ok(false) => StatMode::FILE,
                     Err(_) => StatMode::NULL,
                 }
             },
-            nlink: {
-                let map = INODE_LINK_MAP.exclusive_access();
-                let inner_inode = inner.inode.ino();
-                let count = map
-                    .get(&inner_inode.unwrap().into())
-                    .cloned()
-                    .unwrap_or(1);
-
-                count as u32
-            },
+            nlink: inode.get_attr().map_or(1, |attr| attr.nlink() as u32),
             pad: [0; 7],
         })
     }
 }
 
-lazy_static! {
-    pub static ref INODE_LINK_MAP: UPSafeCell<BTreeMap<u64, u64>> = {
-        let map = BTreeMap::new();
-        unsafe { UPSafeCell::new(map) }
-    };
-}
-
-#[allow(unused)]
-/// link two files
-pub fn link_file(old_name: &str, new_name: &str) -> isize {
-    if old_name == new_name {
-        return -1;
-    }
-
-    if let Ok(old_inode) = ROOT_INODE.lookup(old_name) {
-        if let Ok(new_inode) = ROOT_INODE.lookup(new_name) {
-            // increments link count
-            let mut inner = INODE_LINK_MAP.exclusive_access();
-            let key = new_inode.ino().unwrap();
-            let old_inode = old_inode.ino().unwrap();
-            let old_count = inner.get(&key).cloned().unwrap_or(1);
-            inner.insert(old_inode, old_count + 1);
-            0
-        } else {
-            -1
-        }
-    } else {
-        -1
+/// Make `new_path` another hard link to the file at `old_path`, both absolute
+pub fn link_file(old_path: &str, new_path: &str) -> DevResult {
+    let _fs = lock_fs();
+    if ROOT_INODE.lookup(old_path)?.is_dir()? {
+        return crate::yy_err!(IsADirectory);
     }
-}
-
-#[allow(unused)]
-/// unlink a file
-pub fn unlink_file(file_name: &str) -> isize {
-    if let Ok(inode) = ROOT_INODE.lookup(file_name) {
-        // flag in remove
-        inode.unlink(file_name);
-
-        // decrease link count
-        let inode_num = inode.ino().unwrap();
-        let mut inner = INODE_LINK_MAP.exclusive_access();
-        let old_count = inner.get(&inode_num).cloned().unwrap_or(1);
-        inner.insert(inode_num, old_count - 1);
-
-        if old_count == 0 {
-            inner.remove(&inode_num);
-        }
-
-        0
-    } else {
-        -1
+    if ROOT_INODE.lookup(new_path).is_ok() {
+        return crate::yy_err!(AlreadyExists);
     }
+    ROOT_INODE.link(old_path, new_path)
 }
 
+/// Remove the directory entry of the file at the absolute `path`
+pub fn unlink_file(path: &str) -> DevResult {
+    let _fs = lock_fs();
+    ROOT_INODE.unlink(path)
+}
@@ -3,11 +3,17 @@ mod inode;
 mod pipe;
 mod structs;
 mod console;
+mod tty;
+mod pty;
 
 pub use device::{BlockDevice, disk_device_test, DISK_DEVICE, Disk};
 pub use structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
-pub use inode::{open_file, OpenFlags, Stat};
+pub use inode::{is_dir, make_dir, open_file, remove_dir, OpenFlags, Stat};
 pub use inode::{link_file, unlink_file};
+pub use inode::{queue_write_back, read_mapped_page, sync_write_back};
 pub use pipe::make_pipe;
-pub use inode::File;
-pub use console::{Stdin, Stdout};
+pub use inode::{File, PollEvents, SeekFrom};
+pub use console::{Stdin, Stdout, CONSOLE};
+pub use pty::open_pty;
+#[allow(unused)]
+pub use tty::{Termios, Tty, TtyDevice, WinSize};
@@ -1,111 +1,140 @@
-use super::inode::File;
+use super::inode::{File, OpenFlags, PollEvents};
+use crate::config::PAGE_SIZE;
 use crate::mem::UserBuffer;
-use crate::sync::UPSafeCell;
+use crate::process::{current_add_signal, current_signal_pending, SignalFlags};
+use crate::sync::{SpinNoIrqLock, WaitQueue};
+use crate::syscall::{Errno, SysResult};
+use alloc::collections::VecDeque;
 use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use crate::process::suspend_current_and_run_next;
+/// set the capacity of a pipe
+pub const F_SETPIPE_SZ: usize = 1031;
+/// get the capacity of a pipe
+pub const F_GETPIPE_SZ: usize = 1032;
+
+/// The capacity of a new pipe
+const PIPE_DEFAULT_SIZE: usize = PAGE_SIZE;
+/// F_SETPIPE_SZ refuses capacities beyond this, like Linux' pipe-max-size
+const PIPE_MAX_SIZE: usize = 1024 * 1024;
 
 /// IPC pipe
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    /// fail with EAGAIN instead of waiting, O_NONBLOCK of this end
+    nonblock: AtomicBool,
+    buffer: Arc<PipeBuffer>,
 }
 
 impl Pipe {
     /// create readable pipe
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn read_end_with_buffer(buffer: Arc<PipeBuffer>, nonblock: bool) -> Self {
         Self {
             readable: true,
             writable: false,
+            nonblock: AtomicBool::new(nonblock),
             buffer,
         }
     }
     /// create writable pipe
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn write_end_with_buffer(buffer: Arc<PipeBuffer>, nonblock: bool) -> Self {
         Self {
             readable: false,
             writable: true,
+            nonblock: AtomicBool::new(nonblock),
             buffer,
         }
     }
 }
 
-const RING_BUFFER_SIZE: usize = 32;
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // the other end sees end of file or EPIPE now
+        if self.readable {
+            self.buffer.writers.notify_all();
+        }
+        if self.writable {
+            self.buffer.readers.notify_all();
+        }
+    }
+}
 
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    Full,
-    Empty,
-    Normal,
+/// What the two ends of a pipe share
+pub struct PipeBuffer {
+    ring: SpinNoIrqLock<PipeRingBuffer>,
+    /// readers waiting for data or for the write end to close
+    readers: WaitQueue,
+    /// writers waiting for room or for the read end to close
+    writers: WaitQueue,
 }
 
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
-    head: usize,
-    tail: usize,
-    status: RingBufferStatus,
+    data: VecDeque<u8>,
+    capacity: usize,
+    read_end: Option<Weak<Pipe>>,
     write_end: Option<Weak<Pipe>>,
 }
 
 impl PipeRingBuffer {
     pub fn new() -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
-            head: 0,
-            tail: 0,
-            status: RingBufferStatus::Empty,
+            data: VecDeque::with_capacity(PIPE_DEFAULT_SIZE),
+            capacity: PIPE_DEFAULT_SIZE,
+            read_end: None,
             write_end: None,
         }
     }
+    pub fn set_read_end(&mut self, read_end: &Arc<Pipe>) {
+        self.read_end = Some(Arc::downgrade(read_end));
+    }
     pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
         self.write_end = Some(Arc::downgrade(write_end));
     }
-    pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::Normal;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::Full;
-        }
-    }
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::Normal;
-        let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::Empty;
-        }
-        c
-    }
     pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::Empty {
-            0
-        } else if self.tail > self.head {
-            self.tail - self.head
-        } else {
-            self.tail + RING_BUFFER_SIZE - self.head
-        }
+        self.data.len()
     }
     pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::Full {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
-        }
+        self.capacity - self.data.len()
+    }
+    pub fn all_read_ends_closed(&self) -> bool {
+        self.read_end.as_ref().unwrap().upgrade().is_none()
     }
     pub fn all_write_ends_closed(&self) -> bool {
         self.write_end.as_ref().unwrap().upgrade().is_none()
     }
+    /// Move as much as there is into `buf`, return how much that was
+    fn read_into(&mut self, buf: &mut UserBuffer) -> usize {
+        let mut already_read = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let n = slice.len().min(self.data.len());
+            for (dst, src) in slice.iter_mut().zip(self.data.drain(..n)) {
+                *dst = src;
+            }
+            already_read += n;
+            if n < slice.len() {
+                break;
+            }
+        }
+        already_read
+    }
 }
 
 /// Return (read_end, write_end)
-pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+pub fn make_pipe(flags: OpenFlags) -> (Arc<Pipe>, Arc<Pipe>) {
     trace!("kernel: make_pipe");
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
-    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
+    let nonblock = flags.contains(OpenFlags::NONBLOCK);
+    let buffer = Arc::new(PipeBuffer {
+        ring: SpinNoIrqLock::new(PipeRingBuffer::new()),
+        readers: WaitQueue::new(),
+        writers: WaitQueue::new(),
+    });
+    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone(), nonblock));
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone(), nonblock));
+    let mut ring = buffer.ring.lock();
+    ring.set_read_end(&read_end);
+    ring.set_write_end(&write_end);
+    drop(ring);
     (read_end, write_end)
 }
 
@@ -116,67 +145,125 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: UserBuffer) -> usize {
+    /// Read what is in the pipe, waiting for a writer while it is empty
+    fn read(&self, mut buf: UserBuffer) -> SysResult<usize> {
         trace!("kernel: Pipe::read");
         assert!(self.readable());
-        let want_to_read = buf.len();
-        let mut buf_iter = buf.into_iter();
-        let mut already_read = 0usize;
+        if buf.len() == 0 {
+            return Ok(0);
+        }
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
-            let loop_read = ring_buffer.available_read();
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return already_read;
-                }
+            let mut ring_buffer = self.buffer.ring.lock();
+            if ring_buffer.available_read() > 0 {
+                let already_read = ring_buffer.read_into(&mut buf);
                 drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
+                self.buffer.writers.notify_all();
+                return Ok(already_read);
             }
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    unsafe {
-                        *byte_ref = ring_buffer.read_byte();
-                    }
-                    already_read += 1;
-                    if already_read == want_to_read {
-                        return want_to_read;
-                    }
-                } else {
-                    return already_read;
-                }
+            if ring_buffer.all_write_ends_closed() {
+                return Ok(0);
+            }
+            if self.nonblock.load(Ordering::Relaxed) {
+                return Err(Errno::EAGAIN);
             }
+            if current_signal_pending() {
+                return Err(Errno::EINTR);
+            }
+            self.buffer.readers.wait_unlock_interruptible(ring_buffer);
         }
     }
-    fn write(&self, buf: UserBuffer) -> usize {
+    /// Write all of `buf`, waiting for a reader whenever the pipe is full.
+    /// Without readers this raises SIGPIPE and fails with EPIPE.
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
         trace!("kernel: Pipe::write");
         assert!(self.writable());
         let want_to_write = buf.len();
-        let mut buf_iter = buf.into_iter();
+        let mut bytes = buf.buffers.iter().flat_map(|slice| slice.iter().copied());
         let mut already_write = 0usize;
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
-            let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
+            let mut ring_buffer = self.buffer.ring.lock();
+            let error = if ring_buffer.all_read_ends_closed() {
                 drop(ring_buffer);
-                suspend_current_and_run_next();
-                continue;
-            }
-            // write at most loop_write bytes
-            for _ in 0..loop_write {
-                if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte(unsafe { *byte_ref });
-                    already_write += 1;
-                    if already_write == want_to_write {
-                        return want_to_write;
-                    }
-                } else {
-                    return already_write;
+                current_add_signal(SignalFlags::SIGPIPE);
+                Errno::EPIPE
+            } else if ring_buffer.available_write() > 0 {
+                let n = ring_buffer.available_write().min(want_to_write - already_write);
+                ring_buffer.data.extend(bytes.by_ref().take(n));
+                already_write += n;
+                drop(ring_buffer);
+                self.buffer.readers.notify_all();
+                if already_write == want_to_write {
+                    return Ok(want_to_write);
                 }
-            }
+                continue;
+            } else if self.nonblock.load(Ordering::Relaxed) {
+                Errno::EAGAIN
+            } else if current_signal_pending() {
+                Errno::EINTR
+            } else {
+                self.buffer.writers.wait_unlock_interruptible(ring_buffer);
+                continue;
+            };
+            // a partial write still counts
+            return if already_write > 0 { Ok(already_write) } else { Err(error) };
         }
     }
     fn stat(&self) -> Option<super::inode::Stat> {
         None
     }
+    fn status_flags(&self) -> OpenFlags {
+        if self.nonblock.load(Ordering::Relaxed) {
+            OpenFlags::NONBLOCK
+        } else {
+            OpenFlags::empty()
+        }
+    }
+    fn set_status_flags(&self, flags: OpenFlags) {
+        self.nonblock.store(flags.contains(OpenFlags::NONBLOCK), Ordering::Relaxed);
+    }
+    fn fcntl(&self, cmd: usize, arg: usize) -> SysResult {
+        let mut ring_buffer = self.buffer.ring.lock();
+        match cmd {
+            F_GETPIPE_SZ => Ok(ring_buffer.capacity as isize),
+            F_SETPIPE_SZ => {
+                // whole pages, a power of two of them as on Linux
+                let size = arg.max(PAGE_SIZE).checked_next_power_of_two().ok_or(Errno::EPERM)?;
+                if size > PIPE_MAX_SIZE {
+                    return Err(Errno::EPERM);
+                }
+                if size < ring_buffer.available_read() {
+                    return Err(Errno::EBUSY);
+                }
+                ring_buffer.capacity = size;
+                drop(ring_buffer);
+                self.buffer.writers.notify_all();
+                Ok(size as isize)
+            }
+            _ => Err(Errno::EINVAL),
+        }
+    }
+    fn poll(&self) -> PollEvents {
+        let ring_buffer = self.buffer.ring.lock();
+        let mut events = PollEvents::empty();
+        if self.readable && ring_buffer.available_read() > 0 {
+            events |= PollEvents::POLLIN;
+        }
+        if self.readable && ring_buffer.all_write_ends_closed() {
+            events |= PollEvents::POLLHUP;
+        }
+        if self.writable && ring_buffer.available_write() > 0 {
+            events |= PollEvents::POLLOUT;
+        }
+        if self.writable && ring_buffer.all_read_ends_closed() {
+            events |= PollEvents::POLLERR;
+        }
+        events
+    }
+    fn poll_queue(&self) -> Option<&WaitQueue> {
+        if self.readable {
+            Some(&self.buffer.readers)
+        } else {
+            Some(&self.buffer.writers)
+        }
+    }
 }
@@ -0,0 +1,275 @@
+//! Pseudo-terminals
+//!
+//! Opening `/dev/ptmx` creates a pair and returns its master end, the slave
+//! end is opened as `/dev/pts/N`. What the master writes is the input of the
+//! slave's [`Tty`], and the slave's output and echo are read from the master.
+
+use super::tty::{Tty, TtyDevice};
+use super::{File, PollEvents, Stat};
+use crate::mem::UserBuffer;
+use crate::process::{copy_to_user, current_signal_pending};
+use crate::sync::{SpinNoIrqLock, WaitQueue};
+use crate::syscall::{Errno, SysResult};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use lazy_static::*;
+
+/// get the number of the pty
+const TIOCGPTN: usize = 0x8004_5430;
+/// lock or unlock the slave of the pty
+const TIOCSPTLCK: usize = 0x4004_5431;
+
+/// Writers on the slave wait once this much output is left unread by the master
+const MAX_OUTPUT: usize = 4096;
+
+lazy_static! {
+    /// The pairs which are still open, by number
+    static ref PTYS: SpinNoIrqLock<BTreeMap<usize, Weak<Pty>>> = SpinNoIrqLock::new(BTreeMap::new());
+}
+
+/// The master side as seen from the slave's terminal
+pub struct PtyDevice {
+    /// output of the slave, read by the master
+    output: SpinNoIrqLock<VecDeque<u8>>,
+    /// masters waiting for output
+    readers: WaitQueue,
+    /// slave writers waiting for the master to drain the output
+    writers: WaitQueue,
+    /// the master end is closed, writes on the slave fail
+    master_gone: AtomicBool,
+    /// open slave ends, the master reads end of file once they are all closed
+    slaves: AtomicUsize,
+    /// whether a slave end has been opened at all
+    opened: AtomicBool,
+}
+
+impl TtyDevice for PtyDevice {
+    /// Echo is dropped beyond MAX_OUTPUT, the master typing it may be the
+    /// one to drain the output
+    fn put(&self, bytes: &[u8]) {
+        let mut output = self.output.lock();
+        let room = MAX_OUTPUT.saturating_sub(output.len());
+        output.extend(bytes.iter().take(room));
+        drop(output);
+        self.readers.notify_all();
+    }
+    fn write(&self, bytes: &[u8]) -> SysResult<usize> {
+        let mut written = 0;
+        while written < bytes.len() {
+            let mut output = self.output.lock();
+            if self.master_gone.load(Ordering::Relaxed) {
+                return Err(Errno::EIO);
+            }
+            let room = MAX_OUTPUT.saturating_sub(output.len());
+            if room > 0 {
+                let n = room.min(bytes.len() - written);
+                output.extend(&bytes[written..written + n]);
+                written += n;
+                drop(output);
+                self.readers.notify_all();
+                continue;
+            }
+            if current_signal_pending() {
+                return if written == 0 { Err(Errno::EINTR) } else { Ok(written) };
+            }
+            self.writers.wait_unlock_interruptible(output);
+        }
+        Ok(written)
+    }
+}
+
+/// A pseudo-terminal pair
+pub struct Pty {
+    index: usize,
+    tty: Tty<PtyDevice>,
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let mut ptys = PTYS.lock();
+        // the number may have been handed out again after the last Weak died
+        if ptys.get(&self.index).is_some_and(|pty| pty.strong_count() == 0) {
+            ptys.remove(&self.index);
+        }
+    }
+}
+
+/// The master end of a pseudo-terminal, opened through `/dev/ptmx`
+pub struct PtyMaster(Arc<Pty>);
+
+/// The slave end of a pseudo-terminal, opened as `/dev/pts/N`
+pub struct PtySlave(Arc<Pty>);
+
+/// Create a pseudo-terminal pair, returning its master end
+pub fn open_ptmx() -> Arc<PtyMaster> {
+    let mut ptys = PTYS.lock();
+    let index = (0..).find(|index| {
+        ptys.get(index).is_none_or(|pty| pty.strong_count() == 0)
+    }).unwrap();
+    let pty = Arc::new(Pty {
+        index,
+        tty: Tty::new(PtyDevice {
+            output: SpinNoIrqLock::new(VecDeque::new()),
+            readers: WaitQueue::new(),
+            writers: WaitQueue::new(),
+            master_gone: AtomicBool::new(false),
+            slaves: AtomicUsize::new(0),
+            opened: AtomicBool::new(false),
+        }),
+    });
+    ptys.insert(index, Arc::downgrade(&pty));
+    Arc::new(PtyMaster(pty))
+}
+
+/// Open the slave end of pseudo-terminal `index`
+pub fn open_pts(index: usize) -> Option<Arc<PtySlave>> {
+    let pty = PTYS.lock().get(&index).and_then(Weak::upgrade)?;
+    let device = pty.tty.device();
+    device.slaves.fetch_add(1, Ordering::Relaxed);
+    device.opened.store(true, Ordering::Relaxed);
+    Some(Arc::new(PtySlave(pty)))
+}
+
+impl PtyDevice {
+    /// A slave has been opened and all of them are closed again
+    fn slaves_gone(&self) -> bool {
+        // a slave which has never been opened does not count as closed
+        self.opened.load(Ordering::Relaxed) && self.slaves.load(Ordering::Relaxed) == 0
+    }
+}
+
+impl Drop for PtyMaster {
+    fn drop(&mut self) {
+        let device = self.0.tty.device();
+        // under the lock so that no writer misses it between check and wait
+        let output = device.output.lock();
+        device.master_gone.store(true, Ordering::Relaxed);
+        drop(output);
+        device.writers.notify_all();
+        self.0.tty.hangup();
+    }
+}
+
+impl Drop for PtySlave {
+    fn drop(&mut self) {
+        let device = self.0.tty.device();
+        device.slaves.fetch_sub(1, Ordering::Relaxed);
+        device.readers.notify_all();
+    }
+}
+
+impl File for PtyMaster {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    /// Read what the slave has written, end of file once all slaves are
+    /// closed
+    fn read(&self, buf: UserBuffer) -> SysResult<usize> {
+        let device = self.0.tty.device();
+        let len = buf.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        loop {
+            let mut output = device.output.lock();
+            if !output.is_empty() {
+                let n = output.len().min(len);
+                for (b, p) in output.drain(..n).zip(buf) {
+                    unsafe { *p = b };
+                }
+                drop(output);
+                device.writers.notify_all();
+                return Ok(n);
+            }
+            if device.slaves_gone() {
+                return Ok(0);
+            }
+            if current_signal_pending() {
+                return Err(Errno::EINTR);
+            }
+            device.readers.wait_unlock_interruptible(output);
+        }
+    }
+    /// Type into the slave's terminal
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        let len = buf.len();
+        for b in buf {
+            self.0.tty.receive(unsafe { *b });
+        }
+        Ok(len)
+    }
+    fn stat(&self) -> Option<Stat> {
+        None
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        match cmd {
+            TIOCGPTN => {
+                copy_to_user(arg as *mut u32, &(self.0.index as u32))?;
+                Ok(0)
+            }
+            // slaves are never locked
+            TIOCSPTLCK => Ok(0),
+            _ => self.0.tty.ioctl(cmd, arg),
+        }
+    }
+    fn poll(&self) -> PollEvents {
+        let device = self.0.tty.device();
+        let mut events = PollEvents::POLLOUT;
+        if !device.output.lock().is_empty() {
+            events |= PollEvents::POLLIN;
+        }
+        if device.slaves_gone() {
+            events |= PollEvents::POLLIN | PollEvents::POLLHUP;
+        }
+        events
+    }
+    fn poll_queue(&self) -> Option<&WaitQueue> {
+        Some(&self.0.tty.device().readers)
+    }
+}
+
+impl File for PtySlave {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, buf: UserBuffer) -> SysResult<usize> {
+        self.0.tty.read(buf)
+    }
+    fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        self.0.tty.write(buf)
+    }
+    fn stat(&self) -> Option<Stat> {
+        None
+    }
+    fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        self.0.tty.ioctl(cmd, arg)
+    }
+    fn poll(&self) -> PollEvents {
+        self.0.tty.poll()
+    }
+    fn poll_queue(&self) -> Option<&WaitQueue> {
+        Some(self.0.tty.poll_queue())
+    }
+}
+
+/// Open the pseudo-terminal device at `path`, None if it is no such path
+pub fn open_pty(path: &str) -> Option<SysResult<Arc<dyn File + Send + Sync>>> {
+    if path == "/dev/ptmx" {
+        return Some(Ok(open_ptmx()));
+    }
+    let index = path.strip_prefix("/dev/pts/")?;
+    let file = index
+        .parse()
+        .ok()
+        .and_then(open_pts)
+        .map(|slave| slave as Arc<dyn File + Send + Sync>)
+        .ok_or(Errno::ENOENT);
+    Some(file)
+}
@@ -16,6 +16,8 @@ pub struct VfsNodeAttr {
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// Number of hard links.
+    nlink: u64,
 }
 
 bitflags::bitflags! {
@@ -209,6 +211,7 @@ impl VfsNodeAttr {
             ty,
             size,
             blocks,
+            nlink: 1,
         }
     }
 
@@ -219,6 +222,7 @@ impl VfsNodeAttr {
             ty: VfsNodeType::File,
             size,
             blocks,
+            nlink: 1,
         }
     }
 
@@ -230,6 +234,7 @@ impl VfsNodeAttr {
             ty: VfsNodeType::Dir,
             size,
             blocks,
+            nlink: 1,
         }
     }
 
@@ -243,6 +248,16 @@ impl VfsNodeAttr {
         self.blocks
     }
 
+    /// Returns the number of hard links to the node.
+    pub const fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    /// Sets the number of hard links to the node.
+    pub fn set_nlink(&mut self, nlink: u64) {
+        self.nlink = nlink
+    }
+
     /// Returns the permission of the node.
     pub const fn perm(&self) -> VfsNodePerm {
         self.mode
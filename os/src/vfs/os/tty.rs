@@ -0,0 +1,492 @@
+//! Terminal line discipline
+//!
+//! Input from the device is edited and echoed here according to the
+//! [`Termios`] settings before readers get it: in canonical mode a line at a
+//! time, in raw mode byte by byte. The interrupt, quit and suspend characters
+//! signal the foreground process group instead of being read.
+
+use crate::mem::UserBuffer;
+use crate::process::{copy_from_user, copy_to_user, current_process, current_signal_pending};
+use crate::process::{signal_process_group, SignalFlags};
+use crate::sync::{SpinNoIrqLock, WaitQueue};
+use crate::syscall::{Errno, SysResult};
+use super::PollEvents;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// get the terminal settings
+pub const TCGETS: usize = 0x5401;
+/// set the terminal settings now
+pub const TCSETS: usize = 0x5402;
+/// set the terminal settings once the output is drained
+pub const TCSETSW: usize = 0x5403;
+/// set the terminal settings and discard pending input
+pub const TCSETSF: usize = 0x5404;
+/// get the foreground process group
+pub const TIOCGPGRP: usize = 0x540f;
+/// set the foreground process group
+pub const TIOCSPGRP: usize = 0x5410;
+/// get the window size
+pub const TIOCGWINSZ: usize = 0x5413;
+/// set the window size
+pub const TIOCSWINSZ: usize = 0x5414;
+
+/// number of control characters in [`Termios`]
+pub const NCCS: usize = 19;
+/// index of the interrupt character in `Termios::cc`
+pub const VINTR: usize = 0;
+/// index of the quit character
+pub const VQUIT: usize = 1;
+/// index of the erase character
+pub const VERASE: usize = 2;
+/// index of the kill-line character
+pub const VKILL: usize = 3;
+/// index of the end-of-file character
+pub const VEOF: usize = 4;
+/// index of the minimum number of bytes of a raw read
+pub const VMIN: usize = 6;
+/// index of the suspend character
+pub const VSUSP: usize = 10;
+
+/// A canonical line longer than this only takes a newline
+const MAX_CANON: usize = 4095;
+/// Raw input nobody has read yet is dropped beyond this
+const MAX_INPUT: usize = 4096;
+
+bitflags! {
+    /// Input modes of [`Termios`]
+    pub struct InputModes: u32 {
+        /// translate carriage return to newline
+        const ICRNL = 0o400;
+        /// enable start/stop output control
+        const IXON  = 0o2000;
+    }
+}
+
+bitflags! {
+    /// Output modes of [`Termios`]
+    pub struct OutputModes: u32 {
+        /// post-process output
+        const OPOST = 0o1;
+        /// map newline to carriage return and newline
+        const ONLCR = 0o4;
+    }
+}
+
+bitflags! {
+    /// Local modes of [`Termios`]
+    pub struct LocalModes: u32 {
+        /// signal on the interrupt, quit and suspend characters
+        const ISIG   = 0o1;
+        /// canonical mode: line editing, reads return whole lines
+        const ICANON = 0o2;
+        /// echo input
+        const ECHO   = 0o10;
+        /// the erase character erases on screen
+        const ECHOE  = 0o20;
+        /// the kill character erases the line on screen
+        const ECHOK  = 0o40;
+        /// echo newline even without ECHO
+        const ECHONL = 0o100;
+        /// extended input processing
+        const IEXTEN = 0o100000;
+    }
+}
+
+/// Terminal settings, laid out as the `struct termios` of TCGETS
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct Termios {
+    /// input modes
+    pub iflag: u32,
+    /// output modes
+    pub oflag: u32,
+    /// control modes
+    pub cflag: u32,
+    /// local modes
+    pub lflag: u32,
+    /// line discipline
+    pub line: u8,
+    /// control characters
+    pub cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// The settings of a freshly opened terminal: canonical mode with echo
+    pub const fn new() -> Self {
+        let mut cc = [0u8; NCCS];
+        cc[VINTR] = 0x03; // ^C
+        cc[VQUIT] = 0x1c; // ^\
+        cc[VERASE] = 0x7f; // DEL
+        cc[VKILL] = 0x15; // ^U
+        cc[VEOF] = 0x04; // ^D
+        cc[VMIN] = 1;
+        cc[VSUSP] = 0x1a; // ^Z
+        Self {
+            iflag: InputModes::ICRNL.bits() | InputModes::IXON.bits(),
+            oflag: OutputModes::OPOST.bits() | OutputModes::ONLCR.bits(),
+            // B38400 | CS8 | CREAD
+            cflag: 0o277,
+            lflag: LocalModes::ISIG.bits()
+                | LocalModes::ICANON.bits()
+                | LocalModes::ECHO.bits()
+                | LocalModes::ECHOE.bits()
+                | LocalModes::ECHOK.bits()
+                | LocalModes::IEXTEN.bits(),
+            line: 0,
+            cc,
+        }
+    }
+
+    fn iflag(&self) -> InputModes {
+        InputModes::from_bits_truncate(self.iflag)
+    }
+
+    fn oflag(&self) -> OutputModes {
+        OutputModes::from_bits_truncate(self.oflag)
+    }
+
+    fn lflag(&self) -> LocalModes {
+        LocalModes::from_bits_truncate(self.lflag)
+    }
+}
+
+/// Window size, laid out as the `struct winsize` of TIOCGWINSZ
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct WinSize {
+    /// rows, in characters
+    pub row: u16,
+    /// columns, in characters
+    pub col: u16,
+    /// width, in pixels
+    pub xpixel: u16,
+    /// height, in pixels
+    pub ypixel: u16,
+}
+
+/// Where a terminal sends its output and echo
+pub trait TtyDevice: Send + Sync {
+    /// Put bytes on the screen, for echo which cannot wait
+    fn put(&self, bytes: &[u8]);
+    /// Put the output of a write on the screen, waiting for room if the
+    /// device has to. Return how many bytes went out before a signal
+    /// interrupted, EINTR if none did
+    fn write(&self, bytes: &[u8]) -> SysResult<usize> {
+        self.put(bytes);
+        Ok(bytes.len())
+    }
+}
+
+struct TtyInner {
+    termios: Termios,
+    winsize: WinSize,
+    /// the process group which gets the signals, adopted from the first
+    /// reader unless set by TIOCSPGRP
+    fg_pgrp: Option<usize>,
+    /// the line being edited in canonical mode
+    line: Vec<u8>,
+    /// finished lines in canonical mode, an empty one is end of file
+    lines: VecDeque<Vec<u8>>,
+    /// input in raw mode
+    raw: VecDeque<u8>,
+    /// the other side is gone, reads return end of file once input runs out
+    hung_up: bool,
+}
+
+/// A terminal: a line discipline in front of a [`TtyDevice`]
+pub struct Tty<D: TtyDevice> {
+    inner: SpinNoIrqLock<TtyInner>,
+    /// readers waiting for input
+    readers: WaitQueue,
+    device: D,
+}
+
+impl<D: TtyDevice> Tty<D> {
+    /// Create a terminal in canonical mode on `device`
+    pub const fn new(device: D) -> Self {
+        Self {
+            inner: SpinNoIrqLock::new(TtyInner {
+                termios: Termios::new(),
+                winsize: WinSize { row: 24, col: 80, xpixel: 0, ypixel: 0 },
+                fg_pgrp: None,
+                line: Vec::new(),
+                lines: VecDeque::new(),
+                raw: VecDeque::new(),
+                hung_up: false,
+            }),
+            readers: WaitQueue::new(),
+            device,
+        }
+    }
+
+    /// The device behind the terminal
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Take a byte from the device through the line discipline
+    pub fn receive(&self, ch: u8) {
+        let mut inner = self.inner.lock();
+        let termios = inner.termios;
+        let (iflag, lflag) = (termios.iflag(), termios.lflag());
+        let echo = lflag.contains(LocalModes::ECHO);
+        let ch = if iflag.contains(InputModes::ICRNL) && ch == b'\r' { b'\n' } else { ch };
+        if lflag.contains(LocalModes::ISIG) {
+            let signal = if ch == termios.cc[VINTR] {
+                Some(SignalFlags::SIGINT)
+            } else if ch == termios.cc[VQUIT] {
+                Some(SignalFlags::SIGQUIT)
+            } else if ch == termios.cc[VSUSP] {
+                Some(SignalFlags::SIGTSTP)
+            } else {
+                None
+            };
+            if let Some(signal) = signal {
+                inner.line.clear();
+                if echo {
+                    self.echo_control(ch);
+                    self.output(&termios, b"\n");
+                }
+                if let Some(pgrp) = inner.fg_pgrp {
+                    signal_process_group(pgrp, signal);
+                }
+                // readers of the group have to return for the signal
+                self.readers.notify_all();
+                return;
+            }
+        }
+        if !lflag.contains(LocalModes::ICANON) {
+            if inner.raw.len() < MAX_INPUT {
+                inner.raw.push_back(ch);
+            }
+            if echo {
+                self.output(&termios, &[ch]);
+            }
+            self.readers.notify_all();
+            return;
+        }
+        if ch == termios.cc[VERASE] || ch == b'\x08' {
+            if inner.line.pop().is_some() && echo && lflag.contains(LocalModes::ECHOE) {
+                self.device.put(b"\x08 \x08");
+            }
+        } else if ch == termios.cc[VKILL] {
+            let erased = inner.line.len();
+            inner.line.clear();
+            if echo && lflag.contains(LocalModes::ECHOK) {
+                for _ in 0..erased {
+                    self.device.put(b"\x08 \x08");
+                }
+            }
+        } else if ch == termios.cc[VEOF] {
+            // hand over the line as it is, an empty one reads as end of file
+            let line = core::mem::take(&mut inner.line);
+            inner.lines.push_back(line);
+            self.readers.notify_all();
+        } else if ch == b'\n' {
+            let mut line = core::mem::take(&mut inner.line);
+            line.push(b'\n');
+            inner.lines.push_back(line);
+            if echo || lflag.contains(LocalModes::ECHONL) {
+                self.output(&termios, b"\n");
+            }
+            self.readers.notify_all();
+        } else if inner.line.len() < MAX_CANON {
+            inner.line.push(ch);
+            if echo && ch < b' ' && ch != b'\t' {
+                self.echo_control(ch);
+            } else if echo {
+                self.device.put(&[ch]);
+            }
+        }
+    }
+
+    /// Echo a control character as `^X`
+    fn echo_control(&self, ch: u8) {
+        self.device.put(&[b'^', ch ^ 0x40]);
+    }
+
+    /// Put `bytes` on the device, translating newlines if asked to
+    fn output(&self, termios: &Termios, bytes: &[u8]) {
+        let oflag = termios.oflag();
+        if !oflag.contains(OutputModes::OPOST | OutputModes::ONLCR) {
+            self.device.put(bytes);
+            return;
+        }
+        for segment in bytes.split_inclusive(|&b| b == b'\n') {
+            match segment.split_last() {
+                Some((b'\n', text)) => {
+                    self.device.put(text);
+                    self.device.put(b"\r\n");
+                }
+                _ => self.device.put(segment),
+            }
+        }
+    }
+
+    /// Read into `buf`: a line in canonical mode, else at least VMIN bytes.
+    /// Return 0 at end of file, EINTR when a signal has to be handled first
+    pub fn read(&self, buf: UserBuffer) -> SysResult<usize> {
+        let len = buf.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        let mut dst = buf.into_iter();
+        loop {
+            let mut inner = self.inner.lock();
+            if inner.fg_pgrp.is_none() {
+                inner.fg_pgrp = Some(current_process().inner_exclusive_access().pgid);
+            }
+            if inner.termios.lflag().contains(LocalModes::ICANON) {
+                if let Some(line) = inner.lines.front_mut() {
+                    let n = line.len().min(len);
+                    for (&b, p) in line.iter().zip(&mut dst).take(n) {
+                        unsafe { *p = b };
+                    }
+                    line.drain(..n);
+                    if line.is_empty() {
+                        inner.lines.pop_front();
+                    }
+                    return Ok(n);
+                }
+            } else {
+                let min = (inner.termios.cc[VMIN] as usize).min(len);
+                if inner.raw.len() >= min.max(1) || min == 0 {
+                    let n = inner.raw.len().min(len);
+                    for (b, p) in inner.raw.drain(..n).zip(&mut dst) {
+                        unsafe { *p = b };
+                    }
+                    return Ok(n);
+                }
+            }
+            if inner.hung_up {
+                return Ok(0);
+            }
+            if current_signal_pending() {
+                return Err(Errno::EINTR);
+            }
+            self.readers.wait_unlock_interruptible(inner);
+        }
+    }
+
+    /// The device went away: wake up the readers for end of file
+    pub fn hangup(&self) {
+        self.inner.lock().hung_up = true;
+        self.readers.notify_all();
+    }
+
+    /// Readiness for [`File::poll`](super::File::poll): readable when a read
+    /// would not block, always writable
+    pub fn poll(&self) -> PollEvents {
+        let inner = self.inner.lock();
+        let readable = if inner.termios.lflag().contains(LocalModes::ICANON) {
+            !inner.lines.is_empty()
+        } else {
+            let min = inner.termios.cc[VMIN] as usize;
+            inner.raw.len() >= min.max(1) || min == 0
+        };
+        let mut events = PollEvents::POLLOUT;
+        if readable || inner.hung_up {
+            events |= PollEvents::POLLIN;
+        }
+        if inner.hung_up {
+            events |= PollEvents::POLLHUP;
+        }
+        events
+    }
+
+    /// The queue notified when input arrives
+    pub fn poll_queue(&self) -> &WaitQueue {
+        &self.readers
+    }
+
+    /// Write `buf` to the device with output processing.
+    /// Return how many bytes of `buf` went out
+    pub fn write(&self, buf: UserBuffer) -> SysResult<usize> {
+        let oflag = self.inner.lock().termios.oflag();
+        let onlcr = oflag.contains(OutputModes::OPOST | OutputModes::ONLCR);
+        let mut written = 0;
+        for buffer in buf.buffers.iter() {
+            let mut rest: &[u8] = buffer;
+            while !rest.is_empty() {
+                // a newline is one byte of `buf` but two on the device
+                let (chunk, taken) = if onlcr && rest[0] == b'\n' {
+                    (&b"\r\n"[..], 1)
+                } else {
+                    let end = if onlcr {
+                        rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len())
+                    } else {
+                        rest.len()
+                    };
+                    (&rest[..end], end)
+                };
+                match self.device.write(chunk) {
+                    Ok(n) if n == chunk.len() => {
+                        written += taken;
+                        rest = &rest[taken..];
+                    }
+                    // interrupted part way
+                    Ok(n) => return Ok(written + if taken == chunk.len() { n } else { 0 }),
+                    Err(err) if written == 0 => return Err(err),
+                    Err(_) => return Ok(written),
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    /// Terminal ioctls: settings, window size and foreground process group
+    pub fn ioctl(&self, cmd: usize, arg: usize) -> SysResult {
+        match cmd {
+            TCGETS => {
+                let termios = self.inner.lock().termios;
+                copy_to_user(arg as *mut Termios, &termios)?;
+            }
+            TCSETS | TCSETSW | TCSETSF => {
+                let termios = copy_from_user(arg as *const Termios)?;
+                self.set_termios(termios, cmd == TCSETSF);
+            }
+            TIOCGWINSZ => {
+                let winsize = self.inner.lock().winsize;
+                copy_to_user(arg as *mut WinSize, &winsize)?;
+            }
+            TIOCSWINSZ => {
+                self.inner.lock().winsize = copy_from_user(arg as *const WinSize)?;
+            }
+            TIOCGPGRP => {
+                let pgrp = self.inner.lock().fg_pgrp.unwrap_or(0) as i32;
+                copy_to_user(arg as *mut i32, &pgrp)?;
+            }
+            TIOCSPGRP => {
+                let pgrp = copy_from_user(arg as *const i32)?;
+                if pgrp < 0 {
+                    return Err(Errno::EINVAL);
+                }
+                self.inner.lock().fg_pgrp = Some(pgrp as usize);
+            }
+            _ => return Err(Errno::ENOTTY),
+        }
+        Ok(0)
+    }
+
+    /// Switch settings, carrying the pending input over to the new mode
+    fn set_termios(&self, termios: Termios, flush: bool) {
+        let mut inner = self.inner.lock();
+        let was_canonical = inner.termios.lflag().contains(LocalModes::ICANON);
+        inner.termios = termios;
+        if flush {
+            inner.line.clear();
+            inner.lines.clear();
+            inner.raw.clear();
+        } else if was_canonical && !termios.lflag().contains(LocalModes::ICANON) {
+            let mut pending: Vec<u8> = inner.lines.drain(..).flatten().collect();
+            pending.append(&mut inner.line);
+            inner.raw.extend(pending);
+        } else if !was_canonical && termios.lflag().contains(LocalModes::ICANON) {
+            let pending: Vec<u8> = inner.raw.drain(..).collect();
+            inner.line.extend(pending);
+        }
+        drop(inner);
+        self.readers.notify_all();
+    }
+}
@@ -0,0 +1,15 @@
+#include "syscall_test.h"
+
+int _start() {
+    // nothing is mapped at the first page
+    check("write from unmapped memory is EFAULT", syscall_write(1, (const char *)0x10, 8) == -EFAULT);
+    // the text segment is not writable
+    check("read into text segment is EFAULT", syscall_read(0, (char *)_start, 8) == -EFAULT);
+    check("sigprocmask into unmapped memory is EFAULT",
+          syscall_sigprocmask(SIG_BLOCK, NULL, (unsigned long *)0x10) == -EFAULT);
+    // kernel addresses are never accessible
+    check("write from kernel memory is EFAULT",
+          syscall_write(1, (const char *)0xffffffffc0200000UL, 8) == -EFAULT);
+    syscall_exit(0);
+    return 0;
+}
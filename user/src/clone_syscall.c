@@ -0,0 +1,52 @@
+#include "syscall_test.h"
+
+static char stacks[2][4096] __attribute__((aligned(16)));
+static volatile long shared = 0;
+static int ptid = 0;
+static volatile int ctid = -1;
+
+static int thread_main(void *arg) {
+    unsigned long tp;
+    asm volatile ("mv %0, tp" : "=r" (tp));
+    shared = (long)arg + (tp == 0x1234);
+    return 0;
+}
+
+static int process_main(void *arg) {
+    shared = (long)arg;
+    return (long)arg;
+}
+
+int _start() {
+    int status = 0;
+
+    long tid = syscall_clone(CLONE_VM | CLONE_FILES | CLONE_SIGHAND | CLONE_THREAD | CLONE_SETTLS |
+                                 CLONE_PARENT_SETTID | CLONE_CHILD_CLEARTID,
+                             stacks[0] + 4096, &ptid, 0x1234, (int *)&ctid, thread_main, (void *)41);
+    if (tid > 0 && ptid == tid) {
+        print("CLONE_PARENT_SETTID stored the tid\n");
+    }
+    // CLONE_CHILD_CLEARTID clears ctid once the thread is gone
+    while (ctid != 0) {
+        syscall_yield();
+    }
+    if (shared == 42) {
+        print("thread shares memory and got its tls\n");
+    }
+
+    long pid = syscall_clone(CLONE_VM | SIGCHLD, stacks[1] + 4096, NULL, 0, NULL, process_main, (void *)7);
+    if (syscall_wait4(pid, &status, 0, NULL) == pid && status == (7 << 8) && shared == 7) {
+        print("CLONE_VM child process wrote to the parent's memory\n");
+    }
+
+    pid = syscall_fork();
+    if (pid == 0) {
+        shared = 99;
+        syscall_exit(0);
+    }
+    if (syscall_wait4(pid, &status, 0, NULL) == pid && shared == 7) {
+        print("forked child wrote to its own copy\n");
+    }
+    syscall_exit(0);
+    return 0;
+}
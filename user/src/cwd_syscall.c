@@ -0,0 +1,63 @@
+#include "syscall_test.h"
+
+static int same(const char *a, const char *b) {
+    while (*a && *a == *b) {
+        a++;
+        b++;
+    }
+    return *a == *b;
+}
+
+// Checks the working directory and the *at syscalls relative to it.
+int _start() {
+    char cwd[64];
+    check("starts at the root", syscall_getcwd(cwd, sizeof(cwd)) == 2 && same(cwd, "/"));
+
+    syscall_unlinkat(AT_FDCWD, "/cwd_test/sub/file", 0);
+    syscall_unlinkat(AT_FDCWD, "/cwd_test/sub", AT_REMOVEDIR);
+    syscall_unlinkat(AT_FDCWD, "/cwd_test", AT_REMOVEDIR);
+    check("mkdirat", syscall_mkdirat(AT_FDCWD, "cwd_test", 0755) == 0);
+    check("mkdirat twice is EEXIST", syscall_mkdirat(AT_FDCWD, "/cwd_test", 0755) == -EEXIST);
+    check("chdir", syscall_chdir("cwd_test") == 0);
+    check("getcwd follows", syscall_getcwd(cwd, sizeof(cwd)) > 0 && same(cwd, "/cwd_test"));
+    check("getcwd too small is ERANGE", syscall_getcwd(cwd, 4) == -ERANGE);
+    check("mkdirat relative to the cwd", syscall_mkdirat(AT_FDCWD, "sub", 0755) == 0);
+
+    int dirfd = syscall_open("/cwd_test/sub", O_RDONLY);
+    check("open a directory", dirfd >= 0);
+    int fd = syscall_openat(dirfd, "file", O_CREAT | O_RDWR, 0644);
+    check("openat relative to a directory fd", fd >= 0);
+    syscall_write(fd, "cwd", 3);
+    syscall_close(fd);
+    fd = syscall_open("sub/./file", O_RDONLY);
+    char buf[4] = {0};
+    check("the file is where the cwd says", fd >= 0 && syscall_read(fd, buf, 3) == 3 && same(buf, "cwd"));
+    syscall_close(fd);
+    check("openat relative to a terminal is ENOTDIR", syscall_openat(0, "x", O_RDONLY, 0) == -ENOTDIR);
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        char child_cwd[64];
+        syscall_getcwd(child_cwd, sizeof(child_cwd));
+        syscall_chdir("/");
+        syscall_exit(same(child_cwd, "/cwd_test") ? 0 : 1);
+    }
+    int status;
+    syscall_waitpid(pid, &status, 0);
+    check("fork inherits the cwd", status == 0);
+    check("the child's chdir stays its own", syscall_getcwd(cwd, sizeof(cwd)) > 0 && same(cwd, "/cwd_test"));
+
+    check("fchdir", syscall_fchdir(dirfd) == 0 && syscall_getcwd(cwd, sizeof(cwd)) > 0
+                        && same(cwd, "/cwd_test/sub"));
+    check("chdir to a file is ENOTDIR", syscall_chdir("file") == -ENOTDIR);
+    check("chdir ..", syscall_chdir("..") == 0 && syscall_getcwd(cwd, sizeof(cwd)) > 0
+                          && same(cwd, "/cwd_test"));
+    check("unlinkat relative to a directory fd", syscall_unlinkat(dirfd, "file", 0) == 0);
+    syscall_close(dirfd);
+    check("unlinkat AT_REMOVEDIR", syscall_unlinkat(AT_FDCWD, "sub", AT_REMOVEDIR) == 0);
+    check("the directory is gone", syscall_chdir("sub") == -ENOENT);
+    syscall_chdir("/");
+    syscall_unlinkat(AT_FDCWD, "cwd_test", AT_REMOVEDIR);
+    syscall_exit(0);
+    return 0;
+}
@@ -0,0 +1,67 @@
+#include "syscall_test.h"
+
+#define CHUNK 4096
+#define CHUNKS 16
+#define PASSES 8
+
+static char buf[CHUNK];
+
+static void fill(char *p, int chunk) {
+    for (int i = 0; i < CHUNK; i++) {
+        p[i] = (char)(chunk * 31 + i);
+    }
+}
+
+// Reads the file over and over and checks what comes back
+static int reader() {
+    for (int pass = 0; pass < PASSES; pass++) {
+        int fd = syscall_open("/disk_io.txt", O_RDONLY);
+        if (fd < 0) {
+            return 1;
+        }
+        for (int chunk = 0; chunk < CHUNKS; chunk++) {
+            if (syscall_read(fd, buf, CHUNK) != CHUNK) {
+                return 2;
+            }
+            for (int i = 0; i < CHUNK; i++) {
+                if (buf[i] != (char)(chunk * 31 + i)) {
+                    return 3;
+                }
+            }
+        }
+        syscall_close(fd);
+    }
+    return 0;
+}
+
+// A child keeps the disk busy while the parent checks that it still gets
+// the CPU: block requests sleep until their completion interrupt.
+int _start() {
+    int fd = syscall_open("/disk_io.txt", O_CREAT | O_TRUNC | O_WRONLY);
+    for (int chunk = 0; chunk < CHUNKS; chunk++) {
+        fill(buf, chunk);
+        syscall_write(fd, buf, CHUNK);
+    }
+    syscall_close(fd);
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        syscall_exit(reader());
+    }
+    long rounds = 0;
+    int status = 0;
+    while (syscall_waitpid(pid, &status, WNOHANG) == 0) {
+        rounds++;
+        syscall_yield();
+    }
+    if (((status >> 8) & 0xff) == 0) {
+        print("child read correct data\n");
+    } else {
+        print("child read wrong data\n");
+    }
+    if (rounds > 0) {
+        print("parent ran during disk I/O\n");
+    }
+    syscall_exit(0);
+    return 0;
+}
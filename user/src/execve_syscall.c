@@ -0,0 +1,81 @@
+#include "syscall_test.h"
+
+void _start();
+
+static int streq(const char *a, const char *b) {
+    while (*a && *a == *b) {
+        a++;
+        b++;
+    }
+    return *a == *b;
+}
+
+// Walks the initial stack: argc, argv, NULL, envp, NULL, auxv
+void start_main(long *sp) {
+    long argc = sp[0];
+    char **argv = (char **)(sp + 1);
+    char **envp = argv + argc + 1;
+    char **p = envp;
+    while (*p) {
+        p++;
+    }
+    unsigned long *auxv = (unsigned long *)(p + 1);
+
+    if (argc == 2 && streq(argv[1], "child")) {
+        if (streq(envp[0], "FOO=bar") && envp[1] == NULL) {
+            print("envp reached the new image\n");
+        }
+        int page_size = 0, entry = 0, phdr = 0, random = 0;
+        for (unsigned long *a = auxv; a[0] != AT_NULL; a += 2) {
+            if (a[0] == AT_PAGESZ && a[1] == 4096) {
+                page_size = 1;
+            } else if (a[0] == AT_ENTRY && a[1] == (unsigned long)_start) {
+                entry = 1;
+            } else if (a[0] == AT_PHDR && a[1] != 0) {
+                phdr = 1;
+            } else if (a[0] == AT_RANDOM && a[1] != 0) {
+                random = 1;
+            }
+        }
+        if (page_size && entry && phdr && random) {
+            print("auxv has AT_PAGESZ, AT_ENTRY, AT_PHDR and AT_RANDOM\n");
+        }
+        if (((unsigned long)sp & 0xf) == 0) {
+            print("sp is 16-byte aligned\n");
+        }
+        syscall_exit(0);
+    }
+
+    char *child_argv[] = {"/bin/execve_syscall.elf", "child", NULL};
+    char *child_envp[] = {"FOO=bar", NULL};
+
+    // failures come back to the old image
+    int fd = syscall_open("/execve_text", O_CREAT | O_TRUNC | O_WRONLY);
+    syscall_write(fd, "#!/bin/sh\n", 10);
+    syscall_close(fd);
+    char *text_argv[] = {"/execve_text", NULL};
+    if (syscall_execve(text_argv[0], text_argv, child_envp) == -ENOEXEC) {
+        print("a file which is not ELF is ENOEXEC\n");
+    }
+    syscall_unlinkat(AT_FDCWD, "/execve_text", 0);
+    static char big[4096];
+    for (int i = 0; i < sizeof(big) - 1; i++) {
+        big[i] = 'x';
+    }
+    char *big_argv[] = {child_argv[0], big, NULL};
+    if (syscall_execve(big_argv[0], big_argv, child_envp) == -E2BIG) {
+        print("arguments too large for the stack are E2BIG\n");
+    }
+
+    syscall_execve(child_argv[0], child_argv, child_envp);
+    print("execve failed\n");
+    syscall_exit(1);
+}
+
+// sp points at argc on entry, hand it to C before anything is pushed
+__attribute__((naked)) void _start() {
+    asm volatile (
+        "mv a0, sp\n"
+        "j start_main\n"
+    );
+}
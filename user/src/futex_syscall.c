@@ -0,0 +1,67 @@
+#include "syscall_test.h"
+
+static char stacks[3][4096] __attribute__((aligned(16)));
+static volatile int word = 0;
+static volatile int other = 0;
+static volatile int woken = 0;
+static volatile int ctid[3] = {-1, -1, -1};
+
+static int waiter(void *arg) {
+    if (syscall_futex((int *)arg, FUTEX_WAIT | FUTEX_PRIVATE_FLAG, 0, NULL, NULL, 0) == 0) {
+        __atomic_fetch_add(&woken, 1, __ATOMIC_SEQ_CST);
+    }
+    return 0;
+}
+
+static void spawn(int i, int *futex) {
+    syscall_clone(CLONE_VM | CLONE_FILES | CLONE_SIGHAND | CLONE_THREAD | CLONE_CHILD_CLEARTID,
+                  stacks[i] + 4096, NULL, 0, (int *)&ctid[i], waiter, futex);
+}
+
+// CLONE_CHILD_CLEARTID wakes a futex waiter on ctid when the thread exits
+static void join(int i) {
+    int tid;
+    while ((tid = ctid[i]) != 0) {
+        syscall_futex((int *)&ctid[i], FUTEX_WAIT, tid, NULL, NULL, 0);
+    }
+}
+
+int _start() {
+    if (syscall_futex((int *)&word, FUTEX_WAIT, 1, NULL, NULL, 0) == -EAGAIN) {
+        print("FUTEX_WAIT returns EAGAIN if the value changed\n");
+    }
+
+    struct timespec timeout = {0, 50 * 1000 * 1000};
+    if (syscall_futex((int *)&word, FUTEX_WAIT, 0, &timeout, NULL, 0) == -ETIMEDOUT) {
+        print("FUTEX_WAIT times out\n");
+    }
+
+    spawn(0, (int *)&word);
+    while (syscall_futex((int *)&word, FUTEX_WAKE, 1, NULL, NULL, 0) == 0) {
+        syscall_yield();
+    }
+    join(0);
+    if (woken == 1) {
+        print("FUTEX_WAKE woke the waiting thread\n");
+    }
+
+    woken = 0;
+    spawn(1, (int *)&word);
+    spawn(2, (int *)&word);
+    // move both waiters to `other` without waking them, then wake them there;
+    // the fourth argument of FUTEX_REQUEUE is the number to move, not a timeout
+    long moved = 0;
+    while (moved < 2) {
+        moved += syscall_futex((int *)&word, FUTEX_REQUEUE, 0, (const struct timespec *)2,
+                               (int *)&other, 0);
+        syscall_yield();
+    }
+    if (syscall_futex((int *)&word, FUTEX_WAKE, 2, NULL, NULL, 0) == 0 &&
+        syscall_futex((int *)&other, FUTEX_WAKE, 2, NULL, NULL, 0) == 2) {
+        print("FUTEX_REQUEUE moved the waiters to another futex\n");
+    }
+    join(1);
+    join(2);
+    syscall_exit(0);
+    return 0;
+}
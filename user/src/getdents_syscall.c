@@ -0,0 +1,71 @@
+#include "syscall_test.h"
+
+static int same(const char *a, const char *b) {
+    while (*a && *a == *b) {
+        a++;
+        b++;
+    }
+    return *a == *b;
+}
+
+static char buf[256];
+
+// Counts the entries of the directory open as `fd` that are called `name`,
+// reading them `size` bytes at a time.
+static int count_named(int fd, const char *name, long size, int *type) {
+    int found = 0;
+    long n;
+    while ((n = syscall_getdents64(fd, buf, size)) > 0) {
+        for (long pos = 0; pos < n;) {
+            struct linux_dirent64 *entry = (struct linux_dirent64 *)(buf + pos);
+            if (same(entry->d_name, name)) {
+                found++;
+                *type = entry->d_type;
+            }
+            pos += entry->d_reclen;
+        }
+    }
+    return n < 0 ? -1 : found;
+}
+
+// Checks O_DIRECTORY opens and getdents64 on a directory with a few entries.
+int _start() {
+    syscall_unlinkat(AT_FDCWD, "/dents_test/a", 0);
+    syscall_unlinkat(AT_FDCWD, "/dents_test/b", 0);
+    syscall_unlinkat(AT_FDCWD, "/dents_test/sub", AT_REMOVEDIR);
+    syscall_unlinkat(AT_FDCWD, "/dents_test", AT_REMOVEDIR);
+    syscall_mkdirat(AT_FDCWD, "/dents_test", 0755);
+    syscall_close(syscall_open("/dents_test/a", O_CREAT | O_RDWR));
+    syscall_close(syscall_open("/dents_test/b", O_CREAT | O_RDWR));
+    syscall_mkdirat(AT_FDCWD, "/dents_test/sub", 0755);
+
+    check("O_DIRECTORY on a file is ENOTDIR", syscall_open("/dents_test/a", O_RDONLY | O_DIRECTORY) == -ENOTDIR);
+    check("writing a directory is EISDIR", syscall_open("/dents_test", O_RDWR) == -EISDIR);
+    int fd = syscall_open("/dents_test", O_RDONLY | O_DIRECTORY);
+    check("O_DIRECTORY", fd >= 0);
+    check("read on a directory is EISDIR", syscall_read(fd, buf, sizeof(buf)) == -EISDIR);
+
+    int type = 0;
+    check("lists a file", count_named(fd, "a", sizeof(buf), &type) == 1 && type == DT_REG);
+    check("end of directory", syscall_getdents64(fd, buf, sizeof(buf)) == 0);
+    syscall_close(fd);
+
+    // a record is at least 24 bytes, so 32 only takes one at a time
+    fd = syscall_open("/dents_test", O_RDONLY | O_DIRECTORY);
+    check("resumes across calls", count_named(fd, "sub", 32, &type) == 1 && type == DT_DIR);
+    syscall_close(fd);
+    fd = syscall_open("/dents_test", O_RDONLY | O_DIRECTORY);
+    check("too small for a record is EINVAL", syscall_getdents64(fd, buf, 8) == -EINVAL);
+    syscall_close(fd);
+
+    int file = syscall_open("/dents_test/b", O_RDONLY);
+    check("getdents64 on a file is ENOTDIR", syscall_getdents64(file, buf, sizeof(buf)) == -ENOTDIR);
+    syscall_close(file);
+
+    syscall_unlinkat(AT_FDCWD, "/dents_test/a", 0);
+    syscall_unlinkat(AT_FDCWD, "/dents_test/b", 0);
+    syscall_unlinkat(AT_FDCWD, "/dents_test/sub", AT_REMOVEDIR);
+    syscall_unlinkat(AT_FDCWD, "/dents_test", AT_REMOVEDIR);
+    syscall_exit(0);
+    return 0;
+}
@@ -0,0 +1,59 @@
+#include "syscall_test.h"
+
+static int same(const char *a, const char *b, long len) {
+    for (long i = 0; i < len; i++) {
+        if (a[i] != b[i]) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+static long nlink_of(int fd) {
+    struct stat st;
+    return syscall_fstat(fd, &st) == 0 ? st.st_nlink : -1;
+}
+
+// Checks hard links, link counts and unlinking a file that is still open.
+int _start() {
+    char buf[8];
+    syscall_unlinkat(AT_FDCWD, "/link_a", 0);
+    syscall_unlinkat(AT_FDCWD, "/link_b", 0);
+    syscall_unlinkat(AT_FDCWD, "/link_dir", AT_REMOVEDIR);
+    int fd = syscall_open("/link_a", O_CREAT | O_EXCL | O_RDWR);
+    syscall_write(fd, "data", 4);
+    check("a new file has one link", nlink_of(fd) == 1);
+
+    long linked = syscall_linkat(AT_FDCWD, "/link_a", AT_FDCWD, "/link_b", 0);
+    if (linked == -EPERM) {
+        // FAT keeps the file in its directory entry, there is no second one
+        print("no hard links on this file system\n");
+        syscall_close(fd);
+    } else {
+        check("linkat", linked == 0);
+        check("the count follows", nlink_of(fd) == 2);
+        int other = syscall_open("/link_b", O_RDONLY);
+        check("both names reach the data", syscall_read(other, buf, 4) == 4 && same(buf, "data", 4));
+        check("linking over a name is EEXIST", syscall_linkat(AT_FDCWD, "/link_b", AT_FDCWD, "/link_a", 0) == -EEXIST);
+
+        check("unlinking one name", syscall_unlinkat(AT_FDCWD, "/link_a", 0) == 0);
+        check("leaves the other", nlink_of(other) == 1 && syscall_open("/link_a", O_RDONLY) == -ENOENT);
+        syscall_close(fd);
+
+        check("unlinking the last name of an open file", syscall_unlinkat(AT_FDCWD, "/link_b", 0) == 0);
+        check("no links left", nlink_of(other) == 0);
+        check("still readable while open", syscall_pread64(other, buf, 4, 0) == 4 && same(buf, "data", 4));
+        check("the name is gone", syscall_open("/link_b", O_RDONLY) == -ENOENT);
+        syscall_close(other);
+    }
+
+    syscall_mkdirat(AT_FDCWD, "/link_dir", 0755);
+    check("linking a directory is EPERM", syscall_linkat(AT_FDCWD, "/link_dir", AT_FDCWD, "/link_c", 0) == -EPERM);
+    check("unlinking a directory is EISDIR", syscall_unlinkat(AT_FDCWD, "/link_dir", 0) == -EISDIR);
+    check("linking a missing file is ENOENT", syscall_linkat(AT_FDCWD, "/link_none", AT_FDCWD, "/link_c", 0) == -ENOENT);
+    check("unlinking a missing file is ENOENT", syscall_unlinkat(AT_FDCWD, "/link_none", 0) == -ENOENT);
+    syscall_unlinkat(AT_FDCWD, "/link_dir", AT_REMOVEDIR);
+    syscall_unlinkat(AT_FDCWD, "/link_a", 0);
+    syscall_exit(0);
+    return 0;
+}
@@ -0,0 +1,23 @@
+#include "syscall_test.h"
+
+// Lists the working directory, directories with a trailing slash.
+int _start() {
+    static char buf[512];
+    int fd = syscall_open(".", O_RDONLY | O_DIRECTORY);
+    if (fd < 0) {
+        print("ls: cannot open the working directory\n");
+        syscall_exit(1);
+    }
+    long n;
+    while ((n = syscall_getdents64(fd, buf, sizeof(buf))) > 0) {
+        for (long pos = 0; pos < n;) {
+            struct linux_dirent64 *entry = (struct linux_dirent64 *)(buf + pos);
+            print(entry->d_name);
+            print(entry->d_type == DT_DIR ? "/\n" : "\n");
+            pos += entry->d_reclen;
+        }
+    }
+    syscall_close(fd);
+    syscall_exit(n < 0 ? 1 : 0);
+    return 0;
+}
@@ -0,0 +1,120 @@
+#include "syscall_test.h"
+
+void _start();
+
+static int same(const char *a, const char *b, long len) {
+    for (long i = 0; i < len; i++) {
+        if (a[i] != b[i]) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+static int streq(const char *a, const char *b) {
+    while (*a && *a == *b) {
+        a++;
+        b++;
+    }
+    return *a == *b;
+}
+
+// The descriptors the exec test leaves open, one of them close-on-exec
+#define KEPT_FD   10
+#define CLOSED_FD 11
+
+// Checks O_EXCL, O_TRUNC, O_APPEND, O_CLOEXEC, fcntl and dup3.
+void start_main(long *sp) {
+    long argc = sp[0];
+    char **argv = (char **)(sp + 1);
+    if (argc == 2 && streq(argv[1], "child")) {
+        int kept = syscall_fcntl(KEPT_FD, F_GETFD, 0) == 0;
+        int closed = syscall_fcntl(CLOSED_FD, F_GETFD, 0) == -EBADF;
+        syscall_exit(kept && closed ? 0 : 1);
+    }
+
+    char buf[16];
+    syscall_unlinkat(AT_FDCWD, "/flags_test", 0);
+    int fd = syscall_open("/flags_test", O_CREAT | O_EXCL | O_RDWR);
+    check("O_CREAT | O_EXCL creates", fd >= 0);
+    syscall_write(fd, "abc", 3);
+    syscall_close(fd);
+    check("O_CREAT | O_EXCL on an existing file is EEXIST",
+          syscall_open("/flags_test", O_CREAT | O_EXCL | O_RDWR) == -EEXIST);
+    fd = syscall_open("/flags_test", O_CREAT | O_RDWR);
+    check("O_CREAT keeps the content", syscall_read(fd, buf, sizeof(buf)) == 3 && same(buf, "abc", 3));
+    syscall_close(fd);
+    fd = syscall_open("/flags_test", O_RDONLY | O_TRUNC);
+    check("O_TRUNC needs write access", syscall_read(fd, buf, sizeof(buf)) == 3);
+    syscall_close(fd);
+
+    fd = syscall_open("/flags_test", O_WRONLY | O_APPEND);
+    check("F_GETFL", syscall_fcntl(fd, F_GETFL, 0) == (O_WRONLY | O_APPEND));
+    syscall_lseek(fd, 0, SEEK_SET);
+    syscall_write(fd, "def", 3);
+    check("O_APPEND writes at the end", syscall_lseek(fd, 0, SEEK_CUR) == 6);
+    check("F_SETFL clears O_APPEND", syscall_fcntl(fd, F_SETFL, 0) == 0 && syscall_fcntl(fd, F_GETFL, 0) == O_WRONLY);
+    syscall_lseek(fd, 0, SEEK_SET);
+    syscall_write(fd, "ABC", 3);
+    syscall_close(fd);
+    fd = syscall_open("/flags_test", O_RDONLY);
+    check("both writes landed", syscall_read(fd, buf, sizeof(buf)) == 6 && same(buf, "ABCdef", 6));
+    syscall_close(fd);
+    fd = syscall_open("/flags_test", O_RDWR | O_TRUNC);
+    check("O_TRUNC empties", syscall_read(fd, buf, sizeof(buf)) == 0);
+
+    check("F_GETFD without O_CLOEXEC", syscall_fcntl(fd, F_GETFD, 0) == 0);
+    int copy = syscall_fcntl(fd, F_DUPFD, 20);
+    check("F_DUPFD takes the lowest free from arg", copy == 20);
+    check("F_DUPFD_CLOEXEC", syscall_fcntl(syscall_fcntl(fd, F_DUPFD_CLOEXEC, 0), F_GETFD, 0) == FD_CLOEXEC);
+    check("F_SETFD", syscall_fcntl(copy, F_SETFD, FD_CLOEXEC) == 0 && syscall_fcntl(copy, F_GETFD, 0) == FD_CLOEXEC);
+    check("FD_CLOEXEC is per descriptor", syscall_fcntl(fd, F_GETFD, 0) == 0);
+    syscall_close(copy);
+
+    check("dup3 onto itself is EINVAL", syscall_dup3(fd, fd, 0) == -EINVAL);
+    check("dup3 with unknown flags is EINVAL", syscall_dup3(fd, 30, O_APPEND) == -EINVAL);
+    check("dup3 of a closed fd is EBADF", syscall_dup3(29, 30, 0) == -EBADF);
+    int other = syscall_open("/flags_test", O_RDONLY);
+    check("dup3 replaces an open fd", syscall_dup3(fd, other, O_CLOEXEC) == other
+                                          && syscall_fcntl(other, F_GETFL, 0) == O_RDWR
+                                          && syscall_fcntl(other, F_GETFD, 0) == FD_CLOEXEC);
+    syscall_write(fd, "xyz", 3);
+    check("dup3 shares the offset", syscall_lseek(other, 0, SEEK_CUR) == 3);
+    syscall_close(other);
+
+    int fds[2];
+    syscall_pipe2(fds, O_CLOEXEC);
+    check("pipe2 O_CLOEXEC", syscall_fcntl(fds[0], F_GETFD, 0) == FD_CLOEXEC);
+    check("F_SETFL O_NONBLOCK", syscall_fcntl(fds[0], F_SETFL, O_NONBLOCK) == 0
+                                    && syscall_fcntl(fds[0], F_GETFL, 0) == (O_RDONLY | O_NONBLOCK));
+    check("the pipe stops blocking", syscall_read(fds[0], buf, 1) == -EAGAIN);
+    syscall_close(fds[0]);
+    syscall_close(fds[1]);
+
+    syscall_dup3(fd, KEPT_FD, 0);
+    syscall_dup3(fd, CLOSED_FD, O_CLOEXEC);
+    syscall_close(fd);
+    long pid = syscall_fork();
+    if (pid == 0) {
+        char *child_argv[] = {"/bin/open_flags_syscall.elf", "child", NULL};
+        char *child_envp[] = {NULL};
+        syscall_execve(child_argv[0], child_argv, child_envp);
+        syscall_exit(2);
+    }
+    int status;
+    syscall_waitpid(pid, &status, 0);
+    check("execve closes only the O_CLOEXEC descriptors", status == 0);
+    syscall_close(KEPT_FD);
+    syscall_close(CLOSED_FD);
+
+    syscall_unlinkat(AT_FDCWD, "/flags_test", 0);
+    syscall_exit(0);
+}
+
+// sp points at argc on entry, hand it to C before anything is pushed
+__attribute__((naked)) void _start() {
+    asm volatile (
+        "mv a0, sp\n"
+        "j start_main\n"
+    );
+}
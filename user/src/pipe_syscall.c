@@ -0,0 +1,93 @@
+#include "syscall_test.h"
+
+#define TOTAL (1024 * 1024)
+
+static char buf[8192];
+
+static volatile int interrupted = 0;
+
+static void sigint_handler(int sig) {
+    interrupted = 1;
+}
+
+// Checks pipe capacity, nonblocking ends, EPIPE and a megabyte of traffic.
+int _start() {
+    int fds[2];
+    check("pipe", syscall_pipe(fds) == 0);
+    check("a page by default", syscall_fcntl(fds[0], F_GETPIPE_SZ, 0) == 4096);
+    check("F_SETPIPE_SZ rounds up", syscall_fcntl(fds[1], F_SETPIPE_SZ, 40000) == 65536);
+    check("F_GETPIPE_SZ on the other end", syscall_fcntl(fds[0], F_GETPIPE_SZ, 0) == 65536);
+    check("too large is EPERM", syscall_fcntl(fds[0], F_SETPIPE_SZ, 1 << 30) == -EPERM);
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        syscall_close(fds[0]);
+        for (long sent = 0; sent < TOTAL; sent += sizeof(buf)) {
+            for (int i = 0; i < sizeof(buf); i++) {
+                buf[i] = (char)(sent + i);
+            }
+            if (syscall_write(fds[1], buf, sizeof(buf)) != sizeof(buf)) {
+                syscall_exit(1);
+            }
+        }
+        syscall_exit(0);
+    }
+    syscall_close(fds[1]);
+    long received = 0;
+    int intact = 1;
+    long n;
+    while ((n = syscall_read(fds[0], buf, sizeof(buf))) > 0) {
+        for (long i = 0; i < n; i++) {
+            intact &= buf[i] == (char)(received + i);
+        }
+        received += n;
+    }
+    int status;
+    syscall_waitpid(pid, &status, 0);
+    check("a megabyte went through intact", received == TOTAL && intact && status == 0);
+    syscall_close(fds[0]);
+
+    check("pipe2 O_NONBLOCK", syscall_pipe2(fds, O_NONBLOCK) == 0);
+    check("empty read is EAGAIN", syscall_read(fds[0], buf, 1) == -EAGAIN);
+    long filled = 0;
+    while ((n = syscall_write(fds[1], buf, 1000)) > 0) {
+        filled += n;
+    }
+    check("full write is EAGAIN after a page", n == -EAGAIN && filled == 4096);
+    check("growing makes room", syscall_fcntl(fds[1], F_SETPIPE_SZ, 8192) == 8192
+                                    && syscall_write(fds[1], buf, 1000) == 1000);
+    check("shrinking below the content is EBUSY", syscall_fcntl(fds[1], F_SETPIPE_SZ, 4096) == -EBUSY);
+    check("unknown pipe2 flags are EINVAL", syscall_pipe2(fds, 0x40) == -EINVAL);
+
+    syscall_close(fds[0]);
+    pid = syscall_fork();
+    if (pid == 0) {
+        syscall_write(fds[1], "x", 1);
+        syscall_exit(0);
+    }
+    syscall_waitpid(pid, &status, 0);
+    check("writing without readers kills with SIGPIPE", (status & 0x7f) == SIGPIPE);
+
+    struct sigaction ignore = {SIG_IGN, 0, 0};
+    syscall_sigaction(SIGPIPE, &ignore, NULL);
+    check("ignored SIGPIPE leaves EPIPE", syscall_write(fds[1], "x", 1) == -EPIPE);
+    syscall_close(fds[1]);
+
+    // installed before the fork, so the signal cannot find the child without it
+    struct sigaction catch = {sigint_handler, 0, 0};
+    syscall_sigaction(SIGINT, &catch, NULL);
+    syscall_pipe(fds);
+    pid = syscall_fork();
+    if (pid == 0) {
+        long ret = syscall_read(fds[0], buf, 1);
+        syscall_exit(ret == -EINTR && interrupted ? 3 : 1);
+    }
+    for (int i = 0; i < 10; i++) {
+        syscall_yield();
+    }
+    syscall_kill(pid, SIGINT);
+    syscall_waitpid(pid, &status, 0);
+    check("a signal interrupts a blocked read", status == (3 << 8));
+    syscall_exit(0);
+    return 0;
+}
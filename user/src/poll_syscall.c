@@ -0,0 +1,91 @@
+#include "syscall_test.h"
+
+static long now_ms() {
+    struct timeval tv;
+    syscall_gettimeofday(&tv);
+    return tv.tv_sec * 1000 + tv.tv_usec / 1000;
+}
+
+static volatile int caught = 0;
+
+static void sigint_handler(int sig) {
+    caught = sig;
+}
+
+// Checks ppoll and pselect6 on the two ends of a pipe.
+int _start() {
+    int fds[2];
+    check("pipe", syscall_pipe(fds) == 0);
+
+    struct pollfd pfd[2];
+    pfd[0].fd = fds[0];
+    pfd[0].events = POLLIN;
+    pfd[1].fd = fds[1];
+    pfd[1].events = POLLOUT;
+    struct timespec zero = {0, 0};
+    check("empty pipe: only the write end is ready",
+          syscall_ppoll(pfd, 2, &zero, NULL) == 1 && pfd[0].revents == 0 && pfd[1].revents == POLLOUT);
+
+    struct timespec short_wait = {0, 50 * 1000 * 1000};
+    long start = now_ms();
+    check("timeout expires", syscall_ppoll(pfd, 1, &short_wait, NULL) == 0);
+    check("timeout took its time", now_ms() - start >= 50);
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        for (int i = 0; i < 10; i++) {
+            syscall_yield();
+        }
+        syscall_write(fds[1], "x", 1);
+        syscall_exit(0);
+    }
+    check("woken up by the writer", syscall_ppoll(pfd, 1, NULL, NULL) == 1 && pfd[0].revents == POLLIN);
+    syscall_waitpid(pid, NULL, 0);
+
+    unsigned long readfds = 1ul << fds[0];
+    unsigned long writefds = 1ul << fds[1];
+    check("pselect6 sees both ends",
+          syscall_pselect6(fds[1] + 1, &readfds, &writefds, NULL, &zero) == 2
+              && readfds == 1ul << fds[0] && writefds == 1ul << fds[1]);
+
+    char ch;
+    syscall_read(fds[0], &ch, 1);
+    readfds = 1ul << fds[0];
+    check("pselect6 clears what is not ready",
+          syscall_pselect6(fds[0] + 1, &readfds, NULL, NULL, &zero) == 0 && readfds == 0);
+
+    syscall_close(fds[1]);
+    check("closed writer hangs up the reader",
+          syscall_ppoll(pfd, 1, NULL, NULL) == 1 && (pfd[0].revents & POLLHUP));
+
+    pfd[1].fd = fds[1];
+    check("closed descriptor is POLLNVAL",
+          syscall_ppoll(&pfd[1], 1, NULL, NULL) == 1 && pfd[1].revents == POLLNVAL);
+    pfd[1].fd = -1;
+    check("negative descriptor is ignored",
+          syscall_ppoll(&pfd[1], 1, &zero, NULL) == 0 && pfd[1].revents == 0);
+
+    pfd[0].fd = 0;
+    pfd[0].events = POLLOUT;
+    check("stdin is never writable", syscall_ppoll(pfd, 1, &zero, NULL) == 0);
+
+    // a signal that only the ppoll mask lets through is handled, and the
+    // old mask is back afterwards
+    struct sigaction act = { sigint_handler, 0, 0 };
+    syscall_sigaction(SIGINT, &act, NULL);
+    unsigned long blocked = 1ul << (SIGINT - 1), unblocked = 0, after = 0;
+    syscall_sigprocmask(SIG_BLOCK, &blocked, NULL);
+    syscall_kill(syscall_getpid(), SIGINT);
+    syscall_pipe(fds);
+    pfd[0].fd = fds[0];
+    pfd[0].events = POLLIN;
+    check("the ppoll mask lets a signal in", syscall_ppoll(pfd, 1, NULL, &unblocked) == -EINTR);
+    syscall_sigprocmask(SIG_BLOCK, NULL, &after);
+    check("its handler ran", caught == SIGINT);
+    check("the old mask is back", after == blocked);
+    syscall_sigprocmask(SIG_UNBLOCK, &blocked, NULL);
+    syscall_close(fds[0]);
+    syscall_close(fds[1]);
+    syscall_exit(0);
+    return 0;
+}
@@ -0,0 +1,51 @@
+#include "syscall_test.h"
+
+static long now_ms() {
+    struct timeval tv;
+    syscall_gettimeofday(&tv);
+    return tv.tv_sec * 1000 + tv.tv_usec / 1000;
+}
+
+static char stacks[2][4096] __attribute__((aligned(16)));
+static volatile long counts[2] = {0, 0};
+static volatile int stop = 0;
+
+static int spin(void *arg) {
+    long i = (long)arg;
+    syscall_set_priority(i == 0 ? 4 : 16);
+    while (!stop) {
+        counts[i]++;
+    }
+    return 0;
+}
+
+// With stride scheduling a task with four times the priority gets about four
+// times the CPU; round-robin and MLFQ give both the same share.
+int _start() {
+    int status;
+    long pids[2];
+
+    if (syscall_set_priority(1) < 0) {
+        print("set_priority rejects priorities below 2\n");
+    }
+    // stay ahead of the spinning children so that the deadline is kept
+    syscall_set_priority(64);
+    for (long i = 0; i < 2; i++) {
+        pids[i] = syscall_clone(CLONE_VM | SIGCHLD, stacks[i] + 4096, NULL, 0, NULL, spin, (void *)i);
+    }
+    long deadline = now_ms() + 1000;
+    while (now_ms() < deadline) {
+        syscall_yield();
+    }
+    stop = 1;
+    for (long i = 0; i < 2; i++) {
+        syscall_wait4(pids[i], &status, 0, NULL);
+    }
+    if (counts[1] > counts[0] * 2) {
+        print("the task with the higher priority got more CPU time\n");
+    } else {
+        print("both tasks got about the same CPU time\n");
+    }
+    syscall_exit(0);
+    return 0;
+}
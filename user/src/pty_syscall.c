@@ -0,0 +1,125 @@
+#include "syscall_test.h"
+
+static char output[2048];
+static long output_len = 0;
+
+static int contains(const char *needle) {
+    for (long i = 0; i < output_len; i++) {
+        long j = 0;
+        while (needle[j] && i + j < output_len && output[i + j] == needle[j]) {
+            j++;
+        }
+        if (!needle[j]) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+// Read from the master until `needle` shows up, false on end of file
+static int expect(int master, const char *needle) {
+    while (!contains(needle)) {
+        if (output_len == sizeof(output)) {
+            output_len = 0;
+        }
+        long n = syscall_read(master, output + output_len, sizeof(output) - output_len);
+        if (n <= 0) {
+            return 0;
+        }
+        output_len += n;
+    }
+    return 1;
+}
+
+static void type(int master, const char *line) {
+    long len = 0;
+    while (line[len]) {
+        len++;
+    }
+    syscall_write(master, line, len);
+}
+
+// Drives the shell through a pseudo-terminal, the way a remote shell would.
+int _start() {
+    int master = syscall_open("/dev/ptmx", O_RDWR);
+    unsigned int index = 0;
+    if (master < 0 || syscall_ioctl(master, TIOCGPTN, &index) != 0 || index > 9) {
+        print("no pty\n");
+        syscall_exit(1);
+    }
+    char slave_path[] = "/dev/pts/0";
+    slave_path[9] = '0' + index;
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        // the slave becomes stdin, stdout and stderr of the shell
+        syscall_close(0);
+        syscall_close(1);
+        syscall_close(2);
+        syscall_open(slave_path, O_RDWR);
+        syscall_dup(0);
+        syscall_dup(0);
+        syscall_close(master);
+        char *argv[] = {"/bin/shell_syscall.elf", NULL};
+        char *envp[] = {NULL};
+        syscall_execve(argv[0], argv, envp);
+        syscall_exit(1);
+    }
+
+    if (expect(master, "$ ")) {
+        print("got the prompt\n");
+    }
+    output_len = 0;
+    type(master, "/bin/hello_syscall.elf\n");
+    if (expect(master, "/bin/hello_syscall.elf\r\n")) {
+        print("command was echoed\n");
+    }
+    if (expect(master, "Hello World in ELF!")) {
+        print("command ran in the shell\n");
+    }
+    type(master, "exit\n");
+    int status = -1;
+    syscall_waitpid(pid, &status, 0);
+    if (status == 0) {
+        print("shell exited\n");
+    }
+    // no slave is open any more
+    if (!expect(master, "never printed")) {
+        print("master reads end of file\n");
+    }
+    syscall_close(master);
+
+    // a writer waits for the master to drain the output instead of losing it
+    master = syscall_open("/dev/ptmx", O_RDWR);
+    syscall_ioctl(master, TIOCGPTN, &index);
+    slave_path[9] = '0' + index;
+    int slave = syscall_open(slave_path, O_RDWR);
+    pid = syscall_fork();
+    if (pid == 0) {
+        static char block[1024];
+        long total = 0;
+        for (int i = 0; i < 8; i++) {
+            total += syscall_write(slave, block, sizeof(block));
+        }
+        syscall_exit(total == 8 * sizeof(block) ? 0 : 1);
+    }
+    long got = 0;
+    while (got < 8192) {
+        long n = syscall_read(master, output, sizeof(output));
+        if (n <= 0) {
+            break;
+        }
+        got += n;
+    }
+    syscall_waitpid(pid, &status, 0);
+    if (got == 8192 && status == 0) {
+        print("a full pty blocks the writer\n");
+    }
+    syscall_close(master);
+    if (syscall_write(slave, "x", 1) == -EIO) {
+        print("writing after hangup is EIO\n");
+    }
+    syscall_close(slave);
+    syscall_exit(0);
+    return 0;
+}
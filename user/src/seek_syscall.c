@@ -0,0 +1,71 @@
+#include "syscall_test.h"
+
+static int same(const char *a, const char *b, long len) {
+    for (long i = 0; i < len; i++) {
+        if (a[i] != b[i]) {
+            return 0;
+        }
+    }
+    return 1;
+}
+
+// Checks lseek, pread64/pwrite64, readv/writev and the offset shared by dup and fork.
+int _start() {
+    char buf[16];
+    syscall_unlinkat(AT_FDCWD, "/seek_test", 0);
+    int fd = syscall_open("/seek_test", O_CREAT | O_RDWR);
+    check("open", fd >= 0);
+
+    struct iovec out[3] = {{"hello", 5}, {" ", 1}, {"world", 5}};
+    check("writev", syscall_writev(fd, out, 3) == 11);
+    check("writev moves the offset", syscall_lseek(fd, 0, SEEK_CUR) == 11);
+    check("SEEK_END", syscall_lseek(fd, -5, SEEK_END) == 6);
+    check("read after SEEK_END", syscall_read(fd, buf, 5) == 5 && same(buf, "world", 5));
+    check("SEEK_SET", syscall_lseek(fd, 0, SEEK_SET) == 0);
+    check("before the start is EINVAL", syscall_lseek(fd, -1, SEEK_CUR) == -EINVAL);
+    check("unknown whence is EINVAL", syscall_lseek(fd, 0, 42) == -EINVAL);
+
+    char first[5], second[6];
+    struct iovec in[2] = {{first, 5}, {second, 6}};
+    check("readv", syscall_readv(fd, in, 2) == 11 && same(first, "hello", 5) && same(second, " world", 6));
+
+    check("pread64", syscall_pread64(fd, buf, 5, 0) == 5 && same(buf, "hello", 5));
+    check("pwrite64", syscall_pwrite64(fd, "HELLO", 5, 0) == 5);
+    check("pread64 and pwrite64 leave the offset", syscall_lseek(fd, 0, SEEK_CUR) == 11);
+    check("pread64 sees pwrite64", syscall_pread64(fd, buf, 11, 0) == 11 && same(buf, "HELLO world", 11));
+    check("pread64 past the end", syscall_pread64(fd, buf, 5, 100) == 0);
+    check("negative pread64 offset is EINVAL", syscall_pread64(fd, buf, 5, -1) == -EINVAL);
+
+    check("SEEK_DATA", syscall_lseek(fd, 3, SEEK_DATA) == 3);
+    check("SEEK_HOLE is the end", syscall_lseek(fd, 3, SEEK_HOLE) == 11);
+    check("SEEK_DATA past the end is ENXIO", syscall_lseek(fd, 11, SEEK_DATA) == -ENXIO);
+
+    int copy = syscall_dup(fd);
+    syscall_lseek(fd, 2, SEEK_SET);
+    check("dup shares the offset", syscall_lseek(copy, 0, SEEK_CUR) == 2);
+    syscall_close(copy);
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        syscall_lseek(fd, 6, SEEK_SET);
+        syscall_exit(0);
+    }
+    int status;
+    syscall_waitpid(pid, &status, 0);
+    check("fork shares the offset", syscall_read(fd, buf, 5) == 5 && same(buf, "world", 5));
+    syscall_close(fd);
+
+    int fds[2];
+    syscall_pipe(fds);
+    check("lseek on a pipe is ESPIPE", syscall_lseek(fds[0], 0, SEEK_SET) == -ESPIPE);
+    check("pread64 on a pipe is ESPIPE", syscall_pread64(fds[0], buf, 1, 0) == -ESPIPE);
+    struct iovec pipe_out[2] = {{"ab", 2}, {"cd", 2}};
+    check("writev into a pipe", syscall_writev(fds[1], pipe_out, 2) == 4);
+    check("read it back", syscall_read(fds[0], buf, 4) == 4 && same(buf, "abcd", 4));
+    syscall_close(fds[0]);
+    syscall_close(fds[1]);
+
+    syscall_unlinkat(AT_FDCWD, "/seek_test", 0);
+    syscall_exit(0);
+    return 0;
+}
@@ -3,10 +3,25 @@
 // Function to print the prompt and read user input
 void read_command(char *buffer, long buf_size) {
     syscall_write(1, "\r\n$ ", 4);  // Display prompt
-    syscall_read(0, buffer, buf_size);  // Read input from stdin
+    long n = syscall_read(0, buffer, buf_size - 1);  // Read input from stdin
+    // nothing on end of file (^D) or when interrupted
+    buffer[n > 0 ? n : 0] = '\0';
     syscall_yield();  // Yield the CPU to allow other processes to run
 }
 
+// Hand the terminal to process group `pgid`, its ^C and ^Z go there
+void set_foreground(long pgid) {
+    int pgrp = (int)pgid;
+    syscall_ioctl(0, TIOCSPGRP, &pgrp);
+}
+
+// Set the action of the job control signals
+void set_job_signals(void (*handler)(int)) {
+    struct sigaction act = { handler, 0, 0 };
+    syscall_sigaction(SIGINT, &act, NULL);
+    syscall_sigaction(SIGTSTP, &act, NULL);
+}
+
 // Function to strip newline character from the command
 void strip_newline(char *buffer) {
     while (*buffer) {
@@ -22,7 +37,12 @@ int _start() {
     char buffer[BUF_SIZE];
     char *argv[] = {buffer, NULL};  // argv for execve
     char *envp[] = {NULL};  // Empty environment
-    
+    long shell_pgid = syscall_getpgid(0);
+
+    // ^C and ^Z are meant for the commands, not for the shell
+    set_job_signals(SIG_IGN);
+    set_foreground(shell_pgid);
+
     while (1) {
         // Read the command from the user
         read_command(buffer, BUF_SIZE);
@@ -33,11 +53,29 @@ int _start() {
             continue;  // If no command is given, go back to the prompt
         } else if (buffer[0] == 'e' && buffer[1] == 'x' && buffer[2] == 'i' && buffer[3] == 't') {
             syscall_exit(0);  // Exit the shell
+        } else if (buffer[0] == 'c' && buffer[1] == 'd' && (buffer[2] == ' ' || buffer[2] == '\0')) {
+            // the working directory is the shell's own, a child could not change it
+            char *dir = buffer[2] ? buffer + 3 : "/";
+            if (syscall_chdir(dir) < 0) {
+                syscall_write(1, "No such directory\r\n", 19);
+            }
+            continue;
+        } else if (buffer[0] == 'p' && buffer[1] == 'w' && buffer[2] == 'd' && buffer[3] == '\0') {
+            long len = syscall_getcwd(buffer, BUF_SIZE);
+            if (len > 0) {
+                syscall_write(1, buffer, len - 1);
+                syscall_write(1, "\r\n", 2);
+            }
+            continue;
         }
 
         // Fork the process
         long pid = syscall_fork();
         if (pid == 0) {
+            // every command runs as a job of its own in the foreground
+            syscall_setpgid(0, 0);
+            set_foreground(syscall_getpid());
+            set_job_signals(SIG_DFL);
             char* p;
             for (p = buffer; *p && (*p == ' ' || *p == '\t'); p++);
             int empty = (*p == '\0');  // Check if the command is empty
@@ -52,8 +90,11 @@ int _start() {
             }
             
         } else {
-            // In parent process, wait for the child to complete
-            syscall_waitpid(pid, NULL, 0);
+            // In parent process, wait for the child to complete or stop
+            syscall_setpgid(pid, pid);
+            set_foreground(pid);
+            syscall_waitpid(pid, NULL, WUNTRACED);
+            set_foreground(shell_pgid);
         }
     }
 
@@ -0,0 +1,43 @@
+#include "syscall_test.h"
+
+static volatile int sigint_caught = 0;
+
+static void sigint_handler(int sig) {
+    const char msg[] = "Caught SIGINT\n";
+    syscall_write(1, msg, sizeof(msg) - 1);
+    sigint_caught = 1;
+}
+
+static void sigsegv_handler(int sig) {
+    const char msg[] = "Caught SIGSEGV, exiting\n";
+    syscall_write(1, msg, sizeof(msg) - 1);
+    // returning would fault again on the same instruction
+    syscall_exit(0);
+}
+
+int _start() {
+    struct sigaction act = { sigint_handler, 0, 0 };
+    syscall_sigaction(SIGINT, &act, NULL);
+    act.sa_handler = sigsegv_handler;
+    syscall_sigaction(SIGSEGV, &act, NULL);
+
+    // a blocked signal stays pending until it is unblocked
+    unsigned long mask = 1UL << (SIGINT - 1);
+    syscall_sigprocmask(SIG_BLOCK, &mask, NULL);
+    syscall_kill(syscall_getpid(), SIGINT);
+    if (!sigint_caught) {
+        const char msg[] = "SIGINT is pending while blocked\n";
+        syscall_write(1, msg, sizeof(msg) - 1);
+    }
+    syscall_sigprocmask(SIG_UNBLOCK, &mask, NULL);
+    if (sigint_caught) {
+        const char msg[] = "Back from SIGINT handler\n";
+        syscall_write(1, msg, sizeof(msg) - 1);
+    }
+
+    // touch an unmapped page
+    *(volatile int *)0x10 = 1;
+
+    syscall_exit(-1);
+    return 0;
+}
@@ -0,0 +1,58 @@
+#include "syscall_test.h"
+
+static long now_ms() {
+    struct timeval tv;
+    syscall_gettimeofday(&tv);
+    return tv.tv_sec * 1000 + tv.tv_usec / 1000;
+}
+
+#define THREADS 4
+#define SPINS 20000000L
+
+static char stacks[THREADS][4096] __attribute__((aligned(16)));
+static volatile int ctid[THREADS];
+static volatile long total = 0;
+
+static int spin(void *arg) {
+    for (volatile long i = 0; i < SPINS; i++) {
+    }
+    __atomic_fetch_add(&total, (long)arg, __ATOMIC_SEQ_CST);
+    return 0;
+}
+
+static void join(int i) {
+    int tid;
+    while ((tid = ctid[i]) != 0) {
+        syscall_futex((int *)&ctid[i], FUTEX_WAIT, tid, NULL, NULL, 0);
+    }
+}
+
+// Runs THREADS busy threads at once; with `-smp 4` they take about as long
+// as one of them alone, on a single hart THREADS times as long.
+int _start() {
+    long start = now_ms();
+    spin((void *)1);
+    long single = now_ms() - start;
+
+    start = now_ms();
+    for (int i = 0; i < THREADS; i++) {
+        ctid[i] = -1;
+        syscall_clone(CLONE_VM | CLONE_FILES | CLONE_SIGHAND | CLONE_THREAD | CLONE_CHILD_CLEARTID,
+                      stacks[i] + 4096, NULL, 0, (int *)&ctid[i], spin, (void *)1);
+    }
+    for (int i = 0; i < THREADS; i++) {
+        join(i);
+    }
+    long parallel = now_ms() - start;
+
+    if (total == THREADS + 1) {
+        print("all threads finished\n");
+    }
+    if (parallel * 2 < single * THREADS) {
+        print("threads ran in parallel\n");
+    } else {
+        print("threads ran one after another\n");
+    }
+    syscall_exit(0);
+    return 0;
+}
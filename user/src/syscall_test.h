@@ -1,15 +1,571 @@
+#define SYSCALL_GETCWD  17
+#define SYSCALL_DUP     23
+#define SYSCALL_DUP3    24
+#define SYSCALL_FCNTL   25
+#define SYSCALL_IOCTL   29
+#define SYSCALL_MKDIRAT 34
+#define SYSCALL_UNLINKAT 35
+#define SYSCALL_LINKAT  37
+#define SYSCALL_CHDIR   49
+#define SYSCALL_FCHDIR  50
+#define SYSCALL_OPENAT  56
+#define SYSCALL_CLOSE   57
+#define SYSCALL_PIPE2   59
+#define SYSCALL_GETDENTS64 61
+#define SYSCALL_LSEEK   62
 #define SYSCALL_READ    63
 #define SYSCALL_WRITE   64
+#define SYSCALL_READV   65
+#define SYSCALL_WRITEV  66
+#define SYSCALL_PREAD64 67
+#define SYSCALL_PWRITE64 68
+#define SYSCALL_PSELECT6 72
+#define SYSCALL_PPOLL   73
+#define SYSCALL_FSTAT   80
 #define SYSCALL_EXIT    93
-#define SYSCALL_FORK    220
+#define SYSCALL_FUTEX   98
+#define SYSCALL_CLONE   220
 #define SYSCALL_EXECVE  221
-#define SYSCALL_WAITPID 260
+#define SYSCALL_WAIT4   260
 #define SYSCALL_YIELD   124
+#define SYSCALL_KILL    129
+#define SYSCALL_SIGACTION   134
+#define SYSCALL_SIGPROCMASK 135
+#define SYSCALL_SET_PRIORITY 140
+#define SYSCALL_SETPGID 154
+#define SYSCALL_GETPGID 155
+#define SYSCALL_GETTIMEOFDAY 169
+#define SYSCALL_GETPID  172
+#define SYSCALL_THREAD_CREATE 460
+#define SYSCALL_WAITTID 462
+
+#define SIGINT  2
+#define SIGCHLD 17
+#define SIGSEGV 11
+#define SIGPIPE 13
+#define SIGCONT 18
+#define SIGTSTP 20
+
+#define SIG_DFL ((void (*)(int))0)
+#define SIG_IGN ((void (*)(int))1)
+
+#define AT_FDCWD  -100
+#define AT_REMOVEDIR 0x200
+
+#define O_RDONLY  0
+#define O_WRONLY  1
+#define O_RDWR    2
+#define O_CREAT   0100
+#define O_EXCL    0200
+#define O_TRUNC   01000
+#define O_APPEND  02000
+#define O_NONBLOCK 04000
+#define O_DIRECTORY 0200000
+#define O_CLOEXEC 02000000
+
+#define F_DUPFD  0
+#define F_GETFD  1
+#define F_SETFD  2
+#define F_GETFL  3
+#define F_SETFL  4
+#define F_DUPFD_CLOEXEC 1030
+#define FD_CLOEXEC 1
+#define F_SETPIPE_SZ 1031
+#define F_GETPIPE_SZ 1032
+
+#define TCGETS     0x5401
+#define TCSETS     0x5402
+#define TIOCGPGRP  0x540f
+#define TIOCSPGRP  0x5410
+#define TIOCGWINSZ 0x5413
+#define TIOCGPTN   0x80045430
+
+#define POLLIN   0x001
+#define POLLPRI  0x002
+#define POLLOUT  0x004
+#define POLLERR  0x008
+#define POLLHUP  0x010
+#define POLLNVAL 0x020
+
+#define ICANON 0002
+#define ECHO   0010
+#define VMIN   6
+#define NCCS   19
+
+#define WNOHANG   1
+#define WUNTRACED 2
+
+#define SIG_BLOCK   0
+#define SIG_UNBLOCK 1
+#define SIG_SETMASK 2
+
+#define CLONE_VM             0x00000100
+#define CLONE_FILES          0x00000400
+#define CLONE_SIGHAND        0x00000800
+#define CLONE_THREAD         0x00010000
+#define CLONE_SETTLS         0x00080000
+#define CLONE_PARENT_SETTID  0x00100000
+#define CLONE_CHILD_CLEARTID 0x00200000
+
+#define FUTEX_WAIT         0
+#define FUTEX_WAKE         1
+#define FUTEX_REQUEUE      3
+#define FUTEX_PRIVATE_FLAG 128
+
+#define EPERM   1
+#define ENOENT  2
+#define EINTR   4
+#define EIO     5
+#define ENXIO   6
+#define E2BIG   7
+#define ENOEXEC 8
+#define EBADF   9
+#define ECHILD  10
+#define EAGAIN  11
+#define EFAULT  14
+#define EBUSY   16
+#define EEXIST  17
+#define ENOTDIR 20
+#define EISDIR  21
+#define EINVAL  22
+#define EMFILE  24
+#define ENOTTY  25
+#define ESPIPE  29
+#define EPIPE   32
+#define ERANGE  34
+#define ETIMEDOUT 110
+
+#define AT_NULL   0
+#define AT_PHDR   3
+#define AT_PHNUM  5
+#define AT_PAGESZ 6
+#define AT_ENTRY  9
+#define AT_HWCAP  16
+#define AT_RANDOM 25
 
 #define BUF_SIZE 128
 
 #define NULL ((void*)0)
 
+#define DT_DIR 4
+#define DT_REG 8
+
+#define SEEK_SET  0
+#define SEEK_CUR  1
+#define SEEK_END  2
+#define SEEK_DATA 3
+#define SEEK_HOLE 4
+
+struct timeval {
+    long tv_sec;
+    long tv_usec;
+};
+
+struct timespec {
+    long tv_sec;
+    long tv_nsec;
+};
+
+struct pollfd {
+    int fd;
+    short events;
+    short revents;
+};
+
+// the kernel's stat: the fields it fills, then padding
+struct stat {
+    unsigned long st_dev;
+    unsigned long st_ino;
+    unsigned int st_mode;
+    unsigned int st_nlink;
+    unsigned long pad[7];
+};
+
+struct iovec {
+    void *iov_base;
+    unsigned long iov_len;
+};
+
+struct linux_dirent64 {
+    unsigned long d_ino;
+    long d_off;
+    unsigned short d_reclen;
+    unsigned char d_type;
+    char d_name[];
+};
+
+struct termios {
+    unsigned int c_iflag;
+    unsigned int c_oflag;
+    unsigned int c_cflag;
+    unsigned int c_lflag;
+    unsigned char c_line;
+    unsigned char c_cc[NCCS];
+};
+
+struct winsize {
+    unsigned short ws_row;
+    unsigned short ws_col;
+    unsigned short ws_xpixel;
+    unsigned short ws_ypixel;
+};
+
+struct rusage {
+    struct timeval ru_utime;
+    struct timeval ru_stime;
+    long ru_rest[14];
+};
+
+// Layout of the riscv64 kernel `struct sigaction`
+struct sigaction {
+    void (*sa_handler)(int);
+    unsigned long sa_flags;
+    unsigned long sa_mask;
+};
+
+// Inline assembly for `dup` syscall
+static inline long syscall_dup(int fd) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[fd]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_DUP), [fd] "r" (fd)
+        : "a0", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `ioctl` syscall
+static inline long syscall_ioctl(int fd, unsigned long cmd, void *arg) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[fd]\n"
+        "mv a1, %[cmd]\n"
+        "mv a2, %[arg]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_IOCTL), [fd] "r" (fd), [cmd] "r" (cmd), [arg] "r" (arg)
+        : "a0", "a1", "a2", "a7", "memory"
+    );
+    return ret;
+}
+
+// Inline assembly for `openat` syscall
+static inline long syscall_openat(int dirfd, const char *path, int flags, int mode) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[dirfd]\n"
+        "mv a1, %[path]\n"
+        "mv a2, %[flags]\n"
+        "mv a3, %[mode]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_OPENAT), [dirfd] "r" (dirfd), [path] "r" (path), [flags] "r" (flags), [mode] "r" (mode)
+        : "a0", "a1", "a2", "a3", "a7"
+    );
+    return ret;
+}
+
+static inline long syscall_open(const char *path, int flags) {
+    return syscall_openat(AT_FDCWD, path, flags, 0);
+}
+
+// Inline assembly for `close` syscall
+static inline long syscall_close(int fd) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[fd]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_CLOSE), [fd] "r" (fd)
+        : "a0", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `pipe2` syscall
+static inline long syscall_pipe2(int fds[2], int flags) {
+    register long a0 asm("a0") = (long)fds;
+    register long a1 asm("a1") = flags;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_PIPE2), "r" (a1)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+static inline long syscall_pipe(int fds[2]) {
+    return syscall_pipe2(fds, 0);
+}
+
+// Inline assembly for `dup3` syscall
+static inline long syscall_dup3(int oldfd, int newfd, int flags) {
+    register long a0 asm("a0") = oldfd;
+    register long a1 asm("a1") = newfd;
+    register long a2 asm("a2") = flags;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_DUP3), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `fcntl` syscall
+static inline long syscall_fcntl(int fd, int cmd, long arg) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = cmd;
+    register long a2 asm("a2") = arg;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_FCNTL), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `ppoll` syscall
+static inline long syscall_ppoll(struct pollfd *fds, unsigned long nfds, const struct timespec *timeout,
+                                 const unsigned long *sigmask) {
+    register long a0 asm("a0") = (long)fds;
+    register long a1 asm("a1") = nfds;
+    register long a2 asm("a2") = (long)timeout;
+    register long a3 asm("a3") = (long)sigmask;
+    register long a4 asm("a4") = sizeof(unsigned long);
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_PPOLL), "r" (a1), "r" (a2), "r" (a3), "r" (a4)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `pselect6` syscall, the sets are bitmaps of 64-bit words
+static inline long syscall_pselect6(int nfds, unsigned long *readfds, unsigned long *writefds,
+                                    unsigned long *exceptfds, const struct timespec *timeout) {
+    register long a0 asm("a0") = nfds;
+    register long a1 asm("a1") = (long)readfds;
+    register long a2 asm("a2") = (long)writefds;
+    register long a3 asm("a3") = (long)exceptfds;
+    register long a4 asm("a4") = (long)timeout;
+    register long a5 asm("a5") = 0;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_PSELECT6), "r" (a1), "r" (a2), "r" (a3), "r" (a4), "r" (a5)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `getcwd` syscall
+static inline long syscall_getcwd(char *buf, unsigned long size) {
+    register long a0 asm("a0") = (long)buf;
+    register long a1 asm("a1") = size;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_GETCWD), "r" (a1)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `mkdirat` syscall
+static inline long syscall_mkdirat(int dirfd, const char *path, int mode) {
+    register long a0 asm("a0") = dirfd;
+    register long a1 asm("a1") = (long)path;
+    register long a2 asm("a2") = mode;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_MKDIRAT), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `unlinkat` syscall
+static inline long syscall_unlinkat(int dirfd, const char *path, int flags) {
+    register long a0 asm("a0") = dirfd;
+    register long a1 asm("a1") = (long)path;
+    register long a2 asm("a2") = flags;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_UNLINKAT), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `fstat` syscall
+static inline long syscall_fstat(int fd, struct stat *st) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = (long)st;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_FSTAT), "r" (a1)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `linkat` syscall
+static inline long syscall_linkat(int olddirfd, const char *oldpath, int newdirfd, const char *newpath,
+                                  int flags) {
+    register long a0 asm("a0") = olddirfd;
+    register long a1 asm("a1") = (long)oldpath;
+    register long a2 asm("a2") = newdirfd;
+    register long a3 asm("a3") = (long)newpath;
+    register long a4 asm("a4") = flags;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_LINKAT), "r" (a1), "r" (a2), "r" (a3), "r" (a4)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `chdir` syscall
+static inline long syscall_chdir(const char *path) {
+    register long a0 asm("a0") = (long)path;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_CHDIR)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `fchdir` syscall
+static inline long syscall_fchdir(int fd) {
+    register long a0 asm("a0") = fd;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_FCHDIR)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `getdents64` syscall
+static inline long syscall_getdents64(int fd, void *dirp, unsigned long count) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = (long)dirp;
+    register long a2 asm("a2") = count;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_GETDENTS64), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+static inline long syscall_lseek(int fd, long offset, int whence) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = offset;
+    register long a2 asm("a2") = whence;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_LSEEK), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+static inline long syscall_pread64(int fd, void *buf, unsigned long count, long offset) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = (long)buf;
+    register long a2 asm("a2") = count;
+    register long a3 asm("a3") = offset;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_PREAD64), "r" (a1), "r" (a2), "r" (a3)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+static inline long syscall_pwrite64(int fd, const void *buf, unsigned long count, long offset) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = (long)buf;
+    register long a2 asm("a2") = count;
+    register long a3 asm("a3") = offset;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_PWRITE64), "r" (a1), "r" (a2), "r" (a3)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+static inline long syscall_readv(int fd, const struct iovec *iov, int iovcnt) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = (long)iov;
+    register long a2 asm("a2") = iovcnt;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_READV), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+static inline long syscall_writev(int fd, const struct iovec *iov, int iovcnt) {
+    register long a0 asm("a0") = fd;
+    register long a1 asm("a1") = (long)iov;
+    register long a2 asm("a2") = iovcnt;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_WRITEV), "r" (a1), "r" (a2)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
 // Inline assembly for `read` syscall
 static inline long syscall_read(int fd, char *buf, long count) {
     long ret;
@@ -44,20 +600,70 @@ static inline long syscall_write(int fd, const char *buf, long count) {
     return ret;
 }
 
-// Inline assembly for `fork` syscall
+// `fork` is `clone` with SIGCHLD as exit signal and no flags
 static inline long syscall_fork() {
     long ret;
     asm volatile (
         "mv a7, %[syscall_num]\n"
+        "mv a0, %[flags]\n"
+        "mv a1, zero\n"
         "ecall\n"
         "mv %[ret], a0\n"
         : [ret] "=r" (ret)
-        : [syscall_num] "r" (SYSCALL_FORK)
-        : "a0", "a7"
+        : [syscall_num] "r" (SYSCALL_CLONE), [flags] "r" ((long)SIGCHLD)
+        : "a0", "a1", "a7"
     );
     return ret;
 }
 
+// Inline assembly for `clone` syscall, the child calls `fn(arg)` on `stack`
+// and exits with its return value
+static inline long syscall_clone(unsigned long flags, void *stack, int *ptid, unsigned long tls,
+                                 int *ctid, int (*fn)(void *), void *arg) {
+    register long a0 asm("a0") = flags;
+    register long a1 asm("a1") = (long)stack;
+    register long a2 asm("a2") = (long)ptid;
+    register long a3 asm("a3") = tls;
+    register long a4 asm("a4") = (long)ctid;
+    register long t0 asm("t0") = (long)fn;
+    register long t1 asm("t1") = (long)arg;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        "bnez a0, 1f\n"
+        // child: the registers are those of the parent, but the stack is new
+        "mv a0, t1\n"
+        "jalr t0\n"
+        "li a7, %[exit_num]\n"
+        "ecall\n"
+        "1:\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_CLONE), [exit_num] "i" (SYSCALL_EXIT),
+          "r" (a1), "r" (a2), "r" (a3), "r" (a4), "r" (t0), "r" (t1)
+        : "a7", "ra", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `futex` syscall
+static inline long syscall_futex(int *uaddr, int op, int val, const struct timespec *timeout,
+                                 int *uaddr2, int val3) {
+    register long a0 asm("a0") = (long)uaddr;
+    register long a1 asm("a1") = op;
+    register long a2 asm("a2") = val;
+    register long a3 asm("a3") = (long)timeout;
+    register long a4 asm("a4") = (long)uaddr2;
+    register long a5 asm("a5") = val3;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_FUTEX), "r" (a1), "r" (a2), "r" (a3), "r" (a4), "r" (a5)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
 // Inline assembly for `execve` syscall
 static inline long syscall_execve(const char *path, char *const argv[], char *const envp[]) {
     long ret;
@@ -75,23 +681,28 @@ static inline long syscall_execve(const char *path, char *const argv[], char *co
     return ret;
 }
 
-// Inline assembly for `waitpid` syscall
-static inline long syscall_waitpid(long pid, int *wstatus, int options) {
+// Inline assembly for `wait4` syscall
+static inline long syscall_wait4(long pid, int *wstatus, int options, struct rusage *rusage) {
     long ret;
     asm volatile (
         "mv a7, %[syscall_num]\n"
         "mv a0, %[pid]\n"
         "mv a1, %[wstatus]\n"
         "mv a2, %[options]\n"
+        "mv a3, %[rusage]\n"
         "ecall\n"
         "mv %[ret], a0\n"
         : [ret] "=r" (ret)
-        : [syscall_num] "r" (SYSCALL_WAITPID), [pid] "r" (pid), [wstatus] "r" (wstatus), [options] "r" (options)
-        : "a0", "a1", "a2", "a7"
+        : [syscall_num] "r" (SYSCALL_WAIT4), [pid] "r" (pid), [wstatus] "r" (wstatus), [options] "r" (options), [rusage] "r" (rusage)
+        : "a0", "a1", "a2", "a3", "a7"
     );
     return ret;
 }
 
+static inline long syscall_waitpid(long pid, int *wstatus, int options) {
+    return syscall_wait4(pid, wstatus, options, NULL);
+}
+
 // Inline assembly for `exit` syscall
 static inline void syscall_exit(int exit_code) {
     asm volatile (
@@ -113,4 +724,173 @@ static inline void syscall_yield() {
         : [syscall_num] "r" (SYSCALL_YIELD)
         : "a7"
     );
-}
\ No newline at end of file
+}
+
+// Inline assembly for `getpid` syscall
+static inline long syscall_getpid() {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_GETPID)
+        : "a0", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `setpgid` syscall
+static inline long syscall_setpgid(long pid, long pgid) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[pid]\n"
+        "mv a1, %[pgid]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_SETPGID), [pid] "r" (pid), [pgid] "r" (pgid)
+        : "a0", "a1", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `getpgid` syscall
+static inline long syscall_getpgid(long pid) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[pid]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_GETPGID), [pid] "r" (pid)
+        : "a0", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `kill` syscall
+static inline long syscall_kill(long pid, int sig) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[pid]\n"
+        "mv a1, %[sig]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_KILL), [pid] "r" (pid), [sig] "r" (sig)
+        : "a0", "a1", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `rt_sigaction` syscall
+static inline long syscall_sigaction(int sig, const struct sigaction *act, struct sigaction *oldact) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[sig]\n"
+        "mv a1, %[act]\n"
+        "mv a2, %[oldact]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_SIGACTION), [sig] "r" (sig), [act] "r" (act), [oldact] "r" (oldact)
+        : "a0", "a1", "a2", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `rt_sigprocmask` syscall
+static inline long syscall_sigprocmask(int how, const unsigned long *set, unsigned long *oldset) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[how]\n"
+        "mv a1, %[set]\n"
+        "mv a2, %[oldset]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_SIGPROCMASK), [how] "r" (how), [set] "r" (set), [oldset] "r" (oldset)
+        : "a0", "a1", "a2", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `set_priority` syscall
+static inline long syscall_set_priority(long prio) {
+    register long a0 asm("a0") = prio;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_SET_PRIORITY)
+        : "a7"
+    );
+    return a0;
+}
+
+// Inline assembly for `gettimeofday` syscall
+static inline long syscall_gettimeofday(struct timeval *tv) {
+    register long a0 asm("a0") = (long)tv;
+    register long a1 asm("a1") = 0;
+    asm volatile (
+        "li a7, %[syscall_num]\n"
+        "ecall\n"
+        : "+r" (a0)
+        : [syscall_num] "i" (SYSCALL_GETTIMEOFDAY), "r" (a1)
+        : "a7", "memory"
+    );
+    return a0;
+}
+
+// Inline assembly for `thread_create` syscall, `entry` runs with `arg` in a0
+static inline long syscall_thread_create(void (*entry)(long), long arg) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[entry]\n"
+        "mv a1, %[arg]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_THREAD_CREATE), [entry] "r" (entry), [arg] "r" (arg)
+        : "a0", "a1", "a7"
+    );
+    return ret;
+}
+
+// Inline assembly for `waittid` syscall
+static inline long syscall_waittid(long tid, int *exit_code) {
+    long ret;
+    asm volatile (
+        "mv a7, %[syscall_num]\n"
+        "mv a0, %[tid]\n"
+        "mv a1, %[exit_code]\n"
+        "ecall\n"
+        "mv %[ret], a0\n"
+        : [ret] "=r" (ret)
+        : [syscall_num] "r" (SYSCALL_WAITTID), [tid] "r" (tid), [exit_code] "r" (exit_code)
+        : "a0", "a1", "a7"
+    );
+    return ret;
+}
+
+// Write a string to stdout
+static inline void print(const char *msg) {
+    long len = 0;
+    while (msg[len]) {
+        len++;
+    }
+    syscall_write(1, msg, len);
+}
+
+// Report whether the check `what` passed
+static inline void check(const char *what, int ok) {
+    print(what);
+    print(ok ? ": ok\n" : ": FAILED\n");
+}
@@ -0,0 +1,28 @@
+#include "syscall_test.h"
+
+static void worker(long arg) {
+    int status = 0;
+    long pid = syscall_fork();
+    if (pid == 0) {
+        // only this thread made it into the child
+        print("forked child runs on the stack of the forking thread\n");
+        syscall_exit(5);
+    }
+    if (syscall_wait4(pid, &status, 0, NULL) == pid && status == (5 << 8)) {
+        print("fork from a second thread reaped the child\n");
+    }
+    // the main thread is still spinning, exec has to take it down
+    char *argv[] = {"/bin/hello_syscall.elf", NULL};
+    char *envp[] = {NULL};
+    syscall_execve(argv[0], argv, envp);
+    print("execve failed\n");
+    syscall_exit(1);
+}
+
+int _start() {
+    syscall_thread_create(worker, 0);
+    while (1) {
+        syscall_yield();
+    }
+    return 0;
+}
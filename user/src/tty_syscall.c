@@ -0,0 +1,37 @@
+#include "syscall_test.h"
+
+// Checks the terminal ioctls on the console; typing is left to the shell.
+int _start() {
+    struct termios saved, raw, now;
+    check("TCGETS", syscall_ioctl(0, TCGETS, &saved) == 0);
+    check("canonical with echo by default", (saved.c_lflag & (ICANON | ECHO)) == (ICANON | ECHO));
+
+    raw = saved;
+    raw.c_lflag &= ~(ICANON | ECHO);
+    raw.c_cc[VMIN] = 1;
+    check("TCSETS raw", syscall_ioctl(0, TCSETS, &raw) == 0);
+    syscall_ioctl(0, TCGETS, &now);
+    check("raw mode read back", (now.c_lflag & (ICANON | ECHO)) == 0);
+    check("TCSETS restore", syscall_ioctl(0, TCSETS, &saved) == 0);
+
+    struct winsize ws;
+    check("TIOCGWINSZ", syscall_ioctl(1, TIOCGWINSZ, &ws) == 0 && ws.ws_row > 0 && ws.ws_col > 0);
+
+    long pgid = syscall_getpgid(0);
+    int fg = (int)pgid;
+    check("TIOCSPGRP", syscall_ioctl(0, TIOCSPGRP, &fg) == 0);
+    fg = -1;
+    check("TIOCGPGRP", syscall_ioctl(0, TIOCGPGRP, &fg) == 0 && fg == pgid);
+
+    check("setpgid to a group of our own", syscall_setpgid(0, 0) == 0 && syscall_getpgid(0) == syscall_getpid());
+    check("a negative pgid is EINVAL", syscall_setpgid(0, -1) == -EINVAL);
+    check("joining a missing group is EPERM", syscall_setpgid(0, 99999) == -EPERM);
+    check("writing to stdin is EBADF", syscall_write(0, "x", 1) == -EBADF);
+
+    int fd = syscall_open("/tty_test.txt", O_CREAT | O_RDWR);
+    check("ioctl on a file is ENOTTY", syscall_ioctl(fd, TCGETS, &now) == -ENOTTY);
+    syscall_close(fd);
+    check("bad termios pointer is EFAULT", syscall_ioctl(0, TCGETS, NULL) == -EFAULT);
+    syscall_exit(0);
+    return 0;
+}
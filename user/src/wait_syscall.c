@@ -0,0 +1,63 @@
+#include "syscall_test.h"
+
+int _start() {
+    int status = 0;
+    struct rusage usage;
+
+    long pid = syscall_fork();
+    if (pid == 0) {
+        // give the parent time to find us running
+        for (int i = 0; i < 10; i++) {
+            syscall_yield();
+        }
+        syscall_exit(3);
+    }
+    if (syscall_wait4(pid, &status, WNOHANG, NULL) == 0) {
+        print("WNOHANG returns 0 while the child runs\n");
+    }
+    // sleeps until the child exits
+    if (syscall_wait4(-1, &status, 0, &usage) == pid && status == (3 << 8)) {
+        print("wait4 reaped the child with exit code 3\n");
+    }
+    if (syscall_wait4(-1, &status, 0, NULL) == -ECHILD) {
+        print("wait4 returns -ECHILD without children\n");
+    }
+
+    pid = syscall_fork();
+    if (pid == 0) {
+        *(volatile int *)0x10 = 1;
+        syscall_exit(0);
+    }
+    if (syscall_wait4(pid, &status, 0, NULL) == pid && (status & 0x7f) == SIGSEGV) {
+        print("wait4 reports the child killed by SIGSEGV\n");
+    }
+
+    pid = syscall_fork();
+    if (pid == 0) {
+        syscall_setpgid(0, 0);
+        syscall_exit(5);
+    }
+    // like a shell, set the group from both sides so that neither has to wait
+    syscall_setpgid(pid, pid);
+    if (syscall_wait4(0, &status, WNOHANG, NULL) == -ECHILD) {
+        print("wait4(0) skips a child in another process group\n");
+    }
+    if (syscall_wait4(-pid, &status, 0, NULL) == pid && status == (5 << 8)) {
+        print("wait4(-pgid) reaps a child of that group\n");
+    }
+
+    pid = syscall_fork();
+    if (pid == 0) {
+        syscall_kill(syscall_getpid(), SIGTSTP);
+        syscall_exit(7);
+    }
+    if (syscall_wait4(pid, &status, WUNTRACED, NULL) == pid && status == ((SIGTSTP << 8) | 0x7f)) {
+        print("wait4 reports the child stopped by SIGTSTP\n");
+    }
+    syscall_kill(pid, SIGCONT);
+    if (syscall_wait4(pid, &status, 0, NULL) == pid && status == (7 << 8)) {
+        print("SIGCONT resumes the stopped child\n");
+    }
+    syscall_exit(0);
+    return 0;
+}